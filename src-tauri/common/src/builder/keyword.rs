@@ -1,17 +1,18 @@
 use crate::types::enums::{ClipboardTextType, ClipboardType, Language};
 use std::collections::HashMap;
 
+pub type ClipboardKeywords = HashMap<Language, HashMap<ClipboardType, Vec<String>>>;
+pub type TextKeywords = HashMap<Language, HashMap<ClipboardTextType, Vec<String>>>;
+
+#[derive(Default)]
 pub struct KeywordBuilder {
-    clipboard_keywords: HashMap<Language, HashMap<ClipboardType, Vec<String>>>,
-    text_keywords: HashMap<Language, HashMap<ClipboardTextType, Vec<String>>>,
+    clipboard_keywords: ClipboardKeywords,
+    text_keywords: TextKeywords,
 }
 
 impl KeywordBuilder {
     pub fn new() -> Self {
-        Self {
-            clipboard_keywords: HashMap::new(),
-            text_keywords: HashMap::new(),
-        }
+        Self::default()
     }
 
     pub fn add_type(mut self, lang: Language, type_: ClipboardType, keywords: &[&str]) -> Self {
@@ -35,19 +36,14 @@ impl KeywordBuilder {
         self
     }
 
-    pub fn build(
-        self,
-    ) -> (
-        HashMap<Language, HashMap<ClipboardType, Vec<String>>>,
-        HashMap<Language, HashMap<ClipboardTextType, Vec<String>>>,
-    ) {
+    pub fn build(self) -> (ClipboardKeywords, TextKeywords) {
         (self.clipboard_keywords, self.text_keywords)
     }
 
     pub fn find_clipboard_type(
         term: &str,
         lang: &Language,
-        keywords: &HashMap<Language, HashMap<ClipboardType, Vec<String>>>,
+        keywords: &ClipboardKeywords,
     ) -> Option<ClipboardType> {
         keywords.get(lang).and_then(|lang_keywords| {
             lang_keywords
@@ -60,7 +56,7 @@ impl KeywordBuilder {
     pub fn find_text_type(
         term: &str,
         lang: &Language,
-        keywords: &HashMap<Language, HashMap<ClipboardTextType, Vec<String>>>,
+        keywords: &TextKeywords,
     ) -> Option<ClipboardTextType> {
         keywords.get(lang).and_then(|lang_keywords| {
             lang_keywords
@@ -70,10 +66,7 @@ impl KeywordBuilder {
         })
     }
 
-    pub fn build_default() -> (
-        HashMap<Language, HashMap<ClipboardType, Vec<String>>>,
-        HashMap<Language, HashMap<ClipboardTextType, Vec<String>>>,
-    ) {
+    pub fn build_default() -> (ClipboardKeywords, TextKeywords) {
         let mut builder = KeywordBuilder::new();
 
         // English (en)
@@ -12,11 +12,38 @@ pub static GLOBAL_EVENTS: LazyLock<Vec<String>> = LazyLock::new(|| {
 pub static DB_NAME: &str = "clippy.sqlite";
 pub static CONFIG_NAME: &str = "config.json";
 pub static TOKEN_NAME: &str = "token.json";
-pub static CACHE_KEY: &str = "clipboards";
+pub static TOKEN_KEY_NAME: &str = "token_key"; // encrypts the token, created once per install
+pub static GOOGLE_REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+pub static WEBDAV_CREDENTIALS_NAME: &str = "webdav.json";
+pub static S3_CREDENTIALS_NAME: &str = "s3.json";
+pub static SERVER_CREDENTIALS_NAME: &str = "server.json";
+pub static SYNC_KEY_NAME: &str = "sync_key";
+pub static S3_DEFAULT_REGION: &str = "us-east-1";
 pub static ENCRYPTION_MAGIC_STRING: &str = "clippy";
+pub static SEARCH_INDEX_KEY_LABEL: &str = "clippy-search-index";
+pub static RECOVERY_KEY_LABEL: &str = "clippy-recovery";
+pub static KEY_ID_LABEL: &str = "clippy-key-id";
+pub static VAULT_CHECK_LABEL: &str = "clippy-vault-check";
+pub static SYNC_SEALED_SETTINGS_KEY: &str = "sealed";
+pub static SYNC_SETTINGS_SALT_KEY: &str = "salt"; // salt of the sync passphrase, next to the sealed settings
+pub static ENCRYPTION_KDF_ITERATIONS: u32 = 600_000;
+
+pub static JOB_BATCH_SIZE: u64 = 50;
 
 pub static BACKUP_SETTINGS_PREFIX: &str = "settings";
 pub static BACKUP_FILE_PREFIX: &str = "clippy";
+pub static SYNC_MANIFEST_NAME: &str = "manifest.json";
+pub static SYNC_HOTKEYS_KEY: &str = "hotkeys"; // synced along with the settings fields
+pub static BACKUP_CHUNK_PREFIX: &str = "clippy-chunk";
+pub static SYNC_MANIFEST_VERSION: u32 = 3; // keeps tombstones, older versions fall back to a full listing
+pub static SYNC_PAYLOAD_VERSION: u32 = 2; // zstd compressed JSON, version 1 is plain JSON
+pub static SYNC_COMPRESSION_LEVEL: i32 = 3;
+pub static SYNC_CHUNK_SIZE: usize = 4_194_304; // larger payloads are uploaded in chunks of this size
+pub static SYNC_MANIFEST_WRITE_ATTEMPTS: u32 = 3; // a manifest written meanwhile is read and merged again
+pub static SYNC_MANIFEST_LOCK_STALE_SECS: u64 = 30; // a lock file this old was left by a crashed writer
+pub static SYNC_DEVICE_SEEN_HOURS: i64 = 24; // how often a device refreshes its registry entry
+pub static SYNC_TOMBSTONE_RETENTION_DAYS: i64 = 30; // devices offline for longer may bring deleted clipboards back
+pub static DEVICE_NAME_DEFAULT: &str = "Clippy";
 pub static BACKDUP_DATE_FORMAT: &str = "%Y%m%d%H%M%S";
 
 pub static MAIN_WINDOW_X: i32 = 375;
@@ -31,11 +58,22 @@ pub static SETTINGS_WINDOW_Y: i32 = 600;
 pub static MAX_IMAGE_DIMENSIONS: u32 = 1280;
 pub static MAX_TEXT_PREVIEW: usize = 500; // Adjust preview length as needed
 
+pub static SEARCH_INDEX_NGRAM: usize = 3;
+
 pub static SYNC_LIMIT_SIZE_DEV: u64 = 10;
 pub static SYNC_LIMIT_SIZE_PROD: u64 = 100;
 pub static SYNC_LIMIT_SIZE_MIN: u64 = 0;
 pub static SYNC_LIMIT_SIZE_MAX: u64 = 250;
 
+pub static SYNC_PUSH_DEBOUNCE_MS: u64 = 1_500; // local changes within this window are pushed together
+pub static SYNC_PULL_INTERVAL_DEV: u64 = 10;
+pub static SYNC_PULL_INTERVAL_PROD: u64 = 30;
+pub static SYNC_PULL_INTERVAL_MAX: u64 = 900; // idle pulls back off up to 15 minutes
+pub static SYNC_OUTBOX_RETRY_MIN: i64 = 30; // failed pushes are retried after 30s, doubling each time
+pub static SYNC_OUTBOX_RETRY_MAX: i64 = 3_600;
+pub static SYNC_RETRY_ATTEMPTS: u32 = 3; // transient provider errors are retried within one request
+pub static SYNC_RETRY_DELAY_MS: u64 = 500; // doubled on every attempt
+
 pub static DISPLAY_SCALE: f32 = 1.0;
 pub static DISPLAY_SCALE_MIN: f32 = 0.5;
 pub static DISPLAY_SCALE_MAX: f32 = 2.0;
@@ -59,3 +97,7 @@ pub static MAX_RTF_SIZE_MAX: u32 = 104_857_600;
 pub static MAX_HTML_SIZE: u32 = 10_485_760;
 pub static MAX_HTML_SIZE_MIN: u32 = 0;
 pub static MAX_HTML_SIZE_MAX: u32 = 104_857_600;
+
+pub static SYNC_MAX_SIZE: u32 = 10_485_760; // larger clipboards stay local
+pub static SYNC_MAX_SIZE_MIN: u32 = 0;
+pub static SYNC_MAX_SIZE_MAX: u32 = 104_857_600;
@@ -1,30 +1,36 @@
-use crate::{constants::MAX_TEXT_PREVIEW, types::orm_query::FullClipboardDto};
+use crate::{
+    constants::MAX_TEXT_PREVIEW,
+    types::{cipher::replace_zeroized, orm_query::FullClipboardDto},
+};
 use tl::{parse, ParserOptions};
 
 pub fn trim_clipboard_data(mut clipboards: Vec<FullClipboardDto>) -> Vec<FullClipboardDto> {
     for clipboard in &mut clipboards {
         if let Some(text) = &mut clipboard.text {
-            text.data = truncate_text(&text.data, MAX_TEXT_PREVIEW);
+            let preview = truncate_text(&text.data, MAX_TEXT_PREVIEW);
+            replace_zeroized(&mut text.data, preview);
         }
 
         if let Some(html) = &mut clipboard.html {
-            html.data = extract_and_truncate_html_body(&html.data, MAX_TEXT_PREVIEW);
+            let preview = extract_and_truncate_html_body(&html.data, MAX_TEXT_PREVIEW);
+            replace_zeroized(&mut html.data, preview);
         }
 
         if let Some(rtf) = &mut clipboard.rtf {
-            rtf.data = truncate_text(&rtf.data, MAX_TEXT_PREVIEW);
+            let preview = truncate_text(&rtf.data, MAX_TEXT_PREVIEW);
+            replace_zeroized(&mut rtf.data, preview);
         }
 
         // Remove image binary data but keep metadata
         if let Some(image) = &mut clipboard.image {
-            image.data = Vec::new(); // Clear binary data
-                                     // Thumbnail, dimensions, size etc are preserved
+            // Clear binary data, thumbnail, dimensions, size etc are preserved
+            replace_zeroized(&mut image.data, Vec::new());
         }
 
         // Clear file binary data but keep metadata
         for file in &mut clipboard.files {
-            file.data = Vec::new(); // Clear binary data
-                                    // Name, extension, size etc are preserved
+            // Clear binary data, name, extension, size etc are preserved
+            replace_zeroized(&mut file.data, Vec::new());
         }
     }
 
@@ -1,7 +1,148 @@
-use std::{fmt, sync::Mutex};
+use serde::{Deserialize, Serialize};
+use std::{
+    alloc::{alloc_zeroed, Layout},
+    fmt,
+    ptr::NonNull,
+    sync::{Mutex, PoisonError},
+};
+use zeroize::{Zeroize, Zeroizing};
 
 // Global encryption key stored in memory
-pub static ENCRYPTION_KEY: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+pub static ENCRYPTION_KEY: Mutex<Option<SecretKey>> = Mutex::new(None);
+// Key of the vault entries, derived from a separate passphrase
+pub static VAULT_KEY: Mutex<Option<SecretKey>> = Mutex::new(None);
+// Key sync payloads are sealed with, derived from the sync passphrase
+pub static SYNC_KEY: Mutex<Option<SecretKey>> = Mutex::new(None);
+// Salt the sync key was derived with, shared by every device syncing
+pub static SYNC_SALT: Mutex<Option<String>> = Mutex::new(None);
+
+/// One page locked into memory once and shared by every key, each in its own slot.
+/// Locks are not counted per allocation, so keys on the same page can't lock it themselves
+struct KeyArena {
+    base: NonNull<[u8; 32]>,
+    slots: usize,
+    used: u128,
+    _lock: Option<region::LockGuard>,
+}
+
+// only reached through KEY_ARENA, every slot is owned by a single SecretKey
+unsafe impl Send for KeyArena {}
+
+static KEY_ARENA: Mutex<Option<KeyArena>> = Mutex::new(None);
+
+impl KeyArena {
+    fn new() -> Option<Self> {
+        let page = region::page::size();
+        let layout = Layout::from_size_align(page, page).ok()?;
+        // never freed, the arena lives as long as the app
+        let base = NonNull::new(unsafe { alloc_zeroed(layout) })?.cast::<[u8; 32]>();
+
+        // locking can fail if the memlock limit is reached, the keys are still usable
+        let lock = region::lock(base.as_ptr() as *const u8, page).ok();
+
+        Some(Self {
+            base,
+            slots: (page / 32).min(u128::BITS as usize),
+            used: 0,
+            _lock: lock,
+        })
+    }
+
+    fn take(&mut self) -> Option<(usize, NonNull<[u8; 32]>)> {
+        let index = (0..self.slots).find(|index| self.used & (1u128 << index) == 0)?;
+        self.used |= 1u128 << index;
+
+        // in bounds, the arena holds `slots` keys
+        let ptr = unsafe { NonNull::new_unchecked(self.base.as_ptr().add(index)) };
+        Some((index, ptr))
+    }
+
+    fn release(&mut self, index: usize) {
+        self.used &= !(1u128 << index);
+    }
+}
+
+enum KeySlot {
+    Arena(usize, NonNull<[u8; 32]>),
+    // the arena is full or could not be allocated, kept without a lock
+    Heap(Box<[u8; 32]>),
+}
+
+/// Key bytes kept in the locked key arena so they are never swapped to disk,
+/// and wiped when the key is dropped
+pub struct SecretKey {
+    slot: KeySlot,
+}
+
+// the arena slot is owned by this key alone
+unsafe impl Send for SecretKey {}
+
+impl SecretKey {
+    pub fn new(mut key_bytes: [u8; 32]) -> Self {
+        let slot = {
+            let mut arena = KEY_ARENA.lock().unwrap_or_else(PoisonError::into_inner);
+            if arena.is_none() {
+                *arena = KeyArena::new();
+            }
+            arena.as_mut().and_then(KeyArena::take)
+        };
+
+        let slot = match slot {
+            Some((index, ptr)) => {
+                unsafe { ptr.as_ptr().write(key_bytes) };
+                KeySlot::Arena(index, ptr)
+            }
+            None => KeySlot::Heap(Box::new(key_bytes)),
+        };
+        key_bytes.zeroize();
+
+        Self { slot }
+    }
+
+    /// Copy of the key bytes that is wiped when dropped
+    pub fn expose(&self) -> Zeroizing<[u8; 32]> {
+        match &self.slot {
+            KeySlot::Arena(_, ptr) => Zeroizing::new(unsafe { *ptr.as_ptr() }),
+            KeySlot::Heap(bytes) => Zeroizing::new(**bytes),
+        }
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        match &mut self.slot {
+            KeySlot::Arena(index, ptr) => {
+                unsafe { (*ptr.as_ptr()).zeroize() };
+                if let Some(arena) = KEY_ARENA
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .as_mut()
+                {
+                    arena.release(*index);
+                }
+            }
+            KeySlot::Heap(bytes) => bytes.zeroize(),
+        }
+    }
+}
+
+/// Replaces a value and wipes the previous one, e.g. plaintext overwritten by ciphertext
+pub fn replace_zeroized<T: Zeroize>(slot: &mut T, value: T) {
+    let mut previous = std::mem::replace(slot, value);
+    previous.zeroize();
+}
+
+/// The random data key the history is encrypted with, wrapped by the keys derived
+/// from the password and the optional recovery key. Stored base64 encoded in the settings
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptionKeys {
+    pub salt: String,
+    pub password: String,
+    pub recovery: Option<String>,
+    /// Fingerprint of the data key, tells a rewrapped key apart from another device's own key
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
 
 #[derive(Debug)]
 pub enum EncryptionError {
@@ -11,6 +152,9 @@ pub enum EncryptionError {
     NotEncrypted,
     EncryptionFailed,
     DecryptionFailed,
+    VaultLocked,
+    InvalidRecoveryKey,
+    SyncPassphraseRequired,
 }
 
 impl std::error::Error for EncryptionError {}
@@ -24,6 +168,11 @@ impl fmt::Display for EncryptionError {
             EncryptionError::NotEncrypted => write!(f, "MAIN.ERROR.DATA_IS_NOT_ENCRYPTED"),
             EncryptionError::EncryptionFailed => write!(f, "MAIN.ERROR.ENCRYPTION_FAILED"),
             EncryptionError::DecryptionFailed => write!(f, "MAIN.ERROR.DECRYPTION_FAILED"),
+            EncryptionError::VaultLocked => write!(f, "MAIN.ERROR.VAULT_LOCKED"),
+            EncryptionError::InvalidRecoveryKey => write!(f, "MAIN.ERROR.INVALID_RECOVERY_KEY"),
+            EncryptionError::SyncPassphraseRequired => {
+                write!(f, "MAIN.ERROR.SYNC_PASSPHRASE_REQUIRED")
+            }
         }
     }
 }
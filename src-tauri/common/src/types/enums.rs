@@ -45,6 +45,16 @@ pub enum ClippyPosition {
 pub enum SyncProviderType {
     #[iden = "google_drive"]
     GoogleDrive,
+    #[iden = "webdav"]
+    WebDav,
+    #[iden = "local_folder"]
+    LocalFolder,
+    #[iden = "s3"]
+    S3,
+    #[iden = "server"]
+    Server,
+    #[iden = "dry_run"]
+    DryRun,
 }
 
 #[derive(Iden, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone)]
@@ -120,6 +130,8 @@ pub enum ListenEvent {
     Progress,
     #[iden = "password_lock"]
     PasswordLock,
+    #[iden = "sync_status"]
+    SyncStatus,
 }
 
 #[derive(Iden, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone)]
@@ -224,6 +236,39 @@ pub enum ClipboardType {
     File,
 }
 
+#[derive(Iden, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    #[iden = "encrypt"]
+    Encrypt,
+    #[iden = "decrypt"]
+    Decrypt,
+}
+
+#[derive(Iden, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    #[iden = "running"]
+    Running,
+    #[iden = "completed"]
+    Completed,
+    #[iden = "cancelled"]
+    Cancelled,
+    #[iden = "failed"]
+    Failed,
+}
+
+#[derive(Iden, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncOperation {
+    #[iden = "upload"]
+    Upload,
+    #[iden = "star"]
+    Star,
+    #[iden = "delete"]
+    Delete,
+}
+
 #[derive(Iden, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum PasswordAction {
@@ -233,6 +278,8 @@ pub enum PasswordAction {
     Decrypt,
     #[iden = "sync_decrypt"]
     SyncDecrypt,
+    #[iden = "recover"]
+    Recover,
 }
 
 impl ClipboardType {
@@ -264,7 +311,7 @@ impl ClipboardType {
         }
     }
 
-    pub fn to_json_value(types: &Vec<Self>) -> JsonValue {
+    pub fn to_json_value(types: &[Self]) -> JsonValue {
         json!(types.iter().map(|t| t.to_string()).collect::<Vec<_>>())
     }
 }
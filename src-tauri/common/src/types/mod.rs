@@ -1,6 +1,7 @@
 pub mod enums;
 pub mod hotkey;
 pub mod orm_query;
+#[allow(clippy::module_inception)]
 pub mod types;
 pub mod sync;
 pub mod cipher;
\ No newline at end of file
@@ -3,6 +3,7 @@ use entity::{
     clipboard_file, clipboard_html, clipboard_image, clipboard_rtf, clipboard_text,
 };
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FullClipboardDto {
@@ -14,6 +15,31 @@ pub struct FullClipboardDto {
     pub files: Vec<clipboard_file::Model>,
 }
 
+/// Wipes the clipboard content, used for decrypted clipboards before they are dropped
+impl Zeroize for FullClipboardDto {
+    fn zeroize(&mut self) {
+        if let Some(text) = &mut self.text {
+            text.data.zeroize();
+        }
+        if let Some(html) = &mut self.html {
+            html.data.zeroize();
+        }
+        if let Some(rtf) = &mut self.rtf {
+            rtf.data.zeroize();
+        }
+        if let Some(image) = &mut self.image {
+            image.data.zeroize();
+            image.thumbnail.zeroize();
+        }
+        for file in &mut self.files {
+            file.data.zeroize();
+            file.name.zeroize();
+            file.extension.zeroize();
+            file.mime_type.zeroize();
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FullClipboardDbo {
     pub clipboard_model: entity::clipboard::ActiveModel,
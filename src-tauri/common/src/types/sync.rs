@@ -1,7 +1,9 @@
-use super::orm_query::FullClipboardDto;
+use super::{cipher::EncryptionError, orm_query::FullClipboardDto, types::CommandError};
+use crate::constants::{S3_DEFAULT_REGION, SYNC_SETTINGS_SALT_KEY};
 use chrono::NaiveDateTime;
-use google_drive3::{hyper_rustls, hyper_util, DriveHub};
+use google_drive3::DriveHub;
 use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
@@ -13,62 +15,500 @@ pub struct Clippy {
     pub encrypted: bool,
     pub created_at: NaiveDateTime,
     pub deleted_at: Option<NaiveDateTime>,
+    /// Payload format, see `SYNC_PAYLOAD_VERSION`
+    pub format: u32,
+    /// Parts the payload was split into, 1 if it is stored in one object
+    pub chunks: u32,
 }
 
 #[async_trait::async_trait]
 pub trait SyncProvider: Send + Sync {
-    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, Box<dyn std::error::Error>>;
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError>;
 
     async fn compare_and_fetch_new_clipboards(
         &self,
         local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
-        remote_clipboards: &Vec<Clippy>,
-    ) -> Result<Vec<FullClipboardDto>, Box<dyn std::error::Error>>;
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError>;
 
     async fn upload_new_clipboards(
         &self,
         new_local_clipboards: &[FullClipboardDto],
-        remote_clipboards: &Vec<Clippy>,
-    ) -> Result<Vec<Clippy>, Box<dyn std::error::Error>>;
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError>;
 
-    async fn mark_for_deletion(&self, clippy: &Clippy);
+    /// Returns the remote clipboard as it is stored after being marked
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError>;
 
-    async fn delete_clipboard(&self, clippy: &Clippy);
+    /// Removes the clipboard for good, one that is gone already counts as deleted
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError>;
 
-    async fn download_by_id(
-        &self,
-        id: &String,
-    ) -> Result<FullClipboardDto, Box<dyn std::error::Error>>;
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError>;
 
-    async fn upload_clipboard(
-        &self,
-        clipboard: &FullClipboardDto,
-    ) -> Result<Clippy, Box<dyn std::error::Error>>;
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError>;
 
     async fn update_clipboard(
         &self,
         local_clipboard: &FullClipboardDto,
         remote_clipboard: &Clippy,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+    ) -> Result<Clippy, SyncError>;
 
-    async fn star_clipboard(&self, clippy: &FullClipboardDto);
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError>;
 
-    async fn cleanup_old_clipboards(
-        &self,
-        remote_clipboards: &Vec<Clippy>,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError>;
 
-    async fn upsert_settings(
-        &self,
-        settings: &HashMap<String, Value>,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError>;
+
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError>;
+
+    /// Salt of the sync passphrase, stored unsealed next to the sealed settings.
+    /// `None` if the settings are not sealed
+    async fn get_settings_salt(&self) -> Result<Option<String>, SyncError> {
+        Ok(self
+            .get_settings()
+            .await?
+            .remove(SYNC_SETTINGS_SALT_KEY)
+            .and_then(|salt| salt.as_str().map(str::to_string)))
+    }
+
+    /// `None` if no device wrote a manifest yet
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError>;
+
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError>;
+
+    /// Devices syncing through this provider, empty if it keeps no registry
+    async fn get_devices(&self) -> Result<HashMap<String, DeviceEntry>, SyncError> {
+        Ok(HashMap::new())
+    }
+
+    /// Adds or updates a device in the registry, written by `flush_manifest`
+    async fn put_device(&self, _id: &str, _device: &DeviceEntry) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    /// Deleted clipboards by id, empty if the provider keeps no tombstones
+    async fn get_tombstones(&self) -> Result<HashMap<Uuid, Tombstone>, SyncError> {
+        Ok(HashMap::new())
+    }
+
+    /// Drops the tombstones every device has seen or that outlived their retention
+    async fn collect_tombstones(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
 
-    async fn get_settings(&self) -> Result<HashMap<String, Value>, Box<dyn std::error::Error>>;
+    /// Writes the changes made since the last flush into the remote manifest
+    async fn flush_manifest(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
 
     async fn is_authenticated(&self) -> bool;
 }
 
-pub struct GoogleDriveProvider {
-    pub hub:
-        DriveHub<hyper_rustls::HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>>,
+/// Index of all remote clipboards, so a sync only needs one request to see what changed
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SyncManifest {
+    /// Format version, see `SYNC_MANIFEST_VERSION`
+    pub version: u32,
+    /// Change counter, increased with every write
+    pub revision: u64,
+    /// When the manifest was last rebuilt from a full listing
+    pub rebuilt_at: NaiveDateTime,
+    pub clipboards: HashMap<Uuid, ManifestEntry>,
+    /// Registry of the devices syncing, by device id
+    #[serde(default)]
+    pub devices: HashMap<String, DeviceEntry>,
+    /// Deleted clipboards by id, kept until every device has seen the deletion
+    #[serde(default)]
+    pub tombstones: HashMap<Uuid, Tombstone>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tombstone {
+    pub deleted_at: NaiveDateTime,
+    /// Device the clipboard was deleted on, `None` for markers of older versions
+    pub deleted_by: Option<String>,
+    /// Manifest revision the tombstone was written in
+    pub revision: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceEntry {
+    pub name: String,
+    pub last_seen_at: NaiveDateTime,
+    /// A revoked device stops syncing and its clipboards are removed everywhere
+    pub revoked_at: Option<NaiveDateTime>,
+    /// Newest tombstone revision the device has seen
+    #[serde(default)]
+    pub seen_revision: u64,
+}
+
+impl DeviceEntry {
+    /// Combines two entries of the same device, a revocation is never undone
+    pub fn merge(&self, other: &DeviceEntry) -> DeviceEntry {
+        let newer = if other.last_seen_at > self.last_seen_at {
+            other
+        } else {
+            self
+        };
+
+        DeviceEntry {
+            name: newer.name.clone(),
+            last_seen_at: newer.last_seen_at,
+            revoked_at: self.revoked_at.or(other.revoked_at),
+            seen_revision: self.seen_revision.max(other.seen_revision),
+        }
+    }
+}
+
+impl From<&entity::device::Model> for DeviceEntry {
+    fn from(model: &entity::device::Model) -> Self {
+        DeviceEntry {
+            name: model.name.clone(),
+            last_seen_at: model.last_seen_at,
+            revoked_at: model.revoked_at,
+            seen_revision: 0,
+        }
+    }
+}
+
+/// Binding of a hotkey as synced, devices match hotkeys by their event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncHotkey {
+    pub event: String,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub key: String,
+    pub status: bool,
+}
+
+impl From<&entity::hotkey::Model> for SyncHotkey {
+    fn from(model: &entity::hotkey::Model) -> Self {
+        SyncHotkey {
+            event: model.event.clone(),
+            ctrl: model.ctrl,
+            alt: model.alt,
+            shift: model.shift,
+            key: model.key.clone(),
+            status: model.status,
+        }
+    }
+}
+
+/// A device as listed to the user
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    pub last_seen_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    /// The device the app runs on
+    pub current: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub provider_id: String,
+    pub star: bool,
+    pub encrypted: bool,
+    pub created_at: NaiveDateTime,
+    pub deleted_at: Option<NaiveDateTime>,
+    /// SHA-256 of the uploaded clipboard, unknown for entries found by a full listing
+    pub hash: Option<String>,
+    /// Manifest revision the entry last changed in
+    pub revision: u64,
+    #[serde(default = "default_payload_format")]
+    pub format: u32,
+    #[serde(default = "default_payload_chunks")]
+    pub chunks: u32,
+    /// Device the clipboard was copied on, `None` for entries found by a full listing
+    /// and clipboards from before devices were tracked
+    #[serde(default)]
+    pub device_id: Option<String>,
+}
+
+fn default_payload_format() -> u32 {
+    1
+}
+
+fn default_payload_chunks() -> u32 {
+    1
+}
+
+impl ManifestEntry {
+    pub fn to_clippy(&self, id: Uuid) -> Clippy {
+        Clippy {
+            id,
+            provider_id: self.provider_id.clone(),
+            star: self.star,
+            encrypted: self.encrypted,
+            created_at: self.created_at,
+            deleted_at: self.deleted_at,
+            format: self.format,
+            chunks: self.chunks,
+        }
+    }
+}
+
+impl From<&Clippy> for ManifestEntry {
+    fn from(clippy: &Clippy) -> Self {
+        ManifestEntry {
+            provider_id: clippy.provider_id.clone(),
+            star: clippy.star,
+            encrypted: clippy.encrypted,
+            created_at: clippy.created_at,
+            deleted_at: clippy.deleted_at,
+            hash: None,
+            revision: 0,
+            format: clippy.format,
+            chunks: clippy.chunks,
+            device_id: None,
+        }
+    }
+}
+
+/// Change counter of a clipboard or settings field, the greater one wins a sync conflict.
+/// Equal counters are edits made without seeing each other, the device id breaks the tie
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SyncRevision {
+    pub revision: i32,
+    /// Device that made the change, `None` for data written before revisions existed
+    pub modified_by: Option<String>,
+}
+
+impl SyncRevision {
+    /// The next revision after this one, made on `device_id`
+    pub fn next(&self, device_id: &str) -> Self {
+        SyncRevision {
+            revision: self.revision + 1,
+            modified_by: Some(device_id.to_string()),
+        }
+    }
+
+    /// Both sides changed from the same revision, so one of the edits is dropped
+    pub fn is_concurrent(&self, other: &SyncRevision) -> bool {
+        self.revision == other.revision && self.modified_by != other.modified_by
+    }
+}
+
+/// Drive hub over the connector `C`, which carries the proxy and certificates of the app
+pub struct GoogleDriveProvider<C> {
+    pub hub: DriveHub<C>,
+}
+
+/// WebDAV login, kept in the app data folder and never synced
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebDavCredentials {
+    /// Collection the clipboards are stored in, e.g. a Nextcloud folder
+    pub url: String,
+    pub username: String,
+    /// Account or app password
+    pub password: String,
+}
+
+/// S3 compatible bucket access, kept in the app data folder and never synced
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct S3Credentials {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://localhost:9000` for MinIO
+    pub endpoint: String,
+    pub bucket: String,
+    /// Folder inside the bucket, may be empty
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// Clippy sync server access, kept in the app data folder and never synced
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServerCredentials {
+    pub url: String,
+    pub token: String,
+}
+
+fn default_s3_region() -> String {
+    S3_DEFAULT_REGION.to_string()
+}
+
+/// Health of the sync loop, emitted as `ListenEvent::SyncStatus` on every change
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SyncStatus {
+    pub last_success: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    /// Local changes waiting in the outbox
+    pub pending_uploads: u64,
+    /// Remote clipboards not downloaded yet
+    pub pending_downloads: u64,
+    /// Remote clipboards that are not deleted
+    pub remote_count: u64,
+    /// A pull or push is in progress
+    pub running: bool,
+    /// Payloads are sealed with a sync passphrase
+    pub sealed: bool,
+    /// Health of every configured provider, the sync provider first and then the mirrors
+    #[serde(default)]
+    pub providers: Vec<SyncProviderStatus>,
+}
+
+/// Health of one configured provider, the top level fields of `SyncStatus` follow the first
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SyncProviderStatus {
+    /// `SyncProviderType` of the provider
+    pub provider: String,
+    /// Mirrors only receive changes, they are never pulled from
+    pub mirror: bool,
+    pub last_success: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+}
+
+/// Failure of a sync provider, the kind decides whether retrying can help
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncError {
+    /// Login missing, expired or revoked
+    Auth(String),
+    /// Connection failed, timed out or the remote had a server error
+    Network(String),
+    /// Storage full or rate limited
+    Quota(String),
+    NotFound(String),
+    /// The remote changed since it was read, e.g. a failed conditional write
+    Conflict(String),
+    /// Remote data that does not parse
+    CorruptPayload(String),
+    /// Local failures, e.g. the database
+    Other(String),
+}
+
+impl SyncError {
+    /// Maps an HTTP status of a failed request, `None` for success codes
+    pub fn from_status(status: u16, message: String) -> Option<Self> {
+        match status {
+            200..=399 => None,
+            401 | 403 => Some(SyncError::Auth(message)),
+            404 | 410 => Some(SyncError::NotFound(message)),
+            409 | 412 => Some(SyncError::Conflict(message)),
+            413 | 429 | 507 => Some(SyncError::Quota(message)),
+            408 | 500..=599 => Some(SyncError::Network(message)),
+            _ => Some(SyncError::Other(message)),
+        }
+    }
+
+    /// Transient failures that are worth another attempt after a delay
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, SyncError::Network(_) | SyncError::Quota(_))
+    }
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Auth(message) => write!(f, "authentication failed: {}", message),
+            SyncError::Network(message) => write!(f, "network error: {}", message),
+            SyncError::Quota(message) => write!(f, "quota exceeded: {}", message),
+            SyncError::NotFound(message) => write!(f, "not found: {}", message),
+            SyncError::Conflict(message) => write!(f, "conflict: {}", message),
+            SyncError::CorruptPayload(message) => write!(f, "corrupt payload: {}", message),
+            SyncError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+impl From<String> for SyncError {
+    fn from(message: String) -> Self {
+        SyncError::Other(message)
+    }
+}
+
+impl From<&str> for SyncError {
+    fn from(message: &str) -> Self {
+        SyncError::Other(message.to_string())
+    }
+}
+
+impl From<reqwest::Error> for SyncError {
+    fn from(e: reqwest::Error) -> Self {
+        if let Some(error) = e
+            .status()
+            .and_then(|status| SyncError::from_status(status.as_u16(), e.to_string()))
+        {
+            return error;
+        }
+
+        if e.is_decode() {
+            SyncError::CorruptPayload(e.to_string())
+        } else if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() {
+            SyncError::Network(e.to_string())
+        } else {
+            SyncError::Other(e.to_string())
+        }
+    }
+}
+
+impl From<google_drive3::Error> for SyncError {
+    fn from(e: google_drive3::Error) -> Self {
+        use google_drive3::Error;
+
+        match e {
+            Error::MissingToken(_) | Error::MissingAPIKey => SyncError::Auth(e.to_string()),
+            Error::HttpError(_) | Error::Io(_) => SyncError::Network(e.to_string()),
+            Error::UploadSizeLimitExceeded(..) => SyncError::Quota(e.to_string()),
+            Error::JsonDecodeError(..) => SyncError::CorruptPayload(e.to_string()),
+            Error::Failure(ref response) => {
+                SyncError::from_status(response.status().as_u16(), e.to_string())
+                    .unwrap_or_else(|| SyncError::Other(e.to_string()))
+            }
+            Error::BadRequest(ref body) => body["error"]["code"]
+                .as_u64()
+                .and_then(|code| SyncError::from_status(code as u16, e.to_string()))
+                .unwrap_or_else(|| SyncError::Other(e.to_string())),
+            _ => SyncError::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SyncError {
+    fn from(e: serde_json::Error) -> Self {
+        SyncError::CorruptPayload(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for SyncError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => SyncError::NotFound(e.to_string()),
+            std::io::ErrorKind::PermissionDenied => SyncError::Auth(e.to_string()),
+            std::io::ErrorKind::InvalidData => SyncError::CorruptPayload(e.to_string()),
+            _ => SyncError::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<CommandError> for SyncError {
+    fn from(e: CommandError) -> Self {
+        match e {
+            CommandError::Error(message) => SyncError::Other(message),
+        }
+    }
+}
+
+/// A missing or wrong sync passphrase is an authentication failure, anything else a bad payload
+impl From<EncryptionError> for SyncError {
+    fn from(e: EncryptionError) -> Self {
+        match e {
+            EncryptionError::SyncPassphraseRequired
+            | EncryptionError::InvalidKey
+            | EncryptionError::DecryptionFailed => SyncError::Auth(e.to_string()),
+            _ => SyncError::CorruptPayload(e.to_string()),
+        }
+    }
+}
+
+impl From<sea_orm::DbErr> for SyncError {
+    fn from(e: sea_orm::DbErr) -> Self {
+        SyncError::Other(e.to_string())
+    }
 }
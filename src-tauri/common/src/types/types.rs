@@ -5,6 +5,15 @@ use serde_json::{json, Value as JsonValue};
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub db: String,
+    /// Folder of the local folder sync provider
+    #[serde(default)]
+    pub sync_folder: Option<String>,
+    /// Identifies this installation in sync revisions, created on first use
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// Shown to other devices, defaults to the host name
+    #[serde(default)]
+    pub device_name: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -21,6 +21,20 @@ pub struct Model {
     pub star: bool,
     pub encrypted: bool,
     pub created_at: DateTime,
+    #[serde(default)]
+    pub vault: bool,
+    /// Increased with every change, decides which device wins a sync conflict
+    #[serde(default)]
+    pub revision: i32,
+    /// Device that made the last change, `None` if it never changed
+    #[serde(default)]
+    pub modified_by: Option<String>,
+    /// Device the clipboard was copied on, `None` for clipboards from before devices were tracked
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// Never uploaded, whatever the sync filters allow
+    #[serde(default)]
+    pub sync_excluded: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
@@ -30,6 +44,11 @@ pub enum Column {
     Star,
     Encrypted,
     CreatedAt,
+    Vault,
+    Revision,
+    ModifiedBy,
+    DeviceId,
+    SyncExcluded,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DerivePrimaryKey)]
@@ -50,6 +69,7 @@ pub enum Relation {
     ClipboardHtml,
     ClipboardImage,
     ClipboardRtf,
+    ClipboardSearch,
     ClipboardText,
 }
 
@@ -62,6 +82,11 @@ impl ColumnTrait for Column {
             Self::Star => ColumnType::Boolean.def(),
             Self::Encrypted => ColumnType::Boolean.def(),
             Self::CreatedAt => ColumnType::DateTime.def(),
+            Self::Vault => ColumnType::Boolean.def(),
+            Self::Revision => ColumnType::Integer.def(),
+            Self::ModifiedBy => ColumnType::String(StringLen::None).def().null(),
+            Self::DeviceId => ColumnType::String(StringLen::None).def().null(),
+            Self::SyncExcluded => ColumnType::Boolean.def(),
         }
     }
 }
@@ -73,6 +98,7 @@ impl RelationTrait for Relation {
             Self::ClipboardHtml => Entity::has_one(super::clipboard_html::Entity).into(),
             Self::ClipboardImage => Entity::has_one(super::clipboard_image::Entity).into(),
             Self::ClipboardRtf => Entity::has_one(super::clipboard_rtf::Entity).into(),
+            Self::ClipboardSearch => Entity::has_many(super::clipboard_search::Entity).into(),
             Self::ClipboardText => Entity::has_one(super::clipboard_text::Entity).into(),
         }
     }
@@ -102,6 +128,12 @@ impl Related<super::clipboard_rtf::Entity> for Entity {
     }
 }
 
+impl Related<super::clipboard_search::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ClipboardSearch.def()
+    }
+}
+
 impl Related<super::clipboard_text::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ClipboardText.def()
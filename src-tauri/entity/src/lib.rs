@@ -7,6 +7,10 @@ pub mod clipboard_file;
 pub mod clipboard_html;
 pub mod clipboard_image;
 pub mod clipboard_rtf;
+pub mod clipboard_search;
 pub mod clipboard_text;
+pub mod device;
 pub mod hotkey;
+pub mod job;
 pub mod settings;
+pub mod sync_outbox;
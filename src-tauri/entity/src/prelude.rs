@@ -5,6 +5,10 @@ pub use super::clipboard_file::Entity as ClipboardFile;
 pub use super::clipboard_html::Entity as ClipboardHtml;
 pub use super::clipboard_image::Entity as ClipboardImage;
 pub use super::clipboard_rtf::Entity as ClipboardRtf;
+pub use super::clipboard_search::Entity as ClipboardSearch;
 pub use super::clipboard_text::Entity as ClipboardText;
+pub use super::device::Entity as Device;
 pub use super::hotkey::Entity as Hotkey;
+pub use super::job::Entity as Job;
 pub use super::settings::Entity as Settings;
+pub use super::sync_outbox::Entity as SyncOutbox;
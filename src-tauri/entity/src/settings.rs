@@ -29,11 +29,40 @@ pub struct Model {
     pub position: String,
     pub encryption: bool,
     pub enryption_save_before_unlock: bool,
+    pub encryption_keys: Option<Json>,
     pub max_file_size: i32,
     pub max_image_size: i32,
     pub max_text_size: i32,
     pub max_rtf_size: i32,
     pub max_html_size: i32,
+    /// Revision of every synced field, see `SyncRevision`
+    #[serde(default)]
+    pub sync_revisions: Option<Json>,
+    /// `ClipboardType`s that are uploaded
+    pub sync_types: Json,
+    /// Largest clipboard content in bytes that is uploaded
+    pub sync_max_size: i32,
+    /// `ClipboardTextType`s that stay local
+    pub sync_excluded_text_types: Json,
+    /// Settings fields, or `hotkeys`, that stay on this device instead of syncing
+    pub sync_excluded_settings: Json,
+    /// HTTP proxy the sync providers connect through, never synced
+    #[serde(default)]
+    pub sync_proxy_url: Option<String>,
+    /// Comma separated hosts reached without the proxy
+    #[serde(default)]
+    pub sync_no_proxy: Option<String>,
+    /// PEM file with certificates trusted in addition to the system ones
+    #[serde(default)]
+    pub sync_ca_bundle: Option<String>,
+    /// `SyncProviderType`s every change is copied to besides `sync_provider`, never synced
+    pub sync_mirrors: Json,
+    /// Fingerprint of the vault key, the vault passphrase is checked against it
+    #[serde(default)]
+    pub vault_check: Option<String>,
+    /// Random salt the vault key is derived with from the vault passphrase
+    #[serde(default)]
+    pub vault_salt: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
@@ -51,11 +80,23 @@ pub enum Column {
     Position,
     Encryption,
     EnryptionSaveBeforeUnlock,
+    EncryptionKeys,
     MaxFileSize,
     MaxImageSize,
     MaxTextSize,
     MaxRtfSize,
     MaxHtmlSize,
+    SyncRevisions,
+    SyncTypes,
+    SyncMaxSize,
+    SyncExcludedTextTypes,
+    SyncExcludedSettings,
+    SyncProxyUrl,
+    SyncNoProxy,
+    SyncCaBundle,
+    SyncMirrors,
+    VaultCheck,
+    VaultSalt,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DerivePrimaryKey)]
@@ -90,11 +131,23 @@ impl ColumnTrait for Column {
             Self::Position => ColumnType::String(StringLen::None).def(),
             Self::Encryption => ColumnType::Boolean.def(),
             Self::EnryptionSaveBeforeUnlock => ColumnType::Boolean.def(),
+            Self::EncryptionKeys => ColumnType::Json.def().null(),
             Self::MaxFileSize => ColumnType::Integer.def(),
             Self::MaxImageSize => ColumnType::Integer.def(),
             Self::MaxTextSize => ColumnType::Integer.def(),
             Self::MaxRtfSize => ColumnType::Integer.def(),
             Self::MaxHtmlSize => ColumnType::Integer.def(),
+            Self::SyncRevisions => ColumnType::Json.def().null(),
+            Self::SyncTypes => ColumnType::Json.def(),
+            Self::SyncMaxSize => ColumnType::Integer.def(),
+            Self::SyncExcludedTextTypes => ColumnType::Json.def(),
+            Self::SyncExcludedSettings => ColumnType::Json.def(),
+            Self::SyncProxyUrl => ColumnType::String(StringLen::None).def().null(),
+            Self::SyncNoProxy => ColumnType::String(StringLen::None).def().null(),
+            Self::SyncCaBundle => ColumnType::String(StringLen::None).def().null(),
+            Self::SyncMirrors => ColumnType::Json.def(),
+            Self::VaultCheck => ColumnType::String(StringLen::None).def().null(),
+            Self::VaultSalt => ColumnType::String(StringLen::None).def().null(),
         }
     }
 }
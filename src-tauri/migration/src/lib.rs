@@ -9,6 +9,24 @@ mod m000006_create_clipboard_file;
 mod m000007_create_settings;
 mod m000008_create_hotkey;
 mod m000009_seed;
+mod m000010_create_clipboard_search;
+mod m000011_add_clipboard_vault;
+mod m000012_add_settings_encryption_keys;
+mod m000013_create_job;
+mod m000014_update_settings_sync_provider;
+mod m000015_add_sync_provider_local_folder;
+mod m000016_add_sync_provider_s3;
+mod m000017_add_sync_provider_server;
+mod m000018_create_sync_outbox;
+mod m000019_add_sync_revisions;
+mod m000020_create_device;
+mod m000021_add_sync_filters;
+mod m000022_add_sync_excluded_settings;
+mod m000023_add_sync_provider_dry_run;
+mod m000024_add_sync_network;
+mod m000025_add_sync_mirrors;
+mod m000026_add_vault_check;
+mod m000027_add_vault_salt;
 
 pub struct Migrator;
 
@@ -25,6 +43,24 @@ impl MigratorTrait for Migrator {
             Box::new(m000007_create_settings::Migration),
             Box::new(m000008_create_hotkey::Migration),
             Box::new(m000009_seed::Migration),
+            Box::new(m000010_create_clipboard_search::Migration),
+            Box::new(m000011_add_clipboard_vault::Migration),
+            Box::new(m000012_add_settings_encryption_keys::Migration),
+            Box::new(m000013_create_job::Migration),
+            Box::new(m000014_update_settings_sync_provider::Migration),
+            Box::new(m000015_add_sync_provider_local_folder::Migration),
+            Box::new(m000016_add_sync_provider_s3::Migration),
+            Box::new(m000017_add_sync_provider_server::Migration),
+            Box::new(m000018_create_sync_outbox::Migration),
+            Box::new(m000019_add_sync_revisions::Migration),
+            Box::new(m000020_create_device::Migration),
+            Box::new(m000021_add_sync_filters::Migration),
+            Box::new(m000022_add_sync_excluded_settings::Migration),
+            Box::new(m000023_add_sync_provider_dry_run::Migration),
+            Box::new(m000024_add_sync_network::Migration),
+            Box::new(m000025_add_sync_mirrors::Migration),
+            Box::new(m000026_add_vault_check::Migration),
+            Box::new(m000027_add_vault_salt::Migration),
         ]
     }
 }
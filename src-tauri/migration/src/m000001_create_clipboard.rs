@@ -11,6 +11,11 @@ pub enum Clipboard {
     Star,
     Encrypted,
     CreatedAt,
+    Vault,
+    Revision,
+    ModifiedBy,
+    DeviceId,
+    SyncExcluded,
 }
 
 #[derive(DeriveMigrationName)]
@@ -16,7 +16,8 @@ use sea_orm_migration::{
 };
 
 #[derive(Iden)]
-enum Settings {
+#[allow(clippy::enum_variant_names)]
+pub enum Settings {
     Table,
     Id,
     Language,
@@ -34,12 +35,30 @@ enum Settings {
     //
     Encryption,
     EnryptionSaveBeforeUnlock,
+    EncryptionKeys,
     //
     MaxFileSize,
     MaxImageSize,
     MaxTextSize,
     MaxRtfSize,
     MaxHtmlSize,
+    //
+    SyncRevisions,
+    //
+    SyncTypes,
+    SyncMaxSize,
+    SyncExcludedTextTypes,
+    //
+    SyncExcludedSettings,
+    //
+    SyncProxyUrl,
+    SyncNoProxy,
+    SyncCaBundle,
+    //
+    SyncMirrors,
+    //
+    VaultCheck,
+    VaultSalt,
 }
 
 #[derive(DeriveMigrationName)]
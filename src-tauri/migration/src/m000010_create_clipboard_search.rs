@@ -0,0 +1,58 @@
+use crate::m000001_create_clipboard::Clipboard;
+use sea_orm_migration::{
+    prelude::*,
+    schema::{string, uuid},
+};
+
+#[derive(Iden)]
+pub enum ClipboardSearch {
+    Table,
+    Id,
+    ClipboardId,
+    Token,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ClipboardSearch::Table)
+                    .if_not_exists()
+                    .col(uuid(ClipboardSearch::Id).not_null().primary_key())
+                    .col(uuid(ClipboardSearch::ClipboardId))
+                    .col(string(ClipboardSearch::Token))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-clipboard-search")
+                            .from(ClipboardSearch::Table, ClipboardSearch::ClipboardId)
+                            .to(Clipboard::Table, Clipboard::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-clipboard-search-token")
+                    .table(ClipboardSearch::Table)
+                    .col(ClipboardSearch::Token)
+                    .col(ClipboardSearch::ClipboardId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ClipboardSearch::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,30 @@
+use crate::m000001_create_clipboard::Clipboard;
+use sea_orm_migration::{prelude::*, schema::boolean};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .add_column(boolean(Clipboard::Vault).default(false))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .drop_column(Clipboard::Vault)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,30 @@
+use crate::m000007_create_settings::Settings;
+use sea_orm_migration::{prelude::*, schema::json_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(json_null(Settings::EncryptionKeys))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .drop_column(Settings::EncryptionKeys)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,70 @@
+use common::types::enums::{JobKind, JobStatus};
+use sea_orm::Iterable;
+use sea_orm_migration::{
+    prelude::*,
+    schema::{date_time, integer, string, string_null, uuid, uuid_null},
+};
+
+#[derive(Iden)]
+pub enum Job {
+    Table,
+    Id,
+    Kind,
+    Status,
+    Cursor,
+    Current,
+    Total,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Job::Table)
+                    .if_not_exists()
+                    .col(uuid(Job::Id).not_null().primary_key())
+                    .col(
+                        string(Job::Kind).check(
+                            Expr::col(Job::Kind).is_in(
+                                JobKind::iter()
+                                    .map(|x| x.to_string())
+                                    .collect::<Vec<String>>(),
+                            ),
+                        ),
+                    )
+                    .col(
+                        string(Job::Status)
+                            .default(JobStatus::Running.to_string())
+                            .check(
+                                Expr::col(Job::Status).is_in(
+                                    JobStatus::iter()
+                                        .map(|x| x.to_string())
+                                        .collect::<Vec<String>>(),
+                                ),
+                            ),
+                    )
+                    .col(uuid_null(Job::Cursor))
+                    .col(integer(Job::Current).default(0))
+                    .col(integer(Job::Total).default(0))
+                    .col(string_null(Job::Error))
+                    .col(date_time(Job::CreatedAt).default(Expr::current_timestamp()))
+                    .col(date_time(Job::UpdatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Job::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,82 @@
+use crate::m000007_create_settings::Settings;
+use common::types::enums::SyncProviderType;
+use sea_orm::Iterable;
+use sea_orm_migration::{prelude::*, schema::string};
+
+#[derive(DeriveIden)]
+enum SettingsTmp {
+    SyncProviderTmp,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        rebuild_sync_provider_column(manager).await
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // the check can't be narrowed again while rows may use a newer provider
+        Ok(())
+    }
+}
+
+/// SQLite can't alter a CHECK constraint, so the `sync_provider` column is recreated
+/// with one allowing every current `SyncProviderType`
+pub async fn rebuild_sync_provider_column(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    manager
+        .alter_table(
+            Table::alter()
+                .table(Settings::Table)
+                .add_column(
+                    string(SettingsTmp::SyncProviderTmp)
+                        .default(
+                            SyncProviderType::iter()
+                                .next()
+                                .expect("no default value")
+                                .to_string(),
+                        )
+                        .check(
+                            Expr::col(SettingsTmp::SyncProviderTmp).is_in(
+                                SyncProviderType::iter()
+                                    .map(|x| x.to_string())
+                                    .collect::<Vec<String>>(),
+                            ),
+                        ),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+    manager
+        .exec_stmt(
+            Query::update()
+                .table(Settings::Table)
+                .value(
+                    SettingsTmp::SyncProviderTmp,
+                    Expr::col(Settings::SyncProvider),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+    manager
+        .alter_table(
+            Table::alter()
+                .table(Settings::Table)
+                .drop_column(Settings::SyncProvider)
+                .to_owned(),
+        )
+        .await?;
+
+    manager
+        .alter_table(
+            Table::alter()
+                .table(Settings::Table)
+                .rename_column(SettingsTmp::SyncProviderTmp, Settings::SyncProvider)
+                .to_owned(),
+        )
+        .await
+}
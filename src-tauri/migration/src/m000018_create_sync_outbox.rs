@@ -0,0 +1,54 @@
+use common::types::enums::SyncOperation;
+use sea_orm::Iterable;
+use sea_orm_migration::{
+    prelude::*,
+    schema::{date_time, integer, string, string_null, uuid},
+};
+
+#[derive(Iden)]
+pub enum SyncOutbox {
+    Table,
+    ClipboardId,
+    Operation,
+    Attempts,
+    Error,
+    NextAttemptAt,
+    CreatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SyncOutbox::Table)
+                    .if_not_exists()
+                    .col(uuid(SyncOutbox::ClipboardId).not_null().primary_key())
+                    .col(
+                        string(SyncOutbox::Operation).check(
+                            Expr::col(SyncOutbox::Operation).is_in(
+                                SyncOperation::iter()
+                                    .map(|x| x.to_string())
+                                    .collect::<Vec<String>>(),
+                            ),
+                        ),
+                    )
+                    .col(integer(SyncOutbox::Attempts).default(0))
+                    .col(string_null(SyncOutbox::Error))
+                    .col(date_time(SyncOutbox::NextAttemptAt).default(Expr::current_timestamp()))
+                    .col(date_time(SyncOutbox::CreatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SyncOutbox::Table).to_owned())
+            .await
+    }
+}
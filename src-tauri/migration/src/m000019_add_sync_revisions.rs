@@ -0,0 +1,70 @@
+use crate::{m000001_create_clipboard::Clipboard, m000007_create_settings::Settings};
+use sea_orm_migration::{
+    prelude::*,
+    schema::{integer, json_null, string_null},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // sqlite only alters one column per statement
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .add_column(integer(Clipboard::Revision).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .add_column(string_null(Clipboard::ModifiedBy))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(json_null(Settings::SyncRevisions))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .drop_column(Clipboard::Revision)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .drop_column(Clipboard::ModifiedBy)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .drop_column(Settings::SyncRevisions)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,59 @@
+use crate::m000001_create_clipboard::Clipboard;
+use sea_orm_migration::{
+    prelude::*,
+    schema::{date_time, date_time_null, string, string_null},
+};
+
+#[derive(Iden)]
+pub enum Device {
+    Table,
+    Id,
+    Name,
+    LastSeenAt,
+    RevokedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Device::Table)
+                    .if_not_exists()
+                    .col(string(Device::Id).not_null().primary_key())
+                    .col(string(Device::Name))
+                    .col(date_time(Device::LastSeenAt).default(Expr::current_timestamp()))
+                    .col(date_time_null(Device::RevokedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .add_column(string_null(Clipboard::DeviceId))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .drop_column(Clipboard::DeviceId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Device::Table).to_owned())
+            .await
+    }
+}
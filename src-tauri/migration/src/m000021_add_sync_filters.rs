@@ -0,0 +1,92 @@
+use crate::{m000001_create_clipboard::Clipboard, m000007_create_settings::Settings};
+use common::{
+    constants::{SYNC_MAX_SIZE, SYNC_MAX_SIZE_MAX, SYNC_MAX_SIZE_MIN},
+    types::enums::ClipboardType,
+};
+use sea_orm::Iterable;
+use sea_orm_migration::{
+    prelude::*,
+    schema::{boolean, integer, json},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // every type is synced by default
+        let sync_types =
+            ClipboardType::to_json_value(&ClipboardType::iter().collect::<Vec<_>>()).to_string();
+
+        // sqlite only alters one column per statement
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(json(Settings::SyncTypes).default(Expr::value(sync_types)))
+                    .to_owned(),
+            )
+            .await?;
+
+        // 10MB default, 0 min, 100MB max
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(
+                        integer(Settings::SyncMaxSize).default(SYNC_MAX_SIZE).check(
+                            Expr::col(Settings::SyncMaxSize)
+                                .gte(SYNC_MAX_SIZE_MIN)
+                                .and(Expr::col(Settings::SyncMaxSize).lte(SYNC_MAX_SIZE_MAX)),
+                        ),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(json(Settings::SyncExcludedTextTypes).default(Expr::value("[]")))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .add_column(boolean(Clipboard::SyncExcluded).default(false))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Settings::SyncTypes,
+            Settings::SyncMaxSize,
+            Settings::SyncExcludedTextTypes,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Settings::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Clipboard::Table)
+                    .drop_column(Clipboard::SyncExcluded)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,47 @@
+use crate::m000007_create_settings::Settings;
+use sea_orm_migration::{prelude::*, schema::string_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // sqlite only alters one column per statement
+        for column in [
+            Settings::SyncProxyUrl,
+            Settings::SyncNoProxy,
+            Settings::SyncCaBundle,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Settings::Table)
+                        .add_column(string_null(column))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Settings::SyncProxyUrl,
+            Settings::SyncNoProxy,
+            Settings::SyncCaBundle,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Settings::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
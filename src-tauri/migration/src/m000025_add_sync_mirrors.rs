@@ -0,0 +1,31 @@
+use crate::m000007_create_settings::Settings;
+use sea_orm_migration::{prelude::*, schema::json};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // only the sync provider by default
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(json(Settings::SyncMirrors).default(Expr::value("[]")))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .drop_column(Settings::SyncMirrors)
+                    .to_owned(),
+            )
+            .await
+    }
+}
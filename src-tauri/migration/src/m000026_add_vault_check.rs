@@ -0,0 +1,31 @@
+use crate::m000007_create_settings::Settings;
+use sea_orm_migration::{prelude::*, schema::string_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // set when the vault is created or first unlocked
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(string_null(Settings::VaultCheck))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .drop_column(Settings::VaultCheck)
+                    .to_owned(),
+            )
+            .await
+    }
+}
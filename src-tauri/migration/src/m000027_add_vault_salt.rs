@@ -0,0 +1,31 @@
+use crate::m000007_create_settings::Settings;
+use sea_orm_migration::{prelude::*, schema::string_null};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // salts the vault passphrase, set together with the vault check
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .add_column(string_null(Settings::VaultSalt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Settings::Table)
+                    .drop_column(Settings::VaultSalt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{store::Space, AppState};
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+use ring::digest;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Tokens are only kept hashed, the hash also names the space folder
+pub fn hash_token(token: &str) -> String {
+    digest::digest(&digest::SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Space of the bearer token sent with the request
+pub struct AuthSpace(pub Arc<Mutex<Space>>);
+
+impl FromRequestParts<AppState> for AuthSpace {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let id = hash_token(token);
+        if !state.config.tokens.contains(&id) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        state.space(&id).await.map(AuthSpace).map_err(|e| {
+            eprintln!("failed to open space: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+}
@@ -0,0 +1,102 @@
+//! Self-hosted sync server for Clippy. Every token gets its own space that all devices using it
+//! share, so a team can run one server for everyone.
+//!
+//! Configuration through environment variables:
+//! - `CLIPPY_SERVER_TOKENS` comma separated access tokens (required)
+//! - `CLIPPY_SERVER_ADDR` listen address, default `0.0.0.0:8420`
+//! - `CLIPPY_SERVER_DATA` data folder, default `./data`
+//! - `CLIPPY_SERVER_TOMBSTONE_DAYS` days deleted clipboards are remembered, default `30`
+mod auth;
+mod routes;
+mod store;
+#[cfg(test)]
+mod tests;
+
+use auth::hash_token;
+use chrono::Duration;
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+use store::Space;
+use tokio::sync::Mutex;
+
+pub struct Config {
+    addr: String,
+    data_path: PathBuf,
+    tombstone_retention: Duration,
+    /// Token hashes, also used as the folder name of the space
+    tokens: HashSet<String>,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        let tokens: HashSet<String> = std::env::var("CLIPPY_SERVER_TOKENS")
+            .expect("CLIPPY_SERVER_TOKENS is not set")
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(hash_token)
+            .collect();
+
+        if tokens.is_empty() {
+            panic!("CLIPPY_SERVER_TOKENS has no tokens");
+        }
+
+        Self {
+            addr: std::env::var("CLIPPY_SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:8420".into()),
+            data_path: std::env::var("CLIPPY_SERVER_DATA")
+                .unwrap_or_else(|_| "data".into())
+                .into(),
+            tombstone_retention: Duration::days(
+                std::env::var("CLIPPY_SERVER_TOMBSTONE_DAYS")
+                    .ok()
+                    .and_then(|days| days.parse().ok())
+                    .unwrap_or(30),
+            ),
+            tokens,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    config: Arc<Config>,
+    spaces: Arc<Mutex<std::collections::HashMap<String, Arc<Mutex<Space>>>>>,
+}
+
+impl AppState {
+    /// Space of a token hash, loaded from disk on first use
+    async fn space(&self, id: &str) -> std::io::Result<Arc<Mutex<Space>>> {
+        let mut spaces = self.spaces.lock().await;
+
+        if let Some(space) = spaces.get(id) {
+            return Ok(space.clone());
+        }
+
+        let space = Arc::new(Mutex::new(
+            Space::open(self.config.data_path.join(id)).await?,
+        ));
+        spaces.insert(id.to_string(), space.clone());
+
+        Ok(space)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let config = Config::from_env();
+    let addr = config.addr.clone();
+
+    let state = AppState {
+        config: Arc::new(config),
+        spaces: Default::default(),
+    };
+
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .expect("Failed to bind address");
+
+    println!("clippy sync server listening on {}", addr);
+
+    axum::serve(listener, routes::router(state))
+        .await
+        .expect("Failed to run server");
+}
@@ -0,0 +1,219 @@
+use crate::{
+    auth::AuthSpace,
+    store::{Changes, ClipboardMeta, ClipboardUpload, UpsertError},
+    AppState,
+};
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Clipboards are uploaded with their images and files inlined
+static BODY_LIMIT: usize = 512 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    #[serde(default)]
+    since: u64,
+}
+
+#[derive(Deserialize)]
+struct DeleteQuery {
+    #[serde(default)]
+    purge: bool,
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/api/auth", get(authenticate))
+        .route("/api/clipboards", get(changes))
+        .route(
+            "/api/clipboards/{id}",
+            get(download).put(upload).delete(delete),
+        )
+        .route(
+            "/api/chunks/{name}",
+            get(download_chunk).put(upload_chunk).delete(delete_chunk),
+        )
+        .route("/api/settings", get(get_settings).put(put_settings))
+        .route("/api/devices", get(get_devices).put(put_devices))
+        .layer(DefaultBodyLimit::max(BODY_LIMIT))
+        .with_state(state)
+}
+
+fn internal_error(e: std::io::Error) -> StatusCode {
+    eprintln!("request failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+async fn authenticate(_: AuthSpace) -> StatusCode {
+    StatusCode::NO_CONTENT
+}
+
+async fn changes(AuthSpace(space): AuthSpace, Query(query): Query<ChangesQuery>) -> Json<Changes> {
+    Json(space.lock().await.changes(query.since))
+}
+
+async fn download(
+    AuthSpace(space): AuthSpace,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Value>, StatusCode> {
+    space
+        .lock()
+        .await
+        .payload(&id)
+        .await
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn upload(
+    State(state): State<AppState>,
+    AuthSpace(space): AuthSpace,
+    Path(id): Path<Uuid>,
+    Json(upload): Json<ClipboardUpload>,
+) -> Result<Json<ClipboardMeta>, StatusCode> {
+    // the chunks are removed along with the clipboard, so they are file names as well
+    for name in &upload.chunks {
+        chunk_name(name)?;
+    }
+
+    let mut space = space.lock().await;
+
+    let meta = space.upsert(id, upload).await.map_err(|e| match e {
+        UpsertError::Deleted => StatusCode::CONFLICT,
+        UpsertError::Io(e) => internal_error(e),
+    })?;
+
+    space
+        .prune(state.config.tombstone_retention)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(meta))
+}
+
+async fn delete(
+    AuthSpace(space): AuthSpace,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DeleteQuery>,
+) -> Result<Json<ClipboardMeta>, StatusCode> {
+    space
+        .lock()
+        .await
+        .delete(id, query.purge)
+        .await
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Chunk names become file names, so only the characters the app uses are allowed
+fn chunk_name(name: &str) -> Result<&str, StatusCode> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(name)
+    } else {
+        Err(StatusCode::BAD_REQUEST)
+    }
+}
+
+async fn download_chunk(
+    AuthSpace(space): AuthSpace,
+    Path(name): Path<String>,
+) -> Result<Vec<u8>, StatusCode> {
+    space
+        .lock()
+        .await
+        .chunk(chunk_name(&name)?)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn upload_chunk(
+    AuthSpace(space): AuthSpace,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Result<StatusCode, StatusCode> {
+    space
+        .lock()
+        .await
+        .put_chunk(chunk_name(&name)?, body.to_vec())
+        .await
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_chunk(
+    AuthSpace(space): AuthSpace,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    space
+        .lock()
+        .await
+        .delete_chunk(chunk_name(&name)?)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_settings(AuthSpace(space): AuthSpace) -> Result<Json<Value>, StatusCode> {
+    space
+        .lock()
+        .await
+        .settings()
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn put_settings(
+    AuthSpace(space): AuthSpace,
+    Json(settings): Json<Value>,
+) -> Result<StatusCode, StatusCode> {
+    space
+        .lock()
+        .await
+        .put_settings(settings)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_devices(AuthSpace(space): AuthSpace) -> Result<Json<Value>, StatusCode> {
+    space
+        .lock()
+        .await
+        .devices()
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn put_devices(
+    AuthSpace(space): AuthSpace,
+    Json(devices): Json<Value>,
+) -> Result<StatusCode, StatusCode> {
+    space
+        .lock()
+        .await
+        .put_devices(devices)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,314 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, io, path::PathBuf};
+use tokio::fs;
+use uuid::Uuid;
+
+static INDEX_NAME: &str = "index.json";
+static SETTINGS_NAME: &str = "settings.json";
+static DEVICES_NAME: &str = "devices.json";
+static CLIPBOARDS_DIR: &str = "clipboards";
+static CHUNKS_DIR: &str = "chunks";
+
+/// Same metadata the app keeps in its `Clippy` struct, plus the revision it was last changed in
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClipboardMeta {
+    pub id: Uuid,
+    pub star: bool,
+    pub encrypted: bool,
+    pub created_at: NaiveDateTime,
+    /// Tombstone, set once a device deleted the clipboard
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Removed for good, only kept so devices learn about it through delta queries
+    #[serde(default)]
+    pub purged: bool,
+    pub revision: u64,
+    /// Payload format of the app, 1 is plain JSON
+    #[serde(default = "default_format")]
+    pub format: u32,
+    /// Chunks the payload was split into, removed together with the clipboard
+    #[serde(default)]
+    pub chunks: Vec<String>,
+}
+
+fn default_format() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct ClipboardUpload {
+    pub star: bool,
+    pub encrypted: bool,
+    pub created_at: NaiveDateTime,
+    /// `FullClipboardDto` of the app or its compressed payload, stored as is
+    pub payload: Value,
+    #[serde(default = "default_format")]
+    pub format: u32,
+    /// Uploaded through the chunk routes before the clipboard itself
+    #[serde(default)]
+    pub chunks: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Changes {
+    pub revision: u64,
+    /// The requested revision was pruned already, the device has to replace its listing
+    pub full: bool,
+    pub clipboards: Vec<ClipboardMeta>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    revision: u64,
+    /// Highest revision of a tombstone that was pruned
+    pruned_revision: u64,
+    clipboards: HashMap<Uuid, ClipboardMeta>,
+}
+
+pub enum UpsertError {
+    Deleted,
+    Io(io::Error),
+}
+
+impl From<io::Error> for UpsertError {
+    fn from(e: io::Error) -> Self {
+        UpsertError::Io(e)
+    }
+}
+
+/// Clipboards, settings and device registry of all devices sharing one token
+pub struct Space {
+    path: PathBuf,
+    index: Index,
+}
+
+impl Space {
+    pub async fn open(path: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(path.join(CLIPBOARDS_DIR)).await?;
+        fs::create_dir_all(path.join(CHUNKS_DIR)).await?;
+
+        let index = match fs::read_to_string(path.join(INDEX_NAME)).await {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Index::default(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self { path, index })
+    }
+
+    fn payload_path(&self, id: &Uuid) -> PathBuf {
+        self.path.join(CLIPBOARDS_DIR).join(format!("{}.json", id))
+    }
+
+    fn chunk_path(&self, name: &str) -> PathBuf {
+        self.path.join(CHUNKS_DIR).join(name)
+    }
+
+    async fn remove(&self, path: PathBuf) -> io::Result<()> {
+        match fs::remove_file(path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes into a temp file first, so a crash never leaves a partial file behind
+    async fn write(&self, path: PathBuf, content: Vec<u8>) -> io::Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, content).await?;
+        fs::rename(&tmp_path, path).await
+    }
+
+    async fn save(&self) -> io::Result<()> {
+        self.write(self.path.join(INDEX_NAME), serde_json::to_vec(&self.index)?)
+            .await
+    }
+
+    fn next_revision(&mut self) -> u64 {
+        self.index.revision += 1;
+        self.index.revision
+    }
+
+    /// Everything changed after `since`, or the full listing if tombstones after it were pruned
+    pub fn changes(&self, since: u64) -> Changes {
+        let full = since == 0 || since < self.index.pruned_revision;
+
+        let clipboards = self
+            .index
+            .clipboards
+            .values()
+            .filter(|meta| {
+                if full {
+                    !meta.purged
+                } else {
+                    meta.revision > since
+                }
+            })
+            .cloned()
+            .collect();
+
+        Changes {
+            revision: self.index.revision,
+            full,
+            clipboards,
+        }
+    }
+
+    pub async fn payload(&self, id: &Uuid) -> io::Result<Option<Value>> {
+        match self.index.clipboards.get(id) {
+            Some(meta) if meta.deleted_at.is_none() => {
+                let json = fs::read_to_string(self.payload_path(id)).await?;
+                Ok(Some(serde_json::from_str(&json)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Creates or replaces a clipboard, a deleted clipboard is not brought back
+    pub async fn upsert(
+        &mut self,
+        id: Uuid,
+        upload: ClipboardUpload,
+    ) -> Result<ClipboardMeta, UpsertError> {
+        if self
+            .index
+            .clipboards
+            .get(&id)
+            .is_some_and(|meta| meta.deleted_at.is_some())
+        {
+            return Err(UpsertError::Deleted);
+        }
+
+        let payload = serde_json::to_vec(&upload.payload).map_err(io::Error::from)?;
+        self.write(self.payload_path(&id), payload).await?;
+
+        // chunks of the replaced payload, an identical upload reuses them
+        if let Some(previous) = self.index.clipboards.get(&id) {
+            for name in previous
+                .chunks
+                .iter()
+                .filter(|name| !upload.chunks.contains(name))
+            {
+                self.remove(self.chunk_path(name)).await?;
+            }
+        }
+
+        let meta = ClipboardMeta {
+            id,
+            star: upload.star,
+            encrypted: upload.encrypted,
+            created_at: upload.created_at,
+            deleted_at: None,
+            purged: false,
+            revision: self.next_revision(),
+            format: upload.format,
+            chunks: upload.chunks,
+        };
+        self.index.clipboards.insert(id, meta.clone());
+        self.save().await?;
+
+        Ok(meta)
+    }
+
+    /// Turns the clipboard into a tombstone, `purge` also hides it from full listings
+    pub async fn delete(&mut self, id: Uuid, purge: bool) -> io::Result<Option<ClipboardMeta>> {
+        if !self.index.clipboards.contains_key(&id) {
+            return Ok(None);
+        }
+
+        let revision = self.next_revision();
+        let meta = self
+            .index
+            .clipboards
+            .get_mut(&id)
+            .expect("clipboard exists");
+        meta.deleted_at = meta.deleted_at.or(Some(Utc::now().naive_utc()));
+        meta.purged = meta.purged || purge;
+        meta.revision = revision;
+        let chunks = std::mem::take(&mut meta.chunks);
+        let meta = meta.clone();
+
+        self.remove(self.payload_path(&id)).await?;
+        for name in &chunks {
+            self.remove(self.chunk_path(name)).await?;
+        }
+        self.save().await?;
+
+        Ok(Some(meta))
+    }
+
+    /// Drops tombstones older than `retention`, devices that did not sync since then get a full listing
+    pub async fn prune(&mut self, retention: Duration) -> io::Result<()> {
+        let cutoff = Utc::now().naive_utc() - retention;
+
+        let expired: Vec<(Uuid, u64)> = self
+            .index
+            .clipboards
+            .values()
+            .filter(|meta| {
+                meta.deleted_at
+                    .is_some_and(|deleted_at| deleted_at < cutoff)
+            })
+            .map(|meta| (meta.id, meta.revision))
+            .collect();
+
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        for (id, revision) in expired {
+            self.index.clipboards.remove(&id);
+            self.index.pruned_revision = self.index.pruned_revision.max(revision);
+        }
+
+        self.save().await
+    }
+
+    /// Part of a payload that was too large for one request, written before its clipboard
+    pub async fn put_chunk(&self, name: &str, content: Vec<u8>) -> io::Result<()> {
+        self.write(self.chunk_path(name), content).await
+    }
+
+    pub async fn chunk(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.chunk_path(name)).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn delete_chunk(&self, name: &str) -> io::Result<()> {
+        self.remove(self.chunk_path(name)).await
+    }
+
+    /// Documents the app reads and writes as a whole, an empty object if never written
+    async fn document(&self, name: &str) -> io::Result<Value> {
+        match fs::read_to_string(self.path.join(name)).await {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Value::Object(Default::default())),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn put_document(&self, name: &str, document: Value) -> io::Result<()> {
+        self.write(self.path.join(name), serde_json::to_vec(&document)?)
+            .await
+    }
+
+    pub async fn settings(&self) -> io::Result<Value> {
+        self.document(SETTINGS_NAME).await
+    }
+
+    pub async fn put_settings(&self, settings: Value) -> io::Result<()> {
+        self.put_document(SETTINGS_NAME, settings).await
+    }
+
+    /// Registry of the devices by id, merged by the app
+    pub async fn devices(&self) -> io::Result<Value> {
+        self.document(DEVICES_NAME).await
+    }
+
+    pub async fn put_devices(&self, devices: Value) -> io::Result<()> {
+        self.put_document(DEVICES_NAME, devices).await
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    auth::hash_token,
+    routes::router,
+    store::{ClipboardMeta, ClipboardUpload, Space, UpsertError},
+    AppState, Config,
+};
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, Method, Request, StatusCode},
+};
+use chrono::{Duration, NaiveDateTime};
+use serde_json::json;
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+static TOKEN: &str = "token";
+
+/// Empty data folder of one test
+fn data_path() -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    std::env::temp_dir().join(format!(
+        "clippy-server-test-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ))
+}
+
+fn state(data_path: PathBuf) -> AppState {
+    AppState {
+        config: Arc::new(Config {
+            addr: String::new(),
+            data_path,
+            tombstone_retention: Duration::days(30),
+            tokens: HashSet::from([hash_token(TOKEN)]),
+        }),
+        spaces: Default::default(),
+    }
+}
+
+async fn upload(state: &AppState, id: Uuid, chunks: &[&str]) -> StatusCode {
+    let body = json!({
+        "star": false,
+        "encrypted": false,
+        "created_at": "2025-01-01T00:00:00",
+        "payload": {},
+        "chunks": chunks,
+    });
+
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(format!("/api/clipboards/{}", id))
+        .header(AUTHORIZATION, format!("Bearer {}", TOKEN))
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    router(state.clone())
+        .oneshot(request)
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn chunk_names_outside_the_space_are_rejected() {
+    let data_path = data_path();
+    let state = state(data_path.clone());
+
+    // a file of another space the traversal would reach
+    let victim = data_path.join("other").join("index.json");
+    std::fs::create_dir_all(victim.parent().unwrap()).unwrap();
+    std::fs::write(&victim, "{}").unwrap();
+
+    let id = Uuid::from_u128(1);
+    let traversal = "../../other/index.json";
+    assert_eq!(
+        upload(&state, id, &[traversal]).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(upload(&state, id, &["chunk-1"]).await, StatusCode::OK);
+    assert_eq!(upload(&state, id, &[]).await, StatusCode::OK);
+    assert!(victim.exists());
+
+    std::fs::remove_dir_all(data_path).ok();
+}
+
+fn clipboard_upload(chunks: &[&str]) -> ClipboardUpload {
+    ClipboardUpload {
+        star: false,
+        encrypted: false,
+        created_at: NaiveDateTime::default(),
+        payload: json!({ "text": "clipboard" }),
+        format: 1,
+        chunks: chunks.iter().map(|name| name.to_string()).collect(),
+    }
+}
+
+async fn put(space: &mut Space, id: Uuid, chunks: &[&str]) -> ClipboardMeta {
+    space
+        .upsert(id, clipboard_upload(chunks))
+        .await
+        .unwrap_or_else(|_| panic!("upsert of {} failed", id))
+}
+
+fn changed_ids(space: &Space, since: u64) -> Vec<Uuid> {
+    let mut ids: Vec<Uuid> = space
+        .changes(since)
+        .clipboards
+        .iter()
+        .map(|meta| meta.id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn changes_list_what_was_written_after_a_revision() {
+    let data_path = data_path();
+    let mut space = Space::open(data_path.clone()).await.unwrap();
+    let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+    let first = put(&mut space, a, &[]).await;
+    let second = put(&mut space, b, &[]).await;
+    assert!(second.revision > first.revision);
+
+    let changes = space.changes(0);
+    assert!(changes.full);
+    assert_eq!(changes.revision, second.revision);
+    assert_eq!(changed_ids(&space, 0), vec![a, b]);
+
+    assert_eq!(changed_ids(&space, first.revision), vec![b]);
+    let latest = space.changes(second.revision);
+    assert!(!latest.full);
+    assert!(latest.clipboards.is_empty());
+
+    // a replaced clipboard shows up again
+    let replaced = put(&mut space, a, &[]).await;
+    assert_eq!(changed_ids(&space, second.revision), vec![a]);
+
+    // the index survives a restart
+    let space = Space::open(data_path.clone()).await.unwrap();
+    assert_eq!(space.changes(0).revision, replaced.revision);
+    assert_eq!(
+        space.payload(&a).await.unwrap(),
+        Some(json!({ "text": "clipboard" }))
+    );
+
+    std::fs::remove_dir_all(data_path).ok();
+}
+
+#[tokio::test]
+async fn deleted_clipboards_stay_tombstones() {
+    let data_path = data_path();
+    let mut space = Space::open(data_path.clone()).await.unwrap();
+    let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+    space.put_chunk("chunk-1", b"part".to_vec()).await.unwrap();
+    let uploaded = put(&mut space, a, &["chunk-1"]).await;
+    put(&mut space, b, &[]).await;
+
+    let deleted = space.delete(a, false).await.unwrap().unwrap();
+    assert!(deleted.deleted_at.is_some());
+    assert!(deleted.chunks.is_empty());
+    assert_eq!(space.payload(&a).await.unwrap(), None);
+    assert_eq!(space.chunk("chunk-1").await.unwrap(), None);
+
+    // other devices learn about the deletion through a delta query
+    let changes = space.changes(uploaded.revision);
+    let tombstone = changes.clipboards.iter().find(|meta| meta.id == a).unwrap();
+    assert!(tombstone.deleted_at.is_some());
+
+    // a device that missed the deletion can't bring it back
+    assert!(matches!(
+        space.upsert(a, clipboard_upload(&[])).await,
+        Err(UpsertError::Deleted)
+    ));
+    assert!(space
+        .delete(Uuid::from_u128(3), false)
+        .await
+        .unwrap()
+        .is_none());
+
+    // purged clipboards only show up in deltas
+    let purged = space.delete(b, true).await.unwrap().unwrap();
+    assert!(purged.purged);
+    assert_eq!(changed_ids(&space, 0), vec![a]);
+    assert_eq!(changed_ids(&space, deleted.revision), vec![b]);
+
+    std::fs::remove_dir_all(data_path).ok();
+}
+
+#[tokio::test]
+async fn pruned_tombstones_force_a_full_listing() {
+    let data_path = data_path();
+    let mut space = Space::open(data_path.clone()).await.unwrap();
+    let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+    let first = put(&mut space, a, &[]).await;
+    put(&mut space, b, &[]).await;
+    let deleted = space.delete(a, false).await.unwrap().unwrap();
+
+    // still within the retention
+    space.prune(Duration::days(30)).await.unwrap();
+    assert!(!space.changes(first.revision).full);
+    assert_eq!(changed_ids(&space, 0), vec![a, b]);
+
+    // a negative retention expires every tombstone
+    space.prune(Duration::seconds(-1)).await.unwrap();
+    assert_eq!(changed_ids(&space, 0), vec![b]);
+
+    let stale = space.changes(first.revision);
+    assert!(stale.full);
+    assert_eq!(changed_ids(&space, first.revision), vec![b]);
+
+    // devices that synced after the tombstone keep getting deltas
+    let current = space.changes(deleted.revision);
+    assert!(!current.full);
+    assert!(current.clipboards.is_empty());
+
+    std::fs::remove_dir_all(data_path).ok();
+}
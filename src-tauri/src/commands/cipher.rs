@@ -1,5 +1,8 @@
 use crate::service::{
-    cipher::{handle_password_unlock, is_encryption_key_set, set_encryption_key},
+    cipher::{
+        create_recovery_key, handle_password_unlock, is_encryption_key_set,
+        join_or_create_encryption_key,
+    },
     decrypt::remove_encryption,
     encrypt::encrypt_all_clipboards,
     settings::{get_global_settings, update_settings_db},
@@ -7,8 +10,12 @@ use crate::service::{
 use common::types::{enums::PasswordAction, types::CommandError};
 
 #[tauri::command]
-pub async fn password_unlock(password: String, action: PasswordAction) -> Result<(), CommandError> {
-    handle_password_unlock(password, action).await
+pub async fn password_unlock(
+    password: String,
+    action: PasswordAction,
+    new_password: Option<String>,
+) -> Result<(), CommandError> {
+    handle_password_unlock(password, action, new_password).await
 }
 
 #[tauri::command]
@@ -24,17 +31,29 @@ pub async fn enable_encryption(
         return Err(CommandError::new("MAIN.ERROR.PASSWORD_NOT_MATCH"));
     }
 
-    set_encryption_key(&password).map_err(|e| CommandError::new(&e.to_string()))?;
-
-    encrypt_all_clipboards(true).await?;
+    // joins the key of devices that enabled encryption already
+    let encryption_keys = join_or_create_encryption_key(&password).await?;
 
+    // saved before the job runs so an interrupted job resumes on the next unlock
     let mut settings = get_global_settings();
     settings.encryption = true;
+    settings.encryption_keys = Some(encryption_keys);
     update_settings_db(settings).await?;
 
+    encrypt_all_clipboards(true).await?;
+
     Ok(())
 }
 
+#[tauri::command]
+pub async fn generate_recovery_key(password: String) -> Result<String, CommandError> {
+    if !is_encryption_key_set() {
+        return Err(CommandError::new("MAIN.ERROR.NO_ENCRYPTION_KEY_SET"));
+    }
+
+    create_recovery_key(password).await
+}
+
 #[tauri::command]
 pub async fn disable_encryption(password: String) -> Result<(), CommandError> {
     match remove_encryption(password).await {
@@ -1,16 +1,14 @@
-use crate::service::cipher::is_encryption_key_set;
-use crate::service::clipboard::{filter_clipboards, get_all_clipboards_db, init_clipboards};
+use crate::service::cipher::is_clipboard_key_set;
+use crate::service::clipboard::init_clipboards;
 use crate::service::decrypt::decrypt_clipboard;
-use crate::service::settings::get_global_settings;
-use crate::tao::global::{get_app, get_cache};
+use crate::tao::global::get_app;
 use crate::{
     service::clipboard::{
         clear_clipboards_db, copy_clipboard_from_id, delete_clipboards_db, get_clipboard_count_db,
-        get_clipboard_db, get_clipboards_db, star_clipboard_db,
+        get_clipboard_db, get_clipboards_db, star_clipboard_db, sync_exclude_clipboard_db,
     },
     utils::hotkey_manager::unregister_hotkeys,
 };
-use common::constants::CACHE_KEY;
 use common::io::clipboard::trim_clipboard_data;
 use common::types::orm_query::FullClipboardDto;
 use common::{
@@ -27,90 +25,40 @@ pub async fn get_clipboards(
     search: Option<String>,
     star: Option<bool>,
     img: Option<bool>,
+    device_id: Option<String>,
 ) -> Result<ClipboardsResponse, CommandError> {
     printlog!(
-        "Getting clipboards with cursor: {:?}, search: {:?}, star: {:?}, img: {:?}",
+        "Getting clipboards with cursor: {:?}, search: {:?}, star: {:?}, img: {:?}, device: {:?}",
         cursor,
         search,
         star,
-        img
+        img,
+        device_id
     );
 
-    let settings = get_global_settings();
-    let is_encrypted = settings.encryption && is_encryption_key_set();
     let total = get_clipboard_count_db().await?;
 
-    // Only use cache for encrypted clipboards WITH a search term
-    let clipboards = if is_encrypted && search.is_some() && !search.as_ref().unwrap().is_empty() {
-        // Get or populate the cache
-        let all_decrypted = if let Some(cached) = get_cache().get(CACHE_KEY) {
-            cached
-        } else {
-            // No cache hit, load and decrypt all clipboards
-            let all_clipboards = get_all_clipboards_db().await?;
-
-            // Decrypt all clipboards
-            let decrypted_clipboards: Vec<FullClipboardDto> = all_clipboards
-                .into_iter()
-                .map(|clipboard| {
-                    if clipboard.clipboard.encrypted {
-                        match decrypt_clipboard(clipboard.clone()) {
-                            Ok(decrypted) => decrypted,
-                            Err(e) => {
-                                printlog!("Failed to decrypt clipboard: {:?}", e);
-                                clipboard
-                            }
-                        }
-                    } else {
+    // Searches over encrypted clipboards are resolved through the search index,
+    // so only the requested page has to be decrypted
+    let clipboards_from_db = get_clipboards_db(cursor, search, star, img, device_id).await?;
+
+    // Vault entries are decrypted with the vault key, everything else with the history key
+    let clipboards: Vec<FullClipboardDto> = clipboards_from_db
+        .into_iter()
+        .map(|clipboard| {
+            if clipboard.clipboard.encrypted && is_clipboard_key_set(clipboard.clipboard.vault) {
+                match decrypt_clipboard(clipboard.clone()) {
+                    Ok(decrypted) => decrypted,
+                    Err(e) => {
+                        printlog!("Failed to decrypt clipboard: {:?}", e);
                         clipboard
                     }
-                })
-                .collect();
-
-            // Cache all decrypted clipboards
-            get_cache().insert(CACHE_KEY.to_string(), decrypted_clipboards.clone());
-            decrypted_clipboards
-        };
-
-        // Apply filters in memory
-        let filtered = filter_clipboards(&all_decrypted, search.as_ref(), star, img, &settings);
-
-        // Apply pagination
-        let start = cursor.unwrap_or(0) as usize;
-        let end = (start + 25).min(filtered.len());
-
-        if start < filtered.len() {
-            filtered[start..end].to_vec()
-        } else {
-            Vec::new()
-        }
-    } else {
-        // For regular search (non-encrypted OR encrypted without search string)
-        // we use the standard database query
-        let clipboards_from_db = get_clipboards_db(cursor, search, star, img).await?;
-
-        // If encrypted, we still need to decrypt the results
-        if is_encrypted {
-            clipboards_from_db
-                .into_iter()
-                .map(|clipboard| {
-                    if clipboard.clipboard.encrypted {
-                        match decrypt_clipboard(clipboard.clone()) {
-                            Ok(decrypted) => decrypted,
-                            Err(e) => {
-                                printlog!("Failed to decrypt clipboard: {:?}", e);
-                                clipboard
-                            }
-                        }
-                    } else {
-                        clipboard
-                    }
-                })
-                .collect()
-        } else {
-            clipboards_from_db
-        }
-    };
+                }
+            } else {
+                clipboard
+            }
+        })
+        .collect();
 
     let current_position = cursor.unwrap_or(0) + clipboards.len() as u64;
     let has_more = current_position < total;
@@ -138,7 +86,12 @@ pub async fn copy_clipboard(id: Uuid, r#type: ClipboardType) -> Result<bool, Com
 
 #[tauri::command]
 pub async fn star_clipboard(id: Uuid, star: bool) -> Result<bool, CommandError> {
-    Ok(star_clipboard_db(id, star).await?)
+    star_clipboard_db(id, star).await
+}
+
+#[tauri::command]
+pub async fn sync_exclude_clipboard(id: Uuid, excluded: bool) -> Result<bool, CommandError> {
+    sync_exclude_clipboard_db(id, excluded).await
 }
 
 #[tauri::command]
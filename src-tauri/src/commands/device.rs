@@ -0,0 +1,12 @@
+use crate::service::device::{get_devices_db, revoke_lost_device};
+use common::types::{sync::Device, types::CommandError};
+
+#[tauri::command]
+pub async fn get_devices() -> Result<Vec<Device>, CommandError> {
+    Ok(get_devices_db().await?)
+}
+
+#[tauri::command]
+pub async fn revoke_device(id: String) -> Result<(), CommandError> {
+    revoke_lost_device(id).await
+}
@@ -1,5 +1,9 @@
 use crate::{
-    service::hotkey::{get_all_hotkeys_db, init_hotkey_window, update_hotkey_db},
+    prelude::*,
+    service::{
+        hotkey::{get_all_hotkeys_db, init_hotkey_window, update_hotkey_db},
+        settings::bump_hotkeys_sync_revision,
+    },
     utils::hotkey_manager::unregister_hotkeys,
 };
 use common::types::types::CommandError;
@@ -16,6 +20,10 @@ pub async fn update_hotkey(hotkey: Model) {
         .await
         .expect("Failed to update hotkey");
 
+    if let Err(e) = bump_hotkeys_sync_revision().await {
+        printlog!("failed to sync hotkeys: {:?}", e);
+    }
+
     init_hotkey_window();
 }
 
@@ -0,0 +1,13 @@
+use crate::service::job;
+use common::types::types::CommandError;
+
+#[tauri::command]
+pub async fn cancel_job() -> Result<(), CommandError> {
+    job::cancel_job();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn resume_job() -> Result<(), CommandError> {
+    job::resume_job().await
+}
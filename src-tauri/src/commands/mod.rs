@@ -1,6 +1,9 @@
 pub mod cipher;
 pub mod clipboard;
+pub mod device;
 pub mod hotkey;
 pub mod settings;
 pub mod sync;
 pub mod window;
+pub mod vault;
+pub mod job;
@@ -1,25 +1,50 @@
 use crate::{
+    prelude::*,
     service::{
-        settings::{get_global_settings, init_settings_window, set_global_settings},
-        sync::{get_sync_provider, sync_toggle, upsert_settings_sync},
+        settings::{
+            bump_sync_revisions, get_global_settings, init_settings_window, set_global_settings,
+        },
+        sync::{
+            add_sync_mirror, change_sync_network, cleanup_sync_limit, connect_dry_run,
+            connect_local_folder, connect_s3, connect_server, connect_webdav, disconnect_dry_run,
+            disconnect_google_drive, disconnect_local_folder, disconnect_s3, disconnect_server,
+            disconnect_webdav, load_sync_status, remove_sync_mirror, remove_sync_passphrase,
+            set_sync_passphrase, sync_toggle, trigger_sync,
+        },
     },
     tao::connection::db,
+    utils::providers::network::NetworkSettings,
+};
+use common::types::{
+    sync::{S3Credentials, ServerCredentials, SyncStatus, WebDavCredentials},
+    types::CommandError,
 };
-use common::types::types::CommandError;
 use entity::settings::{self, ActiveModel};
 use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait};
 
 #[tauri::command]
 pub async fn sync_authenticate_toggle() -> Result<bool, CommandError> {
-    Ok(sync_toggle().await?)
+    sync_toggle().await
+}
+
+#[tauri::command]
+pub async fn sync_now() -> Result<(), CommandError> {
+    trigger_sync()
+}
+
+#[tauri::command]
+pub async fn get_sync_status() -> Result<SyncStatus, CommandError> {
+    load_sync_status().await
 }
 
 #[tauri::command]
 pub async fn sync_limit_change(sync_limit: i32) -> Result<settings::Model, CommandError> {
     let db: DatabaseConnection = db().await?;
-    let mut settings = get_global_settings();
+    let previous = get_global_settings();
+    let mut settings = previous.clone();
 
     settings.sync_limit = sync_limit;
+    bump_sync_revisions(&previous, &mut settings);
 
     let active_model: ActiveModel = settings.into();
 
@@ -30,19 +55,9 @@ pub async fn sync_limit_change(sync_limit: i32) -> Result<settings::Model, Comma
     if settings.sync {
         let remote_settings = settings.clone();
         tauri::async_runtime::spawn(async move {
-            let provider = get_sync_provider().await;
-            let remote_clipboards = provider
-                .fetch_all_clipboards()
-                .await
-                .expect("Failed to fetch all clipboards");
-            provider
-                .cleanup_old_clipboards(&remote_clipboards)
-                .await
-                .expect("Failed to cleanup old clipboards");
-
-            upsert_settings_sync(&remote_settings, false)
-                .await
-                .expect("Failed to upsert settings");
+            if let Err(e) = cleanup_sync_limit(&remote_settings).await {
+                printlog!("failed to apply sync limit: {:?}", e);
+            }
         });
     }
 
@@ -51,3 +66,99 @@ pub async fn sync_limit_change(sync_limit: i32) -> Result<settings::Model, Comma
 
     Ok(settings)
 }
+
+#[tauri::command]
+pub async fn sync_google_drive_disconnect() -> Result<(), CommandError> {
+    disconnect_google_drive().await
+}
+
+#[tauri::command]
+pub async fn sync_network_change(
+    proxy_url: Option<String>,
+    no_proxy: Option<String>,
+    ca_bundle: Option<String>,
+) -> Result<(), CommandError> {
+    change_sync_network(NetworkSettings::new(proxy_url, no_proxy, ca_bundle)).await
+}
+
+#[tauri::command]
+pub async fn sync_mirror_add(provider: String) -> Result<(), CommandError> {
+    add_sync_mirror(provider).await
+}
+
+#[tauri::command]
+pub async fn sync_mirror_remove(provider: String) -> Result<(), CommandError> {
+    remove_sync_mirror(provider).await
+}
+
+#[tauri::command]
+pub async fn sync_webdav_connect(
+    url: String,
+    username: String,
+    password: String,
+) -> Result<(), CommandError> {
+    connect_webdav(WebDavCredentials {
+        url,
+        username,
+        password,
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn sync_webdav_disconnect() -> Result<(), CommandError> {
+    disconnect_webdav().await
+}
+
+#[tauri::command]
+pub async fn sync_local_folder_connect(path: String) -> Result<(), CommandError> {
+    connect_local_folder(path).await
+}
+
+#[tauri::command]
+pub async fn sync_local_folder_disconnect() -> Result<(), CommandError> {
+    disconnect_local_folder().await
+}
+
+#[tauri::command]
+pub async fn sync_s3_connect(credentials: S3Credentials) -> Result<(), CommandError> {
+    connect_s3(credentials).await
+}
+
+#[tauri::command]
+pub async fn sync_s3_disconnect() -> Result<(), CommandError> {
+    disconnect_s3().await
+}
+
+#[tauri::command]
+pub async fn sync_server_connect(url: String, token: String) -> Result<(), CommandError> {
+    connect_server(ServerCredentials { url, token }).await
+}
+
+#[tauri::command]
+pub async fn sync_server_disconnect() -> Result<(), CommandError> {
+    disconnect_server().await
+}
+
+#[tauri::command]
+pub async fn sync_dry_run_connect() -> Result<(), CommandError> {
+    connect_dry_run().await
+}
+
+#[tauri::command]
+pub async fn sync_dry_run_disconnect() -> Result<(), CommandError> {
+    disconnect_dry_run().await
+}
+
+#[tauri::command]
+pub async fn sync_passphrase_set(
+    passphrase: String,
+    confirm_passphrase: String,
+) -> Result<(), CommandError> {
+    set_sync_passphrase(passphrase, confirm_passphrase).await
+}
+
+#[tauri::command]
+pub async fn sync_passphrase_remove(passphrase: String) -> Result<(), CommandError> {
+    remove_sync_passphrase(passphrase).await
+}
@@ -0,0 +1,22 @@
+use crate::service::vault::{lock_vault, unlock_vault, vault_clipboard_db};
+use common::types::types::CommandError;
+use sea_orm::prelude::Uuid;
+
+#[tauri::command]
+pub async fn vault_unlock(
+    password: String,
+    confirm_password: Option<String>,
+) -> Result<(), CommandError> {
+    unlock_vault(password, confirm_password).await
+}
+
+#[tauri::command]
+pub async fn vault_lock() -> Result<(), CommandError> {
+    lock_vault();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn vault_clipboard(id: Uuid, vault: bool) -> Result<(), CommandError> {
+    vault_clipboard_db(id, vault).await
+}
@@ -5,15 +5,18 @@ use crate::{
         window_events::setup_window_event_listener,
     },
     service::{
-        cipher::init_encryption_password_lock, settings::setup_settings, sync::setup_sync_interval,
+        cipher::{init_encryption_password_lock, load_sync_key},
+        settings::setup_settings,
+        sync::setup_sync_interval,
         window::setup_window,
     },
     tao::{config::setup_config, tao_constants::setup_globals},
 };
 
-pub fn setup(app: &mut tauri::App) -> Result<(), Box<(dyn std::error::Error + 'static)>> {
+pub fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error + 'static>> {
     setup_globals(app);
     setup_config();
+    load_sync_key();
 
     setup_settings();
     setup_window();
@@ -6,7 +6,7 @@ mod service;
 mod tao;
 mod utils;
 
-use commands::{cipher, clipboard, hotkey, settings, sync, window};
+use commands::{cipher, clipboard, device, hotkey, job, settings, sync, vault, window};
 use config::setup;
 use tauri_plugin_autostart::MacosLauncher;
 
@@ -34,6 +34,7 @@ pub fn run() {
             clipboard::get_clipboards,
             clipboard::delete_clipboard,
             clipboard::star_clipboard,
+            clipboard::sync_exclude_clipboard,
             clipboard::copy_clipboard,
             clipboard::clear_clipboards,
             clipboard::save_clipboard_image,
@@ -60,11 +61,38 @@ pub fn run() {
             //
             sync::sync_authenticate_toggle,
             sync::sync_limit_change,
+            sync::sync_now,
+            sync::get_sync_status,
+            sync::sync_google_drive_disconnect,
+            sync::sync_network_change,
+            sync::sync_mirror_add,
+            sync::sync_mirror_remove,
+            sync::sync_webdav_connect,
+            sync::sync_webdav_disconnect,
+            sync::sync_local_folder_connect,
+            sync::sync_local_folder_disconnect,
+            sync::sync_s3_connect,
+            sync::sync_s3_disconnect,
+            sync::sync_server_connect,
+            sync::sync_server_disconnect,
+            sync::sync_dry_run_connect,
+            sync::sync_dry_run_disconnect,
+            sync::sync_passphrase_set,
+            sync::sync_passphrase_remove,
             //
+            device::get_devices,
+            device::revoke_device,
             //
             cipher::enable_encryption,
             cipher::disable_encryption,
             cipher::password_unlock,
+            cipher::generate_recovery_key,
+            //
+            vault::vault_unlock,
+            vault::vault_lock,
+            vault::vault_clipboard,
+            job::cancel_job,
+            job::resume_job,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,55 +1,81 @@
 use super::{
     clipboard::load_clipboards_with_relations,
-    decrypt::{decrypt_all_clipboards, decrypt_clipboard},
-    encrypt::encrypt_all_clipboards,
-    settings::get_global_settings,
+    decrypt::{decrypt_all_clipboards, decrypt_clipboard, decrypt_data_with_key},
+    encrypt::{encrypt_all_clipboards, encrypt_data_with_key, looks_like_encrypted_data},
+    job::{resume_job, unfinished_job_db},
+    search::index_missing_clipboards_db,
+    settings::{get_global_settings, get_sync_excluded_settings},
+    sync::get_sync_provider,
 };
-use crate::tao::{connection::db, global::get_app};
+use crate::tao::{config::get_data_path, connection::db, global::get_app};
+use crate::utils::providers::token_storage::write_private;
 use crate::{prelude::*, service::settings::update_settings_db};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::constants::{
+    ENCRYPTION_KDF_ITERATIONS, KEY_ID_LABEL, RECOVERY_KEY_LABEL, SEARCH_INDEX_KEY_LABEL,
+    SYNC_KEY_NAME, VAULT_CHECK_LABEL,
+};
 use common::types::{
-    cipher::{EncryptionError, ENCRYPTION_KEY},
-    enums::{ListenEvent, PasswordAction},
+    cipher::{
+        EncryptionError, EncryptionKeys, SecretKey, ENCRYPTION_KEY, SYNC_KEY, SYNC_SALT, VAULT_KEY,
+    },
+    enums::{JobKind, ListenEvent, PasswordAction},
     types::CommandError,
 };
 use entity::clipboard;
+use ring::rand::SecureRandom;
+use ring::{hmac, pbkdf2, rand};
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use std::{num::NonZeroU32, path::PathBuf};
 use tauri::{Emitter, EventTarget};
+use zeroize::Zeroizing;
 
 pub async fn handle_password_unlock(
     password: String,
     action: PasswordAction,
+    new_password: Option<String>,
 ) -> Result<(), CommandError> {
     printlog!("action: {:?}", action);
 
     match action {
         PasswordAction::Encrypt => {
-            set_encryption_key(&password).map_err(|e| CommandError::new(&e.to_string()))?;
-            encrypt_all_clipboards(false).await?;
+            unlock_encryption_key(&password).map_err(|e| CommandError::new(&e.to_string()))?;
             let mut settings = get_global_settings();
             settings.encryption = true;
+            // remote history was encrypted before the data key was introduced
+            if settings.encryption_keys.is_none() {
+                settings.encryption_keys = Some(
+                    wrap_encryption_key(&password, None)
+                        .map_err(|e| CommandError::new(&e.to_string()))?,
+                );
+            }
             update_settings_db(settings).await?;
+            encrypt_all_clipboards(false).await?;
         }
         PasswordAction::Decrypt | PasswordAction::SyncDecrypt => {
-            set_encryption_key(&password).map_err(|e| CommandError::new(&e.to_string()))?;
+            unlock_encryption_key(&password).map_err(|e| CommandError::new(&e.to_string()))?;
 
             // Verify password by trying to decrypt something
             let db = db().await?;
             let encrypted_clipboard = clipboard::Entity::find()
                 .filter(clipboard::Column::Encrypted.eq(true))
+                .filter(clipboard::Column::Vault.eq(false))
                 .one(&db)
                 .await?;
 
             if let Some(clipboard) = encrypted_clipboard {
-                let mut clipboards = load_clipboards_with_relations(vec![clipboard]).await;
-                decrypt_clipboard(clipboards.remove(0)).map_err(|e| {
-                    clear_encryption_key();
-                    match e {
-                        EncryptionError::DecryptionFailed => {
-                            CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD")
+                let mut clipboards = load_clipboards_with_relations(vec![clipboard]).await?;
+                decrypt_clipboard(clipboards.remove(0))
+                    .map(Zeroizing::new)
+                    .map_err(|e| {
+                        clear_encryption_key();
+                        match e {
+                            EncryptionError::DecryptionFailed => {
+                                CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD")
+                            }
+                            _ => CommandError::new(&e.to_string()),
                         }
-                        _ => CommandError::new(&e.to_string()),
-                    }
-                })?;
+                    })?;
 
                 // Only proceed with full decryption for sync decrypt
                 if matches!(action, PasswordAction::SyncDecrypt) {
@@ -58,16 +84,66 @@ pub async fn handle_password_unlock(
                     update_settings_db(settings).await?;
                     decrypt_all_clipboards().await?;
                 } else {
-                    // encrypt all clipboards again if new were added before password was set
-                    encrypt_all_clipboards(false).await?;
+                    // wrap the key of histories encrypted before the data key was introduced
+                    let mut settings = get_global_settings();
+                    if settings.encryption_keys.is_none() {
+                        settings.encryption_keys = Some(
+                            wrap_encryption_key(&password, None)
+                                .map_err(|e| CommandError::new(&e.to_string()))?,
+                        );
+                        update_settings_db(settings).await?;
+                    }
+
+                    // index clipboards that were synced while the history was locked
+                    index_missing_clipboards_db().await?;
+
+                    match unfinished_job_db().await? {
+                        // finish disabling encryption if the app was closed while decrypting
+                        Some(JobKind::Decrypt) => {
+                            tauri::async_runtime::spawn(async {
+                                if let Err(e) = resume_job().await {
+                                    printlog!("Error resuming job: {:?}", e);
+                                }
+                            });
+                        }
+                        // encrypt all clipboards again if new were added before password was set
+                        _ => encrypt_all_clipboards(false).await?,
+                    }
                 }
             }
         }
+        PasswordAction::Recover => {
+            // `password` holds the recovery key
+            let recovery = get_encryption_keys()
+                .and_then(|keys| keys.recovery)
+                .ok_or(EncryptionError::InvalidRecoveryKey)
+                .map_err(|e| CommandError::new(&e.to_string()))?;
+
+            let key_bytes = unwrap_recovery_key(&password, &recovery)
+                .map_err(|e| CommandError::new(&e.to_string()))?;
+
+            *ENCRYPTION_KEY
+                .lock()
+                .map_err(|e| CommandError::new(&e.to_string()))? = Some(SecretKey::new(*key_bytes));
+
+            if let Some(new_password) = new_password {
+                let mut settings = get_global_settings();
+                settings.encryption_keys = Some(
+                    wrap_encryption_key(&new_password, Some(recovery))
+                        .map_err(|e| CommandError::new(&e.to_string()))?,
+                );
+                update_settings_db(settings).await?;
+            }
+
+            index_missing_clipboards_db().await?;
+            encrypt_all_clipboards(false).await?;
+        }
     }
 
     Ok(())
 }
 
+/// Drops the encryption key, which wipes its bytes
 pub fn clear_encryption_key() {
     *ENCRYPTION_KEY
         .lock()
@@ -81,35 +157,488 @@ pub fn is_encryption_key_set() -> bool {
 }
 
 pub fn verify_encryption_password(password: String) -> Result<bool, EncryptionError> {
+    let current_key = get_clipboard_key(false)?;
+
+    let provided_key = match get_encryption_keys() {
+        Some(keys) => match unwrap_key(&keys.password, &*derive_password_key(&password, &keys)?) {
+            Ok(key_bytes) => key_bytes,
+            Err(_) => return Ok(false),
+        },
+        None => legacy_password_key(&password),
+    };
+
+    Ok(provided_key == current_key)
+}
+
+/// Key of histories encrypted before the data key was introduced
+fn legacy_password_key(password: &str) -> Zeroizing<[u8; 32]> {
     let mut hasher = ring::digest::Context::new(&ring::digest::SHA256);
     hasher.update(password.as_bytes());
     let key = hasher.finish();
-    let mut provided_key = [0u8; 32];
-    provided_key.copy_from_slice(key.as_ref());
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    key_bytes.copy_from_slice(key.as_ref());
+    key_bytes
+}
 
-    let current_key = ENCRYPTION_KEY
+/// Sets the encryption key derived from a password
+pub fn set_encryption_key(password: &str) -> Result<(), EncryptionError> {
+    *ENCRYPTION_KEY
         .lock()
-        .map_err(|_| EncryptionError::KeyLockFailed)?
-        .ok_or(EncryptionError::NoKey)?;
+        .map_err(|_| EncryptionError::KeyLockFailed)? =
+        Some(SecretKey::new(*legacy_password_key(password)));
 
-    Ok(provided_key == current_key)
+    Ok(())
 }
 
-/// Sets the encryption key derived from a password
-pub fn set_encryption_key(password: &str) -> Result<(), EncryptionError> {
+/// Returns the wrapped data key stored in the settings
+pub fn get_encryption_keys() -> Option<EncryptionKeys> {
+    get_global_settings()
+        .encryption_keys
+        .and_then(|keys| serde_json::from_value(keys).ok())
+}
+
+/// Key encryption key derived from the password with PBKDF2
+fn derive_password_key(
+    password: &str,
+    keys: &EncryptionKeys,
+) -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    derive_salted_key(password, &keys.salt)
+}
+
+/// Derives a key from a password with PBKDF2 under a base64 encoded salt
+fn derive_salted_key(password: &str, salt: &str) -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    let salt = STANDARD
+        .decode(salt)
+        .map_err(|_| EncryptionError::InvalidKey)?;
+
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(ENCRYPTION_KDF_ITERATIONS).expect("iterations must be non-zero"),
+        &salt,
+        password.as_bytes(),
+        key_bytes.as_mut(),
+    );
+
+    Ok(key_bytes)
+}
+
+/// A fresh random salt, base64 encoded to be stored in the settings
+pub fn generate_salt() -> Result<String, EncryptionError> {
+    let mut salt = [0u8; 16];
+    rand::SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+    Ok(STANDARD.encode(salt))
+}
+
+/// Key encryption key of a recovery key, which already has full entropy
+fn derive_recovery_key(recovery_key: &str) -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    let hex: Vec<char> = recovery_key
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+
+    if hex.len() != 64 {
+        return Err(EncryptionError::InvalidRecoveryKey);
+    }
+
+    let bytes = Zeroizing::new(
+        hex.chunks(2)
+            .map(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| EncryptionError::InvalidRecoveryKey)?,
+    );
+
     let mut hasher = ring::digest::Context::new(&ring::digest::SHA256);
-    hasher.update(password.as_bytes());
+    hasher.update(RECOVERY_KEY_LABEL.as_bytes());
+    hasher.update(&bytes);
     let key = hasher.finish();
-    let mut key_bytes = [0u8; 32];
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
     key_bytes.copy_from_slice(key.as_ref());
 
+    Ok(key_bytes)
+}
+
+/// Generates a recovery key, 32 random bytes written as dash separated hex groups
+pub fn generate_recovery_key() -> Result<String, EncryptionError> {
+    let mut recovery_bytes = Zeroizing::new([0u8; 32]);
+    rand::SystemRandom::new()
+        .fill(recovery_bytes.as_mut())
+        .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+    Ok(recovery_bytes
+        .chunks(2)
+        .map(|pair| format!("{:02X}{:02X}", pair[0], pair[1]))
+        .collect::<Vec<String>>()
+        .join("-"))
+}
+
+/// Wraps the data key with a recovery key, stored as the `recovery` of the encryption keys
+pub fn wrap_recovery_key(
+    recovery_key: &str,
+    key_bytes: &[u8; 32],
+) -> Result<String, EncryptionError> {
+    wrap_key(key_bytes, &*derive_recovery_key(recovery_key)?)
+}
+
+/// Data key wrapped by a recovery key, any mismatch is an invalid recovery key
+pub fn unwrap_recovery_key(
+    recovery_key: &str,
+    wrapped: &str,
+) -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    derive_recovery_key(recovery_key)
+        .and_then(|kek| unwrap_key(wrapped, &kek))
+        .map_err(|_| EncryptionError::InvalidRecoveryKey)
+}
+
+fn wrap_key(key_bytes: &[u8; 32], kek: &[u8; 32]) -> Result<String, EncryptionError> {
+    Ok(STANDARD.encode(encrypt_data_with_key(key_bytes, kek)?))
+}
+
+fn unwrap_key(wrapped: &str, kek: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    let wrapped = STANDARD
+        .decode(wrapped)
+        .map_err(|_| EncryptionError::InvalidKey)?;
+
+    let key = Zeroizing::new(decrypt_data_with_key(&wrapped, kek)?);
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    if key.len() != key_bytes.len() {
+        return Err(EncryptionError::InvalidKey);
+    }
+    key_bytes.copy_from_slice(&key);
+
+    Ok(key_bytes)
+}
+
+/// Unlocks the data key with the password, histories without a wrapped
+/// data key still use the key derived directly from the password
+pub fn unlock_encryption_key(password: &str) -> Result<(), EncryptionError> {
+    let Some(keys) = get_encryption_keys() else {
+        return set_encryption_key(password);
+    };
+
+    let key_bytes = unwrap_key(&keys.password, &*derive_password_key(password, &keys)?)?;
+
+    *ENCRYPTION_KEY
+        .lock()
+        .map_err(|_| EncryptionError::KeyLockFailed)? = Some(SecretKey::new(*key_bytes));
+
+    Ok(())
+}
+
+/// Generates a new random data key and returns it wrapped by the password
+pub fn create_encryption_key(password: &str) -> Result<serde_json::Value, EncryptionError> {
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    rand::SystemRandom::new()
+        .fill(key_bytes.as_mut())
+        .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+    *ENCRYPTION_KEY
+        .lock()
+        .map_err(|_| EncryptionError::KeyLockFailed)? = Some(SecretKey::new(*key_bytes));
+
+    wrap_encryption_key(password, None)
+}
+
+/// Data key to enable encryption with. Another device's synced key is unlocked with the
+/// password, so every device encrypts with the same key, otherwise a new one is created
+pub async fn join_or_create_encryption_key(
+    password: &str,
+) -> Result<serde_json::Value, CommandError> {
+    let Some(remote_keys) = get_remote_setting("encryption_keys").await? else {
+        return create_encryption_key(password).map_err(|e| CommandError::new(&e.to_string()));
+    };
+
+    let keys: EncryptionKeys = serde_json::from_value(remote_keys.clone())
+        .map_err(|_| CommandError::new(&EncryptionError::InvalidKey.to_string()))?;
+    let key_bytes = derive_password_key(password, &keys)
+        .and_then(|kek| unwrap_key(&keys.password, &kek))
+        .map_err(|_| CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD"))?;
+
     *ENCRYPTION_KEY
         .lock()
-        .map_err(|_| EncryptionError::KeyLockFailed)? = Some(key_bytes);
+        .map_err(|e| CommandError::new(&e.to_string()))? = Some(SecretKey::new(*key_bytes));
+
+    Ok(remote_keys)
+}
+
+/// A key field of the remote settings, unless this device keeps the field to itself
+/// or no other device synced it yet
+pub async fn get_remote_setting(key: &str) -> Result<Option<serde_json::Value>, CommandError> {
+    let settings = get_global_settings();
+    if !settings.sync || get_sync_excluded_settings(&settings).contains(&key.to_string()) {
+        return Ok(None);
+    }
+
+    let mut remote_settings = get_sync_provider().await?.get_settings().await?;
+
+    Ok(remote_settings.remove(key).filter(|value| !value.is_null()))
+}
+
+/// Fingerprint of a key under `label`, it reveals nothing about the key itself
+fn key_fingerprint(key_bytes: &[u8; 32], label: &str) -> String {
+    hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, key_bytes),
+        label.as_bytes(),
+    )
+    .as_ref()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn encryption_key_id(key_bytes: &[u8; 32]) -> String {
+    key_fingerprint(key_bytes, KEY_ID_LABEL)
+}
+
+/// Check value of the vault key, stored in the settings so a mistyped passphrase is
+/// refused instead of sealing entries under a key nobody knows
+pub fn vault_key_check() -> Result<String, EncryptionError> {
+    Ok(key_fingerprint(
+        &*get_clipboard_key(true)?,
+        VAULT_CHECK_LABEL,
+    ))
+}
+
+/// Whether synced wrapped keys may replace the local ones, which is only the case when
+/// both wrap the same data key, e.g. after a password change on another device
+pub fn is_same_encryption_key(local: &serde_json::Value, remote: &serde_json::Value) -> bool {
+    let key_id = |keys: &serde_json::Value| {
+        serde_json::from_value::<EncryptionKeys>(keys.clone())
+            .ok()
+            .and_then(|keys| keys.key_id)
+    };
+
+    match (key_id(local), key_id(remote)) {
+        (Some(local_id), Some(remote_id)) => local_id == remote_id,
+        // keys wrapped before the fingerprint can not be told apart
+        _ => local == remote,
+    }
+}
+
+/// Wraps the current data key with a password under a fresh salt
+pub fn wrap_encryption_key(
+    password: &str,
+    recovery: Option<String>,
+) -> Result<serde_json::Value, EncryptionError> {
+    let key_bytes = get_clipboard_key(false)?;
+
+    let mut keys = EncryptionKeys {
+        salt: generate_salt()?,
+        password: String::new(),
+        recovery,
+        key_id: Some(encryption_key_id(&key_bytes)),
+    };
+    keys.password = wrap_key(&key_bytes, &*derive_password_key(password, &keys)?)?;
+
+    serde_json::to_value(keys).map_err(|_| EncryptionError::EncryptionFailed)
+}
+
+/// Generates a recovery key wrapping the data key, it is only returned once
+pub async fn create_recovery_key(password: String) -> Result<String, CommandError> {
+    if !verify_encryption_password(password.clone())
+        .map_err(|e| CommandError::new(&e.to_string()))?
+    {
+        return Err(CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD"));
+    }
+
+    let recovery_key = generate_recovery_key().map_err(|e| CommandError::new(&e.to_string()))?;
+    let wrapped = get_clipboard_key(false)
+        .and_then(|key_bytes| wrap_recovery_key(&recovery_key, &key_bytes))
+        .map_err(|e| CommandError::new(&e.to_string()))?;
+
+    let mut settings = get_global_settings();
+    settings.encryption_keys = Some(match get_encryption_keys() {
+        Some(mut keys) => {
+            keys.recovery = Some(wrapped);
+            serde_json::to_value(keys)?
+        }
+        None => wrap_encryption_key(&password, Some(wrapped))
+            .map_err(|e| CommandError::new(&e.to_string()))?,
+    });
+    update_settings_db(settings).await?;
+
+    Ok(recovery_key)
+}
+
+/// Checks if the vault is unlocked
+pub fn is_vault_key_set() -> bool {
+    VAULT_KEY.lock().map(|k| k.is_some()).unwrap_or(false)
+}
+
+/// Checks if the key a clipboard is encrypted with is available
+pub fn is_clipboard_key_set(vault: bool) -> bool {
+    if vault {
+        is_vault_key_set()
+    } else {
+        is_encryption_key_set()
+    }
+}
+
+/// Sets the vault key derived from the vault passphrase under the salt of the vault
+pub fn set_vault_key(password: &str, salt: &str) -> Result<(), EncryptionError> {
+    let key_bytes = derive_salted_key(password, salt)?;
+
+    *VAULT_KEY
+        .lock()
+        .map_err(|_| EncryptionError::KeyLockFailed)? = Some(SecretKey::new(*key_bytes));
 
     Ok(())
 }
 
+pub fn clear_vault_key() {
+    if let Ok(mut key) = VAULT_KEY.lock() {
+        *key = None;
+    }
+}
+
+/// Returns the key of the history or, for vault entries, the vault key.
+/// The copy is wiped as soon as the caller drops it
+pub fn get_clipboard_key(vault: bool) -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    if vault {
+        VAULT_KEY
+            .lock()
+            .map_err(|_| EncryptionError::KeyLockFailed)?
+            .as_ref()
+            .map(SecretKey::expose)
+            .ok_or(EncryptionError::VaultLocked)
+    } else {
+        ENCRYPTION_KEY
+            .lock()
+            .map_err(|_| EncryptionError::KeyLockFailed)?
+            .as_ref()
+            .map(SecretKey::expose)
+            .ok_or(EncryptionError::NoKey)
+    }
+}
+
+/// Derives the key used to hash search index tokens from the encryption key,
+/// vault entries are indexed under a key derived from the vault key
+pub fn get_search_key(vault: bool) -> Result<hmac::Key, EncryptionError> {
+    let key_bytes = get_clipboard_key(vault)?;
+
+    let search_key = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, key_bytes.as_ref()),
+        SEARCH_INDEX_KEY_LABEL.as_bytes(),
+    );
+
+    Ok(hmac::Key::new(hmac::HMAC_SHA256, search_key.as_ref()))
+}
+
+/// Key derived from the sync passphrase under the salt stored next to the remote settings,
+/// so every device derives the same key from the same passphrase
+pub fn derive_sync_key(
+    passphrase: &str,
+    salt: &str,
+) -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    derive_salted_key(passphrase, salt)
+}
+
+/// Checks if sync payloads are sealed with a passphrase
+pub fn is_sync_key_set() -> bool {
+    SYNC_KEY.lock().map(|k| k.is_some()).unwrap_or(false)
+}
+
+/// The sync key, `None` if payloads are uploaded without a passphrase
+pub fn get_sync_key() -> Result<Option<Zeroizing<[u8; 32]>>, EncryptionError> {
+    Ok(SYNC_KEY
+        .lock()
+        .map_err(|_| EncryptionError::KeyLockFailed)?
+        .as_ref()
+        .map(SecretKey::expose))
+}
+
+/// Salt the sync key was derived with, `None` if payloads are uploaded without a passphrase
+pub fn get_sync_salt() -> Result<Option<String>, EncryptionError> {
+    Ok(SYNC_SALT
+        .lock()
+        .map_err(|_| EncryptionError::KeyLockFailed)?
+        .clone())
+}
+
+/// Sets the sync key and its salt in memory, `None` uploads without a passphrase
+pub fn set_sync_key(key: Option<(SecretKey, String)>) -> Result<(), EncryptionError> {
+    let (key, salt) = key.unzip();
+    *SYNC_KEY
+        .lock()
+        .map_err(|_| EncryptionError::KeyLockFailed)? = key;
+    *SYNC_SALT
+        .lock()
+        .map_err(|_| EncryptionError::KeyLockFailed)? = salt;
+
+    Ok(())
+}
+
+fn sync_key_path() -> PathBuf {
+    std::path::Path::new(&get_data_path().config_path).join(SYNC_KEY_NAME)
+}
+
+/// Stores the sync key and its salt next to the provider credentials, so sync keeps working
+/// after a restart. Only the current user may read it
+pub fn save_sync_key(key_bytes: &[u8; 32], salt: &str) -> Result<(), CommandError> {
+    let stored = Zeroizing::new(serde_json::to_vec(&serde_json::json!({
+        "key": STANDARD.encode(key_bytes),
+        "salt": salt,
+    }))?);
+    write_private(&sync_key_path(), &stored)?;
+    Ok(())
+}
+
+pub fn remove_sync_key() {
+    std::fs::remove_file(sync_key_path()).ok();
+}
+
+/// Reads the stored sync key into memory, an unreadable file leaves sync without a passphrase
+pub fn load_sync_key() {
+    let Ok(stored) = std::fs::read(sync_key_path()).map(Zeroizing::new) else {
+        return;
+    };
+
+    let stored = serde_json::from_slice::<serde_json::Value>(&stored).ok();
+    let field = |name: &str| {
+        stored
+            .as_ref()
+            .and_then(|stored| stored.get(name))
+            .and_then(|value| value.as_str())
+    };
+    let key_bytes = field("key")
+        .and_then(|key| STANDARD.decode(key).ok())
+        .map(Zeroizing::new)
+        .filter(|bytes| bytes.len() == 32);
+    let (Some(key_bytes), Some(salt)) = (key_bytes, field("salt")) else {
+        printlog!("sync key file is invalid, payloads are not sealed");
+        return;
+    };
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&key_bytes);
+    if let Err(e) = set_sync_key(Some((SecretKey::new(*key), salt.to_string()))) {
+        printlog!("failed to load sync key: {:?}", e);
+    }
+}
+
+/// Encrypts data for upload if a sync passphrase is set, otherwise returns it unchanged
+pub fn seal_sync_data(data: Vec<u8>) -> Result<Vec<u8>, EncryptionError> {
+    match get_sync_key()? {
+        Some(key) => encrypt_data_with_key(&data, &key),
+        None => Ok(data),
+    }
+}
+
+/// Decrypts downloaded data sealed with the sync passphrase, unsealed data is returned unchanged
+pub fn open_sync_data(data: Vec<u8>) -> Result<Vec<u8>, EncryptionError> {
+    if !looks_like_encrypted_data(&data) {
+        return Ok(data);
+    }
+
+    match get_sync_key()? {
+        Some(key) => decrypt_data_with_key(&data, &key),
+        None => Err(EncryptionError::SyncPassphraseRequired),
+    }
+}
+
 pub fn init_password_lock_event(action: PasswordAction) {
     get_app()
         .emit_to(
@@ -1,37 +1,42 @@
-use super::cipher::is_encryption_key_set;
+use super::cipher::{is_clipboard_key_set, is_vault_key_set};
 use super::decrypt::decrypt_clipboard;
+use super::search::{index_encrypted_clipboard_db, matches_search, search_index_subquery};
 use super::settings::get_global_settings;
-use super::sync::{get_sync_manager, get_sync_provider};
+use super::sync_outbox::queue_sync_changes;
 use crate::prelude::*;
 use crate::tao::connection::db;
-use crate::tao::global::{get_app, get_main_window};
+use crate::tao::global::{get_app, get_device_id, get_main_window};
 use crate::utils::providers::uuid_to_datetime;
 use chrono::NaiveDateTime;
 use common::builder::keyword::KeywordBuilder;
 use common::io::clipboard::trim_clipboard_data;
-use common::types::enums::{ClipboardTextType, ClipboardType, Language, ListenEvent};
+use common::types::enums::{
+    ClipboardTextType, ClipboardType, Language, ListenEvent, SyncOperation,
+};
 use common::types::orm_query::{FullClipboardDbo, FullClipboardDto};
+use common::types::sync::SyncRevision;
 use common::types::types::CommandError;
 use entity::clipboard::{self, Model};
-use entity::{
-    clipboard_file, clipboard_html, clipboard_image, clipboard_rtf, clipboard_text, settings,
-};
+use entity::{clipboard_file, clipboard_html, clipboard_image, clipboard_rtf, clipboard_text};
 use sea_orm::prelude::Uuid;
 use sea_orm::RelationTrait;
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, EntityTrait, JoinType, LoaderTrait, PaginatorTrait, QueryFilter,
     QueryOrder, QuerySelect, QueryTrait,
 };
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::{cmp::Reverse, collections::HashMap};
 use tauri::{Emitter, Manager};
 use tauri_plugin_clipboard::Clipboard;
 use tokio::try_join;
+use zeroize::Zeroizing;
+
+const PAGE_SIZE: u64 = 25;
+const SEARCH_BATCH_SIZE: u64 = 100;
 
 pub async fn load_clipboards_with_relations(
     clipboards: Vec<clipboard::Model>,
-) -> Vec<FullClipboardDto> {
-    let db = db().await.expect("Failed to establish connection");
+) -> Result<Vec<FullClipboardDto>, DbErr> {
+    let db = db().await?;
 
     let (texts, htmls, images, rtfs, files) = try_join!(
         clipboards.load_one(clipboard_text::Entity, &db),
@@ -39,11 +44,10 @@ pub async fn load_clipboards_with_relations(
         clipboards.load_one(clipboard_image::Entity, &db),
         clipboards.load_one(clipboard_rtf::Entity, &db),
         clipboards.load_many(clipboard_file::Entity, &db),
-    )
-    .expect("Failed to load clipboard relations");
+    )?;
 
     // Zip everything together, taking first item from each Vec or None if empty
-    clipboards
+    Ok(clipboards
         .into_iter()
         .zip(texts)
         .zip(htmls)
@@ -58,13 +62,23 @@ pub async fn load_clipboards_with_relations(
             rtf: r,
             files: f,
         })
-        .collect()
+        .collect())
+}
+
+/// Vault entries are hidden from the history while the vault is locked
+fn locked_vault_filter() -> Option<bool> {
+    (!is_vault_key_set()).then_some(false)
 }
 
 pub async fn get_clipboard_count_db() -> Result<u64, DbErr> {
     let db = db().await?;
 
-    let count = clipboard::Entity::find().count(&db).await?;
+    let count = clipboard::Entity::find()
+        .apply_if(locked_vault_filter(), |q, v| {
+            q.filter(clipboard::Column::Vault.eq(v))
+        })
+        .count(&db)
+        .await?;
 
     Ok(count)
 }
@@ -216,22 +230,25 @@ pub async fn get_clipboard_db(id: Uuid) -> Result<FullClipboardDto, DbErr> {
         .ok_or(DbErr::RecordNotFound("Clipboard not found".into()))?;
 
     Ok(load_clipboards_with_relations(vec![clipboard])
-        .await
+        .await?
         .remove(0))
 }
 
 pub async fn get_last_clipboard_db() -> Result<FullClipboardDto, DbErr> {
     let clipboard = clipboard::Entity::find()
+        .apply_if(locked_vault_filter(), |q, v| {
+            q.filter(clipboard::Column::Vault.eq(v))
+        })
         .order_by_desc(clipboard::Column::Id)
         .one(&db().await?)
         .await?
         .ok_or_else(|| DbErr::RecordNotFound("last clipboard not found".to_string()))?;
 
     let mut dto = load_clipboards_with_relations(vec![clipboard])
-        .await
+        .await?
         .remove(0);
 
-    if dto.clipboard.encrypted && is_encryption_key_set() {
+    if dto.clipboard.encrypted && is_clipboard_key_set(dto.clipboard.vault) {
         if let Ok(decrypted) = decrypt_clipboard(dto.clone()) {
             dto = decrypted;
         }
@@ -240,24 +257,21 @@ pub async fn get_last_clipboard_db() -> Result<FullClipboardDto, DbErr> {
     Ok(dto)
 }
 
-pub async fn get_all_clipboards_db() -> Result<Vec<FullClipboardDto>, DbErr> {
-    let db = db().await?;
-    let clipboards = clipboard::Entity::find().all(&db).await?;
-
-    Ok(load_clipboards_with_relations(clipboards).await)
-}
-
 pub async fn get_clipboards_db(
     cursor: Option<u64>,
     search: Option<String>,
     star: Option<bool>,
     img: Option<bool>,
+    device_id: Option<String>,
 ) -> Result<Vec<FullClipboardDto>, DbErr> {
     let db = db().await?;
     let (clipboard_keywords, text_keywords) = KeywordBuilder::build_default();
 
     let settings = get_global_settings();
 
+    // set when encrypted entries are matched through the search index
+    let mut indexed_search = None;
+
     let query = clipboard::Entity::find()
         .distinct()
         .join(JoinType::LeftJoin, clipboard::Relation::ClipboardText.def())
@@ -268,7 +282,13 @@ pub async fn get_clipboards_db(
         )
         .join(JoinType::LeftJoin, clipboard::Relation::ClipboardRtf.def())
         .join(JoinType::LeftJoin, clipboard::Relation::ClipboardFile.def())
+        .apply_if(locked_vault_filter(), |q, v| {
+            q.filter(clipboard::Column::Vault.eq(v))
+        })
         .apply_if(star, |q, s| q.filter(clipboard::Column::Star.eq(s)))
+        .apply_if(device_id, |q, d| {
+            q.filter(clipboard::Column::DeviceId.eq(d))
+        })
         .apply_if(img, |q, _| {
             q.filter(clipboard::Column::Types.contains(ClipboardType::Image.to_string()))
         })
@@ -296,65 +316,87 @@ pub async fn get_clipboards_db(
                     _ => q.filter(clipboard::Column::Types.contains(clip_type.to_string())),
                 }
             } else {
-                // Fallback to full-text search
-                q.filter(
-                    clipboard_text::Column::Data
-                        .contains(&s)
-                        .or(clipboard_file::Column::Name.contains(&s))
-                        .or(clipboard_file::Column::Extension.contains(&s))
-                        .or(clipboard_file::Column::MimeType.contains(&s))
-                        .or(clipboard_image::Column::Extension.contains(&s))
-                        .or(clipboard_text::Column::Type.contains(&s))
-                        .or(clipboard_html::Column::Data.contains(&s))
-                        .or(clipboard_rtf::Column::Data.contains(&s)),
-                )
-            }
-        })
-        .apply_if(search.as_ref().map(|s| s.to_lowercase()), |q, s| {
-            if let Some(clip_type) = KeywordBuilder::find_clipboard_type(
-                &s,
-                &Language::from_iso_code(&settings.language),
-                &clipboard_keywords,
-            ) {
-                match clip_type {
-                    ClipboardType::Text => {
-                        if let Some(text_type) = KeywordBuilder::find_text_type(
-                            &s,
-                            &Language::from_iso_code(&settings.language),
-                            &text_keywords,
-                        ) {
-                            q.filter(clipboard_text::Column::Type.eq(text_type.to_string()))
-                        } else {
-                            q.filter(
-                                clipboard_text::Column::Type
-                                    .eq(ClipboardTextType::Text.to_string()),
-                            )
-                        }
+                // Columns that are never encrypted
+                let metadata = clipboard_image::Column::Extension
+                    .contains(&s)
+                    .or(clipboard_text::Column::Type.contains(&s));
+
+                let content = clipboard_text::Column::Data
+                    .contains(&s)
+                    .or(clipboard_file::Column::Name.contains(&s))
+                    .or(clipboard_file::Column::Extension.contains(&s))
+                    .or(clipboard_file::Column::MimeType.contains(&s))
+                    .or(clipboard_html::Column::Data.contains(&s))
+                    .or(clipboard_rtf::Column::Data.contains(&s));
+
+                // Encrypted content can only be matched through the search index
+                match search_index_subquery(&s) {
+                    Some(indexed) => {
+                        indexed_search = Some(s.clone());
+                        q.filter(
+                            metadata
+                                .or(clipboard::Column::Encrypted.eq(false).and(content))
+                                .or(clipboard::Column::Id.in_subquery(indexed)),
+                        )
                     }
-                    _ => q.filter(clipboard::Column::Types.contains(clip_type.to_string())),
+                    // Fallback to full-text search
+                    None => q.filter(metadata.or(content)),
                 }
-            } else {
-                // Fallback to full-text search
-                q.filter(
-                    clipboard_text::Column::Data
-                        .contains(&s)
-                        .or(clipboard_file::Column::Name.contains(&s))
-                        .or(clipboard_file::Column::Extension.contains(&s))
-                        .or(clipboard_file::Column::MimeType.contains(&s))
-                        .or(clipboard_image::Column::Extension.contains(&s))
-                        .or(clipboard_text::Column::Type.contains(&s))
-                        .or(clipboard_html::Column::Data.contains(&s))
-                        .or(clipboard_rtf::Column::Data.contains(&s)),
-                )
             }
         })
-        .offset(cursor)
-        .limit(25)
         .order_by_desc(clipboard::Column::Id);
 
-    let clipboards = query.all(&db).await?;
+    let Some(search) = indexed_search else {
+        let clipboards = query.offset(cursor).limit(PAGE_SIZE).all(&db).await?;
+        return load_clipboards_with_relations(clipboards).await;
+    };
 
-    Ok(load_clipboards_with_relations(clipboards).await)
+    // An entry can hold every trigram of the search without containing it, so the
+    // page is filled with candidates that still match once decrypted
+    let mut skip = cursor.unwrap_or(0);
+    let mut offset = 0;
+    let mut clipboards = Vec::new();
+    loop {
+        let candidates = query
+            .clone()
+            .offset(offset)
+            .limit(SEARCH_BATCH_SIZE)
+            .all(&db)
+            .await?;
+        let exhausted = (candidates.len() as u64) < SEARCH_BATCH_SIZE;
+        offset += candidates.len() as u64;
+
+        for clipboard in load_clipboards_with_relations(candidates).await? {
+            if !is_search_match(&clipboard, &search) {
+                continue;
+            }
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            clipboards.push(clipboard);
+            if clipboards.len() as u64 == PAGE_SIZE {
+                return Ok(clipboards);
+            }
+        }
+
+        if exhausted {
+            return Ok(clipboards);
+        }
+    }
+}
+
+/// Whether a candidate of an indexed search contains `search`. Plain entries were
+/// already matched by the database, entries without a key are kept as they are
+fn is_search_match(clipboard: &FullClipboardDto, search: &str) -> bool {
+    if !clipboard.clipboard.encrypted || !is_clipboard_key_set(clipboard.clipboard.vault) {
+        return true;
+    }
+
+    match decrypt_clipboard(clipboard.clone()) {
+        Ok(decrypted) => matches_search(&Zeroizing::new(decrypted), search),
+        Err(_) => true,
+    }
 }
 
 pub async fn get_latest_syncable_cliboards_db() -> Result<Vec<FullClipboardDto>, DbErr> {
@@ -384,7 +426,7 @@ pub async fn get_latest_syncable_cliboards_db() -> Result<Vec<FullClipboardDto>,
         .into_values()
         .collect::<Vec<_>>();
 
-    clipboards.sort_by(|a, b| b.id.cmp(&a.id));
+    clipboards.sort_by_key(|clipboard| Reverse(clipboard.id));
 
     if !clipboards.is_empty() {
         let newest = &clipboards[0];
@@ -404,35 +446,63 @@ pub async fn get_latest_syncable_cliboards_db() -> Result<Vec<FullClipboardDto>,
         );
     }
 
-    Ok(load_clipboards_with_relations(clipboards).await)
+    load_clipboards_with_relations(clipboards).await
 }
 
 pub async fn star_clipboard_db(id: Uuid, star: bool) -> Result<bool, CommandError> {
     let db = db().await?;
 
+    let revision = get_clipboard_revision_db(id)
+        .await?
+        .unwrap_or_default()
+        .next(get_device_id());
+
     let model = clipboard::ActiveModel {
         id: Set(id),
         star: Set(star),
+        revision: Set(revision.revision),
+        modified_by: Set(revision.modified_by),
         ..Default::default()
     };
 
-    let clipboard = clipboard::Entity::update(model).exec(&db).await?;
-
-    let settings = get_app().state::<Mutex<settings::Model>>();
-    if settings.lock().expect("Failed to lock settings").sync {
-        let clipboard = load_clipboards_with_relations(vec![clipboard])
-            .await
-            .remove(0);
+    clipboard::Entity::update(model).exec(&db).await?;
 
-        tauri::async_runtime::spawn(async move {
-            let provider = get_sync_provider().await;
-            provider.star_clipboard(&clipboard).await
-        });
-    }
+    queue_sync_changes(vec![id], SyncOperation::Star).await?;
 
     Ok(true)
 }
 
+/// Marks a clipboard to never be synced, an uploaded one is removed from the remote
+pub async fn sync_exclude_clipboard_db(id: Uuid, excluded: bool) -> Result<bool, CommandError> {
+    let db = db().await?;
+
+    let model = clipboard::ActiveModel {
+        id: Set(id),
+        sync_excluded: Set(excluded),
+        ..Default::default()
+    };
+
+    clipboard::Entity::update(model).exec(&db).await?;
+
+    // the star operation uploads or removes it according to the sync filters
+    queue_sync_changes(vec![id], SyncOperation::Star).await?;
+
+    Ok(excluded)
+}
+
+/// Clipboards that are never synced, so remote deletions do not apply to them
+pub async fn get_sync_excluded_ids_db() -> Result<Vec<Uuid>, DbErr> {
+    let db = db().await?;
+
+    clipboard::Entity::find()
+        .filter(clipboard::Column::SyncExcluded.eq(true))
+        .select_only()
+        .column(clipboard::Column::Id)
+        .into_tuple()
+        .all(&db)
+        .await
+}
+
 pub async fn delete_clipboards_db(
     ids: Vec<Uuid>,
     command: Option<bool>,
@@ -445,8 +515,13 @@ pub async fn delete_clipboards_db(
         .exec(&db)
         .await?;
 
-    // Only spawn deletion task if records were actually deleted
     if result.rows_affected > 0 && settings.sync {
+        // Deletions that came from the remote side only need to reach the ui
+        if command.is_none() {
+            init_clipboards();
+            return Ok(());
+        }
+
         // Get the actually deleted IDs by querying what remains
         let remaining_ids = clipboard::Entity::find()
             .filter(clipboard::Column::Id.is_in(ids.clone()))
@@ -461,39 +536,40 @@ pub async fn delete_clipboards_db(
             .filter(|id| !remaining_ids.contains(id))
             .collect();
 
-        tauri::async_runtime::spawn(async move {
-            let provider = get_sync_provider().await;
+        queue_sync_changes(deleted_ids, SyncOperation::Delete).await?;
+    }
 
-            let clipboards = provider
-                .fetch_all_clipboards()
-                .await
-                .expect("Failed to fetch clipboards")
-                .into_iter()
-                .filter(|c| deleted_ids.contains(&c.id))
-                .collect::<Vec<_>>();
+    Ok(())
+}
 
-            if command.is_none() && !clipboards.is_empty() {
-                init_clipboards();
-            }
+/// Removes the clipboards copied on the given devices, e.g. on a revoked one
+pub async fn delete_device_clipboards_db(
+    device_ids: Vec<String>,
+    command: Option<bool>,
+) -> Result<(), CommandError> {
+    if device_ids.is_empty() {
+        return Ok(());
+    }
 
-            for clippy in clipboards {
-                printlog!(
-                    "deleting remote clipboard: {:?} command: {:?}",
-                    clippy.id,
-                    command
-                );
-                provider.mark_for_deletion(&clippy).await;
-                break;
-            }
-        });
+    let db = db().await?;
+
+    let ids: Vec<Uuid> = clipboard::Entity::find()
+        .filter(clipboard::Column::DeviceId.is_in(device_ids))
+        .select_only()
+        .column(clipboard::Column::Id)
+        .into_tuple()
+        .all(&db)
+        .await?;
+
+    if ids.is_empty() {
+        return Ok(());
     }
 
-    Ok(())
+    delete_clipboards_db(ids, command).await
 }
 
 pub async fn clear_clipboards_db(r#type: Option<ClipboardType>) -> Result<(), DbErr> {
     let db = db().await?;
-    let settings = get_global_settings();
     let mut remote_clipboards_to_delete = Vec::new();
 
     match r#type {
@@ -565,7 +641,7 @@ pub async fn clear_clipboards_db(r#type: Option<ClipboardType>) -> Result<(), Db
                         remote_clipboards_to_delete.push(clipboard.clone());
 
                         // If no types remain, delete the clipboard
-                        clipboard::Entity::delete_by_id(clipboard.id.clone())
+                        clipboard::Entity::delete_by_id(clipboard.id)
                             .exec(&db)
                             .await?;
                     } else {
@@ -575,7 +651,17 @@ pub async fn clear_clipboards_db(r#type: Option<ClipboardType>) -> Result<(), Db
                             types: Set(ClipboardType::to_json_value(&types)),
                             ..Default::default()
                         };
-                        clipboard::Entity::update(model).exec(&db).await?;
+                        let clipboard = clipboard::Entity::update(model).exec(&db).await?;
+
+                        // Drop search tokens of the removed content
+                        if clipboard.encrypted {
+                            let clipboard = load_clipboards_with_relations(vec![clipboard])
+                                .await?
+                                .remove(0);
+                            if let Err(e) = index_encrypted_clipboard_db(&clipboard).await {
+                                printlog!("Failed to reindex clipboard: {:?}", e);
+                            }
+                        }
                     }
                 }
             }
@@ -583,36 +669,11 @@ pub async fn clear_clipboards_db(r#type: Option<ClipboardType>) -> Result<(), Db
     }
 
     // Handle remote deletion if sync is enabled
-    if settings.sync && !remote_clipboards_to_delete.is_empty() {
-        tauri::async_runtime::spawn(async move {
-            let provider = get_sync_provider().await;
-            let manager = get_sync_manager();
-
-            // Fetch all remote clipboards
-            let remote_clipboards = provider.fetch_all_clipboards().await.unwrap_or_default();
-
-            // Stop the sync manager before making changes
-            manager.lock().await.stop().await;
-
-            // Filter remote clipboards that match our local ones to delete
-            let remote_to_delete: Vec<_> = remote_clipboards
-                .iter()
-                .filter(|remote| {
-                    remote_clipboards_to_delete
-                        .iter()
-                        .any(|local| local.id == remote.id)
-                })
-                .collect();
-
-            // Delete filtered clipboards using their provider IDs
-            for clippy in remote_to_delete {
-                provider.mark_for_deletion(clippy).await;
-            }
-
-            // Restart the sync manager
-            manager.lock().await.start().await;
-        });
-    }
+    queue_sync_changes(
+        remote_clipboards_to_delete.iter().map(|c| c.id).collect(),
+        SyncOperation::Delete,
+    )
+    .await?;
 
     Ok(())
 }
@@ -646,10 +707,30 @@ pub async fn get_clipboard_uuids_db() -> Result<HashMap<Uuid, (bool, NaiveDateTi
         .collect())
 }
 
+/// `None` if the clipboard does not exist locally
+pub async fn get_clipboard_revision_db(id: Uuid) -> Result<Option<SyncRevision>, DbErr> {
+    let db = db().await?;
+
+    let revision: Option<(i32, Option<String>)> = clipboard::Entity::find_by_id(id)
+        .select_only()
+        .columns([clipboard::Column::Revision, clipboard::Column::ModifiedBy])
+        .into_tuple()
+        .one(&db)
+        .await?;
+
+    Ok(revision.map(|(revision, modified_by)| SyncRevision {
+        revision,
+        modified_by,
+    }))
+}
+
 pub async fn copy_clipboard_from_index(i: u64) -> Result<Option<Model>, DbErr> {
     let db = db().await?;
 
     let model = clipboard::Entity::find()
+        .apply_if(locked_vault_filter(), |q, v| {
+            q.filter(clipboard::Column::Vault.eq(v))
+        })
         .order_by_desc(clipboard::Column::Id)
         .offset(Some(i))
         .limit(1)
@@ -675,23 +756,30 @@ pub async fn copy_clipboard_from_id(
     let clipboard = get_app().state::<Clipboard>();
 
     // Decrypt the clipboard data if it's encrypted
-    if clipboard_data.clipboard.encrypted && is_encryption_key_set() {
+    if clipboard_data.clipboard.encrypted && is_clipboard_key_set(clipboard_data.clipboard.vault) {
         clipboard_data = decrypt_clipboard(clipboard_data)
             .map_err(|e| DbErr::Custom(format!("Failed to decrypt clipboard: {}", e)))?;
     }
 
+    // wipe whatever is not handed over to the clipboard
+    let mut clipboard_data = Zeroizing::new(clipboard_data);
+
     let success = match requested_type {
         ClipboardType::Image => clipboard_data
             .image
+            .take()
             .and_then(|m| clipboard.write_image_binary(m.data).ok()),
         ClipboardType::Text => clipboard_data
             .text
+            .take()
             .and_then(|m| clipboard.write_text(m.data).ok()),
         ClipboardType::Html => clipboard_data
             .html
+            .take()
             .and_then(|m| clipboard.write_html(m.data).ok()),
         ClipboardType::Rtf => clipboard_data
             .rtf
+            .take()
             .and_then(|m| clipboard.write_rtf(m.data).ok()),
         ClipboardType::File => Some(
             clipboard_data
@@ -724,131 +812,32 @@ pub async fn copy_clipboard_from_id(
     Ok(success)
 }
 
-// Helper function to filter clipboards in memory
-pub fn filter_clipboards(
-    clipboards: &[FullClipboardDto],
-    search: Option<&String>,
-    star: Option<bool>,
-    img: Option<bool>,
-    settings: &settings::Model,
-) -> Vec<FullClipboardDto> {
-    use common::builder::keyword::KeywordBuilder;
-
-    let (clipboard_keywords, text_keywords) = KeywordBuilder::build_default();
-
-    clipboards
-        .iter()
-        .filter(|clipboard| {
-            // Apply star filter
-            if let Some(star_filter) = star {
-                if clipboard.clipboard.star != star_filter {
-                    return false;
-                }
-            }
-
-            // Apply image filter
-            if let Some(true) = img {
-                if let Some(types) = ClipboardType::from_json_value(&clipboard.clipboard.types) {
-                    if !types.contains(&ClipboardType::Image) {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
-            }
-
-            // Apply search filter
-            if let Some(search_text) = search {
-                let search_lower = search_text.to_lowercase();
-
-                // Try to match clipboard type from search
-                if let Some(clip_type) = KeywordBuilder::find_clipboard_type(
-                    &search_lower,
-                    &Language::from_iso_code(&settings.language),
-                    &clipboard_keywords,
-                ) {
-                    match clip_type {
-                        ClipboardType::Text => {
-                            if let Some(text) = &clipboard.text {
-                                if let Some(text_type) = KeywordBuilder::find_text_type(
-                                    &search_lower,
-                                    &Language::from_iso_code(&settings.language),
-                                    &text_keywords,
-                                ) {
-                                    return text.r#type == text_type.to_string();
-                                } else {
-                                    return text.r#type == ClipboardTextType::Text.to_string();
-                                }
-                            }
-                            return false;
-                        }
-                        _ => {
-                            if let Some(types) =
-                                ClipboardType::from_json_value(&clipboard.clipboard.types)
-                            {
-                                return types.contains(&clip_type);
-                            }
-                            return false;
-                        }
-                    }
-                }
-
-                // Fallback to full-text search
-                let contains_search = clipboard
-                    .text
-                    .as_ref()
-                    .map_or(false, |t| t.data.to_lowercase().contains(&search_lower))
-                    || clipboard
-                        .html
-                        .as_ref()
-                        .map_or(false, |h| h.data.to_lowercase().contains(&search_lower))
-                    || clipboard
-                        .rtf
-                        .as_ref()
-                        .map_or(false, |r| r.data.to_lowercase().contains(&search_lower))
-                    || clipboard.files.iter().any(|f| {
-                        f.name.to_lowercase().contains(&search_lower)
-                            || f.extension
-                                .as_ref()
-                                .map_or(false, |e| e.to_lowercase().contains(&search_lower))
-                            || f.mime_type
-                                .as_ref()
-                                .map_or(false, |m| m.to_lowercase().contains(&search_lower))
-                    })
-                    || clipboard.image.as_ref().map_or(false, |i| {
-                        i.extension.to_lowercase().contains(&search_lower)
-                    })
-                    || clipboard
-                        .text
-                        .as_ref()
-                        .map_or(false, |t| t.r#type.to_lowercase().contains(&search_lower));
-
-                if !contains_search {
-                    return false;
-                }
-            }
-
-            true
-        })
-        .cloned()
-        .collect()
-}
-
 pub fn init_clipboards() {
-    get_main_window()
-        .emit(ListenEvent::InitClipboards.to_string().as_str(), ())
-        .expect("Failed to emit event");
+    if let Err(e) = get_main_window().emit(ListenEvent::InitClipboards.to_string().as_str(), ()) {
+        printlog!("failed to emit clipboards event: {:?}", e);
+    }
 }
 
 pub fn new_clipboard_event(mut clipboard: FullClipboardDto) {
-    if clipboard.clipboard.encrypted && is_encryption_key_set() {
-        clipboard = decrypt_clipboard(clipboard).expect("Failed to decrypt clipboard");
+    if clipboard.clipboard.vault && !is_vault_key_set() {
+        return;
     }
 
-    get_main_window()
-        .emit(
-            ListenEvent::NewClipboard.to_string().as_str(),
-            trim_clipboard_data(vec![clipboard]).remove(0),
-        )
-        .expect("Failed to emit event");
+    if clipboard.clipboard.encrypted && is_clipboard_key_set(clipboard.clipboard.vault) {
+        // synced clipboards may come from a device with another key
+        clipboard = match decrypt_clipboard(clipboard) {
+            Ok(clipboard) => clipboard,
+            Err(e) => {
+                printlog!("failed to decrypt new clipboard: {:?}", e);
+                return;
+            }
+        };
+    }
+
+    if let Err(e) = get_main_window().emit(
+        ListenEvent::NewClipboard.to_string().as_str(),
+        trim_clipboard_data(vec![clipboard]).remove(0),
+    ) {
+        printlog!("failed to emit new clipboard event: {:?}", e);
+    }
 }
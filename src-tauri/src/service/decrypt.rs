@@ -1,132 +1,22 @@
 use super::{
-    cipher::{clear_encryption_key, is_encryption_key_set, verify_encryption_password},
-    clipboard::load_clipboards_with_relations,
-    settings::{get_global_settings, update_settings_db},
-    sync::{get_sync_manager, get_sync_provider},
-};
-use crate::{
-    prelude::*,
-    service::clipboard::upsert_clipboard_dto,
-    tao::{connection::db, global::get_app},
+    cipher::{get_clipboard_key, is_encryption_key_set, verify_encryption_password},
+    job::run_job,
 };
+use crate::prelude::*;
 use base64::{engine::general_purpose::STANDARD, Engine};
-use common::{constants::ENCRYPTION_MAGIC_STRING, types::{
-    cipher::{EncryptionError, ENCRYPTION_KEY},
-    enums::ListenEvent,
-    orm_query::FullClipboardDto,
-    types::{CommandError, Progress},
-}};
-use entity::clipboard;
+use common::{
+    constants::ENCRYPTION_MAGIC_STRING,
+    types::{
+        cipher::EncryptionError, enums::JobKind, orm_query::FullClipboardDto, types::CommandError,
+    },
+};
 use ring::aead;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
-use std::thread::sleep;
-use tauri::{Emitter, EventTarget};
+use zeroize::Zeroizing;
 
+/// Decrypts every local and remote clipboard through a resumable job, the
+/// encryption is only switched off once the job completed
 pub async fn decrypt_all_clipboards() -> Result<(), CommandError> {
-    let settings = get_global_settings();
-    let db = db().await?;
-
-    // Get all local encrypted clipboards
-    let mut clipboards = load_clipboards_with_relations(
-        clipboard::Entity::find()
-            .filter(clipboard::Column::Encrypted.eq(true))
-            .all(&db)
-            .await?,
-    )
-    .await;
-
-    // Get remote clipboards if sync enabled
-    let (provider, remote_clipboards) = if settings.sync {
-        let provider = get_sync_provider().await;
-        let remote_clipboards = provider
-            .fetch_all_clipboards()
-            .await
-            .expect("Failed to fetch remote clipboards");
-
-        // Download all remote clipboards with progress logging
-        let download_total = remote_clipboards.len();
-        for (index, remote) in remote_clipboards.iter().enumerate() {
-            get_app().emit_to(
-                EventTarget::any(),
-                ListenEvent::Progress.to_string().as_str(),
-                Progress {
-                    label: "SETTINGS.ENCRYPT.DOWNLOADING_REMOTE_CLIPBOARDS".to_string(),
-                    total: download_total,
-                    current: index + 1,
-                },
-            )?;
-
-            if !remote.encrypted {
-                continue;
-            }
-
-            if remote.deleted_at.is_some() {
-                continue;
-            }
-
-            if clipboards.iter().any(|c| c.clipboard.id == remote.id) {
-                continue;
-            }
-
-            if let Ok(clipboard) = provider.download_by_id(&remote.provider_id).await {
-                clipboards.push(clipboard);
-            }
-        }
-        (Some(provider), remote_clipboards)
-    } else {
-        (None, Vec::new())
-    };
-
-    let total = clipboards.len();
-    for (index, clipboard) in clipboards.into_iter().enumerate() {
-        get_app().emit_to(
-            EventTarget::any(),
-            ListenEvent::Progress.to_string().as_str(),
-            Progress {
-                label: "SETTINGS.ENCRYPT.DECRYPTION_PROGRESS".to_string(),
-                total,
-                current: index + 1,
-            },
-        )?;
-        match decrypt_clipboard(clipboard.clone()) {
-            Ok(decrypted) => {
-                upsert_clipboard_dto(decrypted.clone()).await?;
-
-                if let Some(provider) = &provider {
-                    if let Some(remote) = remote_clipboards
-                        .iter()
-                        .find(|r| r.id == decrypted.clipboard.id)
-                    {
-                        provider.update_clipboard(&decrypted, remote).await.ok();
-                    }
-                }
-            }
-            Err(e) => {
-                printlog!(
-                    "Failed to decrypt clipboard {}: {:?}",
-                    clipboard.clipboard.id,
-                    e
-                );
-
-                // Delete locally
-                clipboard::Entity::delete_by_id(clipboard.clipboard.id)
-                    .exec(&db)
-                    .await?;
-
-                // Mark for deletion remotely if sync is enabled
-                if let Some(provider) = &provider {
-                    if let Some(remote) = remote_clipboards
-                        .iter()
-                        .find(|r| r.id == clipboard.clipboard.id)
-                    {
-                        provider.mark_for_deletion(remote).await;
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(())
+    run_job(JobKind::Decrypt).await
 }
 
 pub fn decrypt_clipboard(
@@ -136,6 +26,10 @@ pub fn decrypt_clipboard(
         return Err(EncryptionError::NotEncrypted);
     }
 
+    // vault entries are encrypted with the vault key instead of the history key
+    let key_bytes = get_clipboard_key(clipboard.clipboard.vault)?;
+    let decrypt_data = |data: &[u8]| decrypt_data_with_key(data, &key_bytes);
+
     if let Some(text) = &mut clipboard.text {
         match STANDARD.decode(&text.data) {
             Ok(decoded) => {
@@ -390,8 +284,11 @@ pub fn decrypt_clipboard(
     Ok(clipboard)
 }
 
-/// Decrypts data using AES-256-GCM
-pub fn decrypt_data(encrypted_data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+/// Decrypts data using AES-256-GCM with the given key
+pub fn decrypt_data_with_key(
+    encrypted_data: &[u8],
+    key_bytes: &[u8; 32],
+) -> Result<Vec<u8>, EncryptionError> {
     let magic_bytes = ENCRYPTION_MAGIC_STRING.as_bytes();
 
     // Validate input has minimum required length
@@ -405,13 +302,8 @@ pub fn decrypt_data(encrypted_data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
         return Err(EncryptionError::NotEncrypted);
     }
 
-    let key_bytes = ENCRYPTION_KEY
-        .lock()
-        .map_err(|_| EncryptionError::KeyLockFailed)?
-        .ok_or(EncryptionError::NoKey)?;
-
     // Create unbound key from key bytes
-    let unbound_key = aead::UnboundKey::new(&aead::AES_256_GCM, &key_bytes)
+    let unbound_key = aead::UnboundKey::new(&aead::AES_256_GCM, key_bytes)
         .map_err(|_| EncryptionError::DecryptionFailed)?;
     let key = aead::LessSafeKey::new(unbound_key);
 
@@ -424,7 +316,8 @@ pub fn decrypt_data(encrypted_data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
     );
 
     // Get encrypted data (after magic bytes and nonce)
-    let mut in_out = encrypted_data[magic_bytes_len + 12..].to_vec();
+    // wiped on drop as it holds the plaintext after opening
+    let mut in_out = Zeroizing::new(encrypted_data[magic_bytes_len + 12..].to_vec());
 
     // Decrypt in place
     match key.open_in_place(nonce, aead::Aad::empty(), &mut in_out) {
@@ -444,23 +337,8 @@ pub async fn remove_encryption(password: String) -> Result<(), CommandError> {
     if !is_password_valid {
         return Err(CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD"));
     }
-    // Stop the sync manager before making changes
-    get_sync_manager().lock().await.stop().await;
+    // the job turns encryption off and clears the key once it completed
     decrypt_all_clipboards().await?;
 
-    if get_global_settings().sync {
-        // race condition with settings sync
-        tauri::async_runtime::spawn(async {
-            sleep(std::time::Duration::from_secs(5));
-            get_sync_manager().lock().await.start().await;
-        });
-    }
-
-    let mut settings = get_global_settings();
-    settings.encryption = false;
-    update_settings_db(settings).await?;
-
-    clear_encryption_key();
-
     Ok(())
 }
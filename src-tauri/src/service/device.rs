@@ -0,0 +1,123 @@
+use super::{
+    clipboard::delete_device_clipboards_db, settings::get_global_settings, sync::get_sync_provider,
+};
+use crate::tao::{config::get_device_name, connection::db, global::get_device_id};
+use chrono::Utc;
+use common::types::{
+    sync::{Device, DeviceEntry},
+    types::CommandError,
+};
+use entity::device;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DbErr, EntityTrait};
+use std::{cmp::Reverse, collections::HashMap};
+
+/// Known devices, newest first, this device is listed even before its first sync
+pub async fn get_devices_db() -> Result<Vec<Device>, DbErr> {
+    let db = db().await?;
+    let device_id = get_device_id();
+
+    let mut devices: Vec<Device> = device::Entity::find()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|model| Device {
+            current: model.id == device_id,
+            id: model.id,
+            name: model.name,
+            last_seen_at: model.last_seen_at,
+            revoked_at: model.revoked_at,
+        })
+        .collect();
+
+    if !devices.iter().any(|device| device.current) {
+        devices.push(Device {
+            id: device_id.to_string(),
+            name: get_device_name(),
+            last_seen_at: Utc::now().naive_utc(),
+            revoked_at: None,
+            current: true,
+        });
+    }
+
+    devices.sort_by_key(|device| Reverse(device.last_seen_at));
+
+    Ok(devices)
+}
+
+/// Merges the remote registry into the local one and returns the merged registry
+pub async fn upsert_devices_db(
+    devices: &HashMap<String, DeviceEntry>,
+) -> Result<HashMap<String, DeviceEntry>, DbErr> {
+    let db = db().await?;
+
+    for (id, device) in devices {
+        match device::Entity::find_by_id(id.clone()).one(&db).await? {
+            Some(known) => {
+                let merged = DeviceEntry::from(&known).merge(device);
+                // the seen tombstone revision is only kept in the remote registry
+                let unchanged = DeviceEntry {
+                    seen_revision: merged.seen_revision,
+                    ..DeviceEntry::from(&known)
+                };
+                if merged == unchanged {
+                    continue;
+                }
+
+                let mut model: device::ActiveModel = known.into();
+                model.name = Set(merged.name);
+                model.last_seen_at = Set(merged.last_seen_at);
+                model.revoked_at = Set(merged.revoked_at);
+                model.update(&db).await?;
+            }
+            None => {
+                device::ActiveModel {
+                    id: Set(id.clone()),
+                    name: Set(device.name.clone()),
+                    last_seen_at: Set(device.last_seen_at),
+                    revoked_at: Set(device.revoked_at),
+                }
+                .insert(&db)
+                .await?;
+            }
+        }
+    }
+
+    Ok(device::Entity::find()
+        .all(&db)
+        .await?
+        .iter()
+        .map(|model| (model.id.clone(), DeviceEntry::from(model)))
+        .collect())
+}
+
+/// Revokes a lost device, its clipboards are removed here and on the remote and the device
+/// stops syncing once it sees the revocation
+pub async fn revoke_lost_device(id: String) -> Result<(), CommandError> {
+    if id == get_device_id() {
+        return Err(CommandError::new("MAIN.ERROR.DEVICE_CURRENT"));
+    }
+
+    let db = db().await?;
+
+    let known = device::Entity::find_by_id(id.clone())
+        .one(&db)
+        .await?
+        .ok_or_else(|| CommandError::new("MAIN.ERROR.DEVICE_NOT_FOUND"))?;
+
+    let mut model: device::ActiveModel = known.into();
+    model.revoked_at = Set(Some(Utc::now().naive_utc()));
+    let revoked = model.update(&db).await?;
+
+    delete_device_clipboards_db(vec![id.clone()], Some(true)).await?;
+
+    // a failed write is repeated by the next sync, which pushes local revocations
+    if get_global_settings().sync {
+        let provider = get_sync_provider().await?;
+        provider
+            .put_device(&id, &DeviceEntry::from(&revoked))
+            .await?;
+        provider.flush_manifest().await?;
+    }
+
+    Ok(())
+}
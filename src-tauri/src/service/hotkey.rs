@@ -3,11 +3,11 @@ use crate::{
     prelude::*,
     tao::{
         connection::db,
-        global::{get_app, get_main_window},
+        global::{get_app, get_hotkey_store, get_main_window},
     },
-    utils::hotkey_manager::register_hotkeys,
+    utils::hotkey_manager::{register_hotkeys, unregister_hotkeys, upsert_hotkeys_in_store},
 };
-use common::types::enums::ListenEvent;
+use common::types::{enums::ListenEvent, sync::SyncHotkey};
 use entity::hotkey::{self, ActiveModel, Model};
 use sea_orm::{ActiveModelTrait, EntityTrait};
 use tauri::{Emitter, EventTarget};
@@ -32,6 +32,62 @@ pub async fn update_hotkey_db(hotkey: Model) -> Result<Model, DbErr> {
     Ok(updated_hotkey)
 }
 
+/// Bindings as synced, ordered by event so equal bindings compare equal
+pub async fn get_sync_hotkeys_db() -> Result<Vec<SyncHotkey>, DbErr> {
+    let mut hotkeys: Vec<SyncHotkey> = get_all_hotkeys_db()
+        .await?
+        .iter()
+        .map(SyncHotkey::from)
+        .collect();
+    hotkeys.sort_by(|a, b| a.event.cmp(&b.event));
+
+    Ok(hotkeys)
+}
+
+/// Applies synced bindings to the hotkeys with the same event, events unknown to this
+/// version are skipped. Returns whether any hotkey changed
+pub async fn update_sync_hotkeys_db(hotkeys: Vec<SyncHotkey>) -> Result<bool, DbErr> {
+    let mut changed = false;
+
+    for local in get_all_hotkeys_db().await? {
+        let Some(remote) = hotkeys.iter().find(|hotkey| hotkey.event == local.event) else {
+            continue;
+        };
+        if SyncHotkey::from(&local) == *remote {
+            continue;
+        }
+
+        update_hotkey_db(Model {
+            ctrl: remote.ctrl,
+            alt: remote.alt,
+            shift: remote.shift,
+            key: remote.key.clone(),
+            status: remote.status,
+            ..local
+        })
+        .await?;
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+/// Registers the hotkeys again after their bindings changed, window hotkeys only if they
+/// were registered before
+pub async fn reload_hotkeys() {
+    let window_hotkeys = get_hotkey_store()
+        .values()
+        .any(|hotkey| hotkey.state && !hotkey.is_global);
+
+    unregister_hotkeys(true);
+    if let Err(e) = upsert_hotkeys_in_store().await {
+        printlog!("failed to load hotkeys: {:?}", e);
+    }
+    register_hotkeys(window_hotkeys);
+
+    init_hotkey_window();
+}
+
 pub fn init_hotkey_window() {
     get_app()
         .emit_to(
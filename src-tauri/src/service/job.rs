@@ -0,0 +1,364 @@
+use super::{
+    cipher::clear_encryption_key,
+    clipboard::{init_clipboards, load_clipboards_with_relations, upsert_clipboard_dto},
+    decrypt::decrypt_clipboard,
+    encrypt::encrypt_clipboard,
+    search::{index_clipboard_db, remove_clipboard_index},
+    settings::{get_global_settings, update_settings_db},
+    sync::{get_sync_manager, get_sync_provider},
+};
+use crate::{
+    prelude::*,
+    tao::{
+        connection::db,
+        global::{get_app, get_job_cancel, get_job_lock},
+    },
+};
+use common::{
+    constants::JOB_BATCH_SIZE,
+    types::{
+        enums::{JobKind, JobStatus, ListenEvent},
+        orm_query::FullClipboardDto,
+        sync::{Clippy, SyncProvider},
+        types::{CommandError, Progress},
+    },
+};
+use entity::{clipboard, job};
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ColumnTrait, EntityTrait, Iterable, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect,
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+};
+use tauri::{Emitter, EventTarget};
+use zeroize::Zeroizing;
+
+/// Runs a bulk encryption or decryption of the history. An unfinished job of the
+/// same kind is resumed with its progress instead of starting over.
+/// Boxed as the job restarts the sync loop, which may start a job again
+pub fn run_job(kind: JobKind) -> Pin<Box<dyn Future<Output = Result<(), CommandError>> + Send>> {
+    Box::pin(execute_job(kind))
+}
+
+async fn execute_job(kind: JobKind) -> Result<(), CommandError> {
+    let Ok(_guard) = get_job_lock().try_lock() else {
+        return Err(CommandError::new("MAIN.ERROR.JOB_ALREADY_RUNNING"));
+    };
+    get_job_cancel().store(false, Ordering::SeqCst);
+
+    let job = get_or_create_job_db(kind).await?;
+    printlog!("(job) {} {} from {:?}", job.kind, job.id, job.cursor);
+
+    // The sync loop must not pick up half processed clipboards
+    get_sync_manager().lock().await.stop().await;
+
+    let result = process_job(kind, job.clone()).await;
+
+    let status = match &result {
+        Ok(status) => *status,
+        Err(_) => JobStatus::Failed,
+    };
+    let error = result.as_ref().err().map(|e| format!("{:?}", e));
+
+    if status == JobStatus::Completed {
+        if let Err(e) = complete_job(kind).await {
+            printlog!("(job) failed to complete {}: {:?}", job.id, e);
+        }
+    }
+
+    let mut model: job::ActiveModel = find_job_db(job.id).await?.into();
+    model.status = Set(status.to_string());
+    model.error = Set(error);
+    model.updated_at = Set(chrono::Utc::now().naive_utc());
+    model.update(&db().await?).await?;
+
+    printlog!("(job) {} {}", job.id, status.to_string());
+
+    // Restart sync no matter how the job ended, settings are already updated at this point
+    if get_global_settings().sync {
+        get_sync_manager().lock().await.start().await;
+    }
+
+    init_clipboards();
+
+    result.map(|_| ())
+}
+
+/// Asks the running job to stop after the clipboard it is currently processing
+pub fn cancel_job() {
+    get_job_cancel().store(true, Ordering::SeqCst);
+}
+
+/// Kind of the latest job that was interrupted or failed, a job the user cancelled is not
+/// picked up again
+pub async fn unfinished_job_db() -> Result<Option<JobKind>, DbErr> {
+    let job = job::Entity::find()
+        .filter(job::Column::Status.is_in([
+            JobStatus::Running.to_string(),
+            JobStatus::Failed.to_string(),
+        ]))
+        .order_by_desc(job::Column::Id)
+        .one(&db().await?)
+        .await?;
+
+    Ok(job.and_then(|job| JobKind::iter().find(|kind| kind.to_string() == job.kind)))
+}
+
+/// Resumes the latest job that was interrupted or failed
+pub async fn resume_job() -> Result<(), CommandError> {
+    match unfinished_job_db().await? {
+        Some(kind) => run_job(kind).await,
+        None => Ok(()),
+    }
+}
+
+async fn find_job_db(id: Uuid) -> Result<job::Model, DbErr> {
+    job::Entity::find_by_id(id)
+        .one(&db().await?)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound("job not found".to_string()))
+}
+
+async fn get_or_create_job_db(kind: JobKind) -> Result<job::Model, DbErr> {
+    let db = db().await?;
+
+    let unfinished = job::Entity::find()
+        .filter(job::Column::Kind.eq(kind.to_string()))
+        .filter(job::Column::Status.ne(JobStatus::Completed.to_string()))
+        .order_by_desc(job::Column::Id)
+        .one(&db)
+        .await?;
+
+    match unfinished {
+        Some(job) => {
+            let mut model: job::ActiveModel = job.into();
+            model.status = Set(JobStatus::Running.to_string());
+            model.error = Set(None);
+            model.update(&db).await
+        }
+        None => {
+            // an opposite job left behind is superseded by this one
+            job::Entity::delete_many()
+                .filter(job::Column::Status.ne(JobStatus::Completed.to_string()))
+                .exec(&db)
+                .await?;
+
+            job::ActiveModel {
+                id: Set(Uuid::now_v7()),
+                kind: Set(kind.to_string()),
+                status: Set(JobStatus::Running.to_string()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+        }
+    }
+}
+
+/// Local clipboards the job still has to process, processed ones drop out of the filter.
+/// The cursor only tracks progress, remote clipboards downloaded when a job resumes have
+/// older ids than it
+fn pending_clipboards(kind: JobKind) -> sea_orm::Select<clipboard::Entity> {
+    clipboard::Entity::find()
+        .filter(match kind {
+            JobKind::Encrypt => clipboard::Column::Encrypted.eq(false),
+            // vault entries stay encrypted
+            JobKind::Decrypt => clipboard::Column::Encrypted
+                .eq(true)
+                .and(clipboard::Column::Vault.eq(false)),
+        })
+        .order_by_asc(clipboard::Column::Id)
+}
+
+fn emit_progress(label: &str, total: usize, current: usize) -> Result<(), CommandError> {
+    get_app().emit_to(
+        EventTarget::any(),
+        ListenEvent::Progress.to_string().as_str(),
+        Progress {
+            label: label.to_string(),
+            total,
+            current,
+        },
+    )?;
+
+    Ok(())
+}
+
+async fn process_job(kind: JobKind, job: job::Model) -> Result<JobStatus, CommandError> {
+    let settings = get_global_settings();
+    let db = db().await?;
+
+    // Store remote clipboards that still have to be processed locally, so they
+    // are processed like every other clipboard
+    let remote_clipboards = if settings.sync {
+        let provider = get_sync_provider().await?;
+        let remote_clipboards = provider.fetch_all_clipboards().await?;
+
+        let local_ids: Vec<Uuid> = clipboard::Entity::find()
+            .select_only()
+            .column(clipboard::Column::Id)
+            .into_tuple()
+            .all(&db)
+            .await?;
+
+        let download_total = remote_clipboards.len();
+        for (index, remote) in remote_clipboards.iter().enumerate() {
+            if get_job_cancel().load(Ordering::SeqCst) {
+                return Ok(JobStatus::Cancelled);
+            }
+
+            let matches_kind = match kind {
+                JobKind::Encrypt => !remote.encrypted,
+                JobKind::Decrypt => remote.encrypted,
+            };
+
+            if !matches_kind || remote.deleted_at.is_some() || local_ids.contains(&remote.id) {
+                continue;
+            }
+
+            // the provider error is not Send, so it must not live across the upsert
+            let downloaded = provider.download_by_id(&remote.provider_id).await.ok();
+            if let Some(clipboard) = downloaded.filter(|c| !c.clipboard.vault) {
+                upsert_clipboard_dto(clipboard).await?;
+            }
+
+            emit_progress(
+                "SETTINGS.ENCRYPT.DOWNLOADING_REMOTE_CLIPBOARDS",
+                download_total,
+                index + 1,
+            )?;
+        }
+
+        Some((provider, remote_clipboards))
+    } else {
+        None
+    };
+
+    let label = match kind {
+        JobKind::Encrypt => "SETTINGS.ENCRYPT.ENCRYPTION_PROGRESS_LOCAL",
+        JobKind::Decrypt => "SETTINGS.ENCRYPT.DECRYPTION_PROGRESS",
+    };
+
+    let mut current = job.current as usize;
+    let total = current + pending_clipboards(kind).count(&db).await? as usize;
+
+    loop {
+        let batch = load_clipboards_with_relations(
+            pending_clipboards(kind)
+                .limit(JOB_BATCH_SIZE)
+                .all(&db)
+                .await?,
+        )
+        .await?;
+
+        if batch.is_empty() {
+            return Ok(JobStatus::Completed);
+        }
+
+        for clipboard in batch {
+            if get_job_cancel().load(Ordering::SeqCst) {
+                return Ok(JobStatus::Cancelled);
+            }
+
+            let id = clipboard.clipboard.id;
+            let remote = remote_clipboards.as_ref().and_then(|(provider, remotes)| {
+                remotes
+                    .iter()
+                    .find(|r| r.id == id)
+                    .map(|remote| (provider.clone(), remote))
+            });
+
+            match kind {
+                JobKind::Encrypt => encrypt_job_clipboard(clipboard, remote).await?,
+                JobKind::Decrypt => decrypt_job_clipboard(clipboard, remote).await?,
+            }
+
+            current += 1;
+
+            job::ActiveModel {
+                id: Set(job.id),
+                cursor: Set(Some(id)),
+                current: Set(current as i32),
+                total: Set(total as i32),
+                updated_at: Set(chrono::Utc::now().naive_utc()),
+                ..Default::default()
+            }
+            .update(&db)
+            .await?;
+
+            emit_progress(label, total, current)?;
+        }
+    }
+}
+
+type RemoteClipboard<'a> = Option<(Arc<dyn SyncProvider>, &'a Clippy)>;
+
+async fn encrypt_job_clipboard(
+    clipboard: FullClipboardDto,
+    remote: RemoteClipboard<'_>,
+) -> Result<(), CommandError> {
+    let clipboard = Zeroizing::new(clipboard);
+    let encrypted = encrypt_clipboard((*clipboard).clone());
+    upsert_clipboard_dto(encrypted.clone()).await?;
+    index_clipboard_db(&clipboard).await?;
+
+    if let Some((provider, remote)) = remote {
+        provider.update_clipboard(&encrypted, remote).await.ok();
+    }
+
+    Ok(())
+}
+
+async fn decrypt_job_clipboard(
+    clipboard: FullClipboardDto,
+    remote: RemoteClipboard<'_>,
+) -> Result<(), CommandError> {
+    match decrypt_clipboard(clipboard.clone()) {
+        Ok(decrypted) => {
+            upsert_clipboard_dto(decrypted.clone()).await?;
+            // plaintext entries are searched directly, their tokens would only outlive the key
+            remove_clipboard_index(&db().await?, decrypted.clipboard.id).await?;
+
+            if let Some((provider, remote)) = remote {
+                provider.update_clipboard(&decrypted, remote).await.ok();
+            }
+        }
+        Err(e) => {
+            printlog!(
+                "Failed to decrypt clipboard {}: {:?}",
+                clipboard.clipboard.id,
+                e
+            );
+
+            // Delete locally
+            clipboard::Entity::delete_by_id(clipboard.clipboard.id)
+                .exec(&db().await?)
+                .await?;
+
+            // Mark for deletion remotely if sync is enabled
+            if let Some((provider, remote)) = remote {
+                if let Err(e) = provider.mark_for_deletion(remote).await {
+                    printlog!("(job) failed to delete remote {}: {}", remote.id, e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Settings change that only applies once every clipboard was processed
+async fn complete_job(kind: JobKind) -> Result<(), CommandError> {
+    if kind == JobKind::Decrypt {
+        let mut settings = get_global_settings();
+        settings.encryption = false;
+        settings.encryption_keys = None;
+        update_settings_db(settings).await?;
+        clear_encryption_key();
+    }
+
+    Ok(())
+}
@@ -40,7 +40,7 @@ pub async fn type_last_clipboard_linux() -> Result<(), Box<dyn std::error::Error
             if content.len() < 500 {
                 std::thread::sleep(Duration::from_millis(300));
                 Command::new("xdotool")
-                    .args(&["type", "--clearmodifiers", "--", &content])
+                    .args(["type", "--clearmodifiers", "--", &content])
                     .output()?;
             }
         }
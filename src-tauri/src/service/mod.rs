@@ -3,7 +3,15 @@ pub mod decrypt;
 pub mod encrypt;
 pub mod hotkey;
 pub mod keyboard;
+pub mod search;
 pub mod settings;
 pub mod sync;
 pub mod window;
-pub mod cipher;
\ No newline at end of file
+pub mod cipher;
+pub mod vault;
+pub mod job;
+pub mod sync_outbox;
+pub mod device;
+
+#[cfg(test)]
+mod tests;
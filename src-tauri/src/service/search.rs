@@ -0,0 +1,243 @@
+use super::{
+    cipher::{get_search_key, is_clipboard_key_set, is_encryption_key_set, is_vault_key_set},
+    clipboard::load_clipboards_with_relations,
+    decrypt::decrypt_clipboard,
+    settings::get_global_settings,
+};
+use crate::{prelude::*, tao::connection::db};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    constants::SEARCH_INDEX_NGRAM,
+    types::{orm_query::FullClipboardDto, types::CommandError},
+};
+use entity::{clipboard, clipboard_search};
+use ring::hmac;
+use sea_orm::{
+    prelude::Uuid,
+    sea_query::{Expr, SelectStatement},
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect, QueryTrait,
+};
+use std::collections::HashSet;
+use zeroize::Zeroizing;
+
+/// Splits text into the lowercase 1- to 3-grams that get stored in the search index.
+/// The whole text is split, so any part of it can be found
+pub fn index_terms(text: &str) -> HashSet<String> {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+
+    (1..=SEARCH_INDEX_NGRAM)
+        .flat_map(|size| chars.windows(size).map(|w| w.iter().collect::<String>()))
+        .collect()
+}
+
+/// Terms a search string has to match, the whole string if short or all of its trigrams
+pub fn query_terms(search: &str) -> HashSet<String> {
+    let chars: Vec<char> = search.to_lowercase().chars().collect();
+
+    if chars.len() <= SEARCH_INDEX_NGRAM {
+        return HashSet::from([chars.iter().collect()]);
+    }
+
+    chars
+        .windows(SEARCH_INDEX_NGRAM)
+        .map(|w| w.iter().collect())
+        .collect()
+}
+
+/// Whether a decrypted clipboard contains `search`, the index only tells that all of
+/// its trigrams occur somewhere in the entry
+pub fn matches_search(clipboard: &FullClipboardDto, search: &str) -> bool {
+    let search = search.to_lowercase();
+    let contains = |data: &str| data.to_lowercase().contains(&search);
+
+    clipboard
+        .text
+        .as_ref()
+        .is_some_and(|text| contains(&text.data) || contains(&text.r#type))
+        || clipboard
+            .html
+            .as_ref()
+            .is_some_and(|html| contains(&html.data))
+        || clipboard
+            .rtf
+            .as_ref()
+            .is_some_and(|rtf| contains(&rtf.data))
+        || clipboard
+            .image
+            .as_ref()
+            .is_some_and(|image| contains(&image.extension))
+        || clipboard.files.iter().any(|file| {
+            contains(&file.name)
+                || file.extension.as_deref().is_some_and(contains)
+                || file.mime_type.as_deref().is_some_and(contains)
+        })
+}
+
+fn hash_term(key: &hmac::Key, term: &str) -> String {
+    let tag = hmac::sign(key, term.as_bytes());
+    STANDARD.encode(&tag.as_ref()[..16])
+}
+
+/// Writes the search tokens of a decrypted clipboard, replacing any previous ones
+pub async fn index_clipboard_db(clipboard: &FullClipboardDto) -> Result<(), CommandError> {
+    let key = get_search_key(clipboard.clipboard.vault)?;
+    write_clipboard_index(&db().await?, clipboard, &key).await?;
+
+    Ok(())
+}
+
+/// Replaces the search tokens of a decrypted clipboard with its terms hashed under `key`
+pub async fn write_clipboard_index(
+    db: &DatabaseConnection,
+    clipboard: &FullClipboardDto,
+    key: &hmac::Key,
+) -> Result<(), DbErr> {
+    let mut terms = HashSet::new();
+    if let Some(text) = &clipboard.text {
+        terms.extend(index_terms(&text.data));
+    }
+    if let Some(html) = &clipboard.html {
+        terms.extend(index_terms(&html.data));
+    }
+    if let Some(rtf) = &clipboard.rtf {
+        terms.extend(index_terms(&rtf.data));
+    }
+    for file in &clipboard.files {
+        terms.extend(index_terms(&file.name));
+        if let Some(extension) = &file.extension {
+            terms.extend(index_terms(extension));
+        }
+        if let Some(mime_type) = &file.mime_type {
+            terms.extend(index_terms(mime_type));
+        }
+    }
+
+    remove_clipboard_index(db, clipboard.clipboard.id).await?;
+
+    let models: Vec<clipboard_search::ActiveModel> = terms
+        .iter()
+        .map(|term| clipboard_search::ActiveModel {
+            id: Set(Uuid::now_v7()),
+            clipboard_id: Set(clipboard.clipboard.id),
+            token: Set(hash_term(key, term)),
+        })
+        .collect();
+
+    // stay below the sqlite bound parameter limit
+    for chunk in models.chunks(1000) {
+        clipboard_search::Entity::insert_many(chunk.to_vec())
+            .exec_without_returning(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Drops the search tokens of a clipboard, e.g. once it is stored in plaintext again
+pub async fn remove_clipboard_index(db: &DatabaseConnection, id: Uuid) -> Result<(), DbErr> {
+    clipboard_search::Entity::delete_many()
+        .filter(clipboard_search::Column::ClipboardId.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Indexes a clipboard as stored in the database, decrypting it first if needed
+pub async fn index_encrypted_clipboard_db(
+    clipboard: &FullClipboardDto,
+) -> Result<(), CommandError> {
+    if !clipboard.clipboard.encrypted || !is_clipboard_key_set(clipboard.clipboard.vault) {
+        return Ok(());
+    }
+
+    let decrypted = Zeroizing::new(decrypt_clipboard(clipboard.clone())?);
+    index_clipboard_db(&decrypted).await
+}
+
+/// Indexes encrypted clipboards that were stored while no key was available,
+/// e.g. entries pulled by sync before the password was entered
+pub async fn index_missing_clipboards_db() -> Result<(), CommandError> {
+    // only entries whose key is available can be decrypted for indexing
+    let unlocked: Vec<bool> = [(false, is_encryption_key_set()), (true, is_vault_key_set())]
+        .into_iter()
+        .filter_map(|(vault, unlocked)| unlocked.then_some(vault))
+        .collect();
+    if unlocked.is_empty() {
+        return Ok(());
+    }
+
+    let db = db().await?;
+
+    let indexed = clipboard_search::Entity::find()
+        .select_only()
+        .column(clipboard_search::Column::ClipboardId)
+        .distinct()
+        .into_query();
+
+    let clipboards = load_clipboards_with_relations(
+        clipboard::Entity::find()
+            .filter(clipboard::Column::Encrypted.eq(true))
+            .filter(clipboard::Column::Id.not_in_subquery(indexed))
+            .filter(clipboard::Column::Vault.is_in(unlocked))
+            .all(&db)
+            .await?,
+    )
+    .await?;
+
+    printlog!("(search) indexing {} clipboards", clipboards.len());
+
+    for clipboard in clipboards {
+        if let Err(e) = index_encrypted_clipboard_db(&clipboard).await {
+            printlog!(
+                "Failed to index clipboard {}: {:?}",
+                clipboard.clipboard.id,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Subquery selecting the ids of encrypted clipboards whose index matches every
+/// term of `search`, or `None` if neither the history nor the vault is unlocked
+pub fn search_index_subquery(search: &str) -> Option<SelectStatement> {
+    // entries are indexed under the key they are encrypted with, so search with both
+    let keys: Vec<hmac::Key> = [
+        get_global_settings()
+            .encryption
+            .then(|| get_search_key(false).ok())
+            .flatten(),
+        get_search_key(true).ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if keys.is_empty() {
+        return None;
+    }
+
+    Some(search_index_query(search, &keys))
+}
+
+/// Subquery selecting the ids of clipboards indexed under one of `keys` with every term of
+/// `search`. Candidates only, they still have to be checked with `matches_search`
+pub fn search_index_query(search: &str, keys: &[hmac::Key]) -> SelectStatement {
+    let terms = query_terms(search);
+    let count = terms.len() as i32;
+
+    let tokens: Vec<String> = keys
+        .iter()
+        .flat_map(|key| terms.iter().map(move |term| hash_term(key, term)))
+        .collect();
+
+    clipboard_search::Entity::find()
+        .select_only()
+        .column(clipboard_search::Column::ClipboardId)
+        .filter(clipboard_search::Column::Token.is_in(tokens))
+        .group_by(clipboard_search::Column::ClipboardId)
+        .having(Expr::expr(Expr::col(clipboard_search::Column::Token).count()).eq(count))
+        .into_query()
+}
@@ -1,13 +1,16 @@
-use super::cipher::{init_password_lock_event, is_encryption_key_set};
+use super::cipher::{init_password_lock_event, is_encryption_key_set, is_same_encryption_key};
 use super::clipboard::get_last_clipboard_db;
 use super::decrypt::decrypt_all_clipboards;
+use super::hotkey::{get_sync_hotkeys_db, reload_hotkeys, update_sync_hotkeys_db};
 use super::sync::upsert_settings_sync;
 use crate::prelude::*;
 use crate::service::window::get_monitor_scale_factor;
 use crate::tao::connection::db;
-use crate::tao::global::get_app;
+use crate::tao::global::{get_app, get_device_id};
+use common::constants::SYNC_HOTKEYS_KEY;
 use common::io::language::get_system_language;
 use common::types::enums::{ListenEvent, PasswordAction};
+use common::types::sync::{SyncError, SyncHotkey, SyncRevision};
 use common::types::types::{CommandError, TextMatcher};
 use entity::settings;
 use sea_orm::{ActiveModelTrait, EntityTrait};
@@ -18,6 +21,10 @@ use tauri::Manager;
 use tauri::{Emitter, EventTarget};
 use tauri_plugin_autostart::AutoLaunchManager;
 
+/// Settings that depend on the network the device is in and may hold proxy credentials,
+/// they are never uploaded
+const NETWORK_SETTINGS: [&str; 3] = ["sync_proxy_url", "sync_no_proxy", "sync_ca_bundle"];
+
 pub fn autostart() {
     tauri::async_runtime::spawn(async {
         let settings = get_global_settings();
@@ -50,6 +57,9 @@ pub async fn update_settings_db(
 ) -> Result<settings::Model, CommandError> {
     let db: DatabaseConnection = db().await?;
 
+    let mut settings = settings;
+    bump_sync_revisions(&get_global_settings(), &mut settings);
+
     let active_model: settings::ActiveModel = settings.into();
 
     let settings = settings::Entity::update(active_model.reset_all())
@@ -110,9 +120,11 @@ pub fn setup_settings() {
 pub async fn update_settings_text_matchers(
     text_matchers: Vec<TextMatcher>,
 ) -> Result<Vec<TextMatcher>, CommandError> {
-    let mut settings = get_global_settings();
+    let previous = get_global_settings();
+    let mut settings = previous.clone();
 
     settings.text_matchers = json!(text_matchers);
+    bump_sync_revisions(&previous, &mut settings);
 
     let active_model: settings::ActiveModel = settings.into();
 
@@ -129,33 +141,266 @@ pub async fn update_settings_text_matchers(
     Ok(text_matchers)
 }
 
+/// Revisions of the synced settings fields, empty for settings that never changed
+pub fn get_sync_revisions(settings: &settings::Model) -> HashMap<String, SyncRevision> {
+    settings
+        .sync_revisions
+        .clone()
+        .and_then(|revisions| serde_json::from_value(revisions).ok())
+        .unwrap_or_default()
+}
+
+/// Starts a new revision for every field that differs from `previous`.
+/// Changes made while sync is off are not versioned, the remote wins once it is turned on
+pub fn bump_sync_revisions(previous: &settings::Model, settings: &mut settings::Model) {
+    // revisions sent along by the frontend may be outdated
+    settings.sync_revisions = previous.sync_revisions.clone();
+
+    if !settings.sync {
+        return;
+    }
+
+    let (Ok(serde_json::Value::Object(previous_map)), Ok(serde_json::Value::Object(map))) = (
+        serde_json::to_value(previous),
+        serde_json::to_value(&*settings),
+    ) else {
+        return;
+    };
+
+    let mut revisions = get_sync_revisions(previous);
+    for (key, value) in map {
+        if key == "sync_revisions"
+            || NETWORK_SETTINGS.contains(&key.as_str())
+            || previous_map.get(&key) == Some(&value)
+        {
+            continue;
+        }
+
+        let revision = revisions
+            .get(&key)
+            .cloned()
+            .unwrap_or_default()
+            .next(get_device_id());
+        revisions.insert(key, revision);
+    }
+
+    settings.sync_revisions = Some(json!(revisions));
+}
+
+/// Settings fields, or `SYNC_HOTKEYS_KEY`, this device keeps to itself
+pub fn get_sync_excluded_settings(settings: &settings::Model) -> Vec<String> {
+    serde_json::from_value(settings.sync_excluded_settings.clone()).unwrap_or_default()
+}
+
+/// Starts a new revision of the hotkey bindings after one of them was changed here
+pub async fn bump_hotkeys_sync_revision() -> Result<(), CommandError> {
+    let mut settings = get_global_settings();
+    if !settings.sync {
+        return Ok(());
+    }
+
+    let mut revisions = get_sync_revisions(&settings);
+    let revision = revisions
+        .get(SYNC_HOTKEYS_KEY)
+        .cloned()
+        .unwrap_or_default()
+        .next(get_device_id());
+    revisions.insert(SYNC_HOTKEYS_KEY.to_string(), revision);
+    settings.sync_revisions = Some(json!(revisions));
+
+    let active_model: settings::ActiveModel = settings.into();
+
+    let settings = settings::Entity::update(active_model.reset_all())
+        .exec(&db().await?)
+        .await?;
+
+    set_global_settings(settings.clone());
+
+    upsert_settings_sync(&settings, false).await
+}
+
+/// Settings as uploaded, with the hotkey bindings under `SYNC_HOTKEYS_KEY`.
+/// Excluded keys keep the value and revision they have in `remote_settings`,
+/// so this device does not overwrite what the other devices sync
+pub async fn get_settings_for_sync(
+    remote_settings: HashMap<String, serde_json::Value>,
+) -> Result<HashMap<String, serde_json::Value>, SyncError> {
+    settings_for_sync(
+        &get_global_settings(),
+        &get_sync_hotkeys_db().await?,
+        remote_settings,
+    )
+}
+
+/// `get_settings_for_sync` for the given settings and hotkey bindings
+pub fn settings_for_sync(
+    settings: &settings::Model,
+    hotkeys: &[SyncHotkey],
+    mut remote_settings: HashMap<String, serde_json::Value>,
+) -> Result<HashMap<String, serde_json::Value>, SyncError> {
+    let mut settings_map: HashMap<String, serde_json::Value> =
+        serde_json::from_value(serde_json::to_value(settings)?)?;
+    settings_map.insert(SYNC_HOTKEYS_KEY.to_string(), serde_json::to_value(hotkeys)?);
+    for key in NETWORK_SETTINGS {
+        settings_map.remove(key);
+    }
+
+    let mut revisions = get_sync_revisions(settings);
+    let remote_revisions: HashMap<String, SyncRevision> = remote_settings
+        .remove("sync_revisions")
+        .and_then(|revisions| serde_json::from_value(revisions).ok())
+        .unwrap_or_default();
+
+    for key in get_sync_excluded_settings(settings) {
+        match remote_settings.remove(&key) {
+            Some(value) => settings_map.insert(key.clone(), value),
+            None => settings_map.remove(&key),
+        };
+        match remote_revisions.get(&key) {
+            Some(revision) => revisions.insert(key, revision.clone()),
+            None => revisions.remove(&key),
+        };
+    }
+    settings_map.insert("sync_revisions".to_string(), json!(revisions));
+
+    Ok(settings_map)
+}
+
+/// Removes the remote fields this device never takes over: fields set up per device,
+/// the keys it keeps to itself and wrapped keys of another data key or vault key
+pub fn remove_local_settings(
+    current_settings: &settings::Model,
+    remote_settings: &mut HashMap<String, serde_json::Value>,
+) {
+    // Skip display_scale as it is calculated on first time setup
+    remote_settings.remove("display_scale");
+    // Skip startup as it users choice
+    remote_settings.remove("startup");
+    // Skip sync_provider and sync_mirrors as every device connects on its own
+    remote_settings.remove("sync_provider");
+    remote_settings.remove("sync_mirrors");
+    for key in NETWORK_SETTINGS {
+        remote_settings.remove(key);
+    }
+    // Skip the keys this device keeps to itself, including which ones those are
+    remote_settings.remove("sync_excluded_settings");
+    for key in get_sync_excluded_settings(current_settings) {
+        remote_settings.remove(&key);
+    }
+    // Keep the local wrapped data key if the remote has none or wraps another data key,
+    // the history of this device can only be decrypted with its own key
+    if let Some(remote_keys) = remote_settings.get("encryption_keys") {
+        let other_key = current_settings
+            .encryption_keys
+            .as_ref()
+            .is_some_and(|local_keys| !is_same_encryption_key(local_keys, remote_keys));
+        if other_key {
+            printlog!("(conflict) remote settings wrap another encryption key, keeping local");
+        }
+        if remote_keys.is_null() || other_key {
+            remote_settings.remove("encryption_keys");
+        }
+    }
+    // Keep the local vault salt and check, the vault entries here are sealed under their key
+    if let Some(local_check) = current_settings.vault_check.as_deref() {
+        let remote_check = remote_settings
+            .get("vault_check")
+            .and_then(|check| check.as_str());
+        if remote_check.is_some_and(|check| check != local_check) {
+            printlog!("(conflict) remote settings check another vault key, keeping local");
+        }
+        remote_settings.remove("vault_check");
+        remote_settings.remove("vault_salt");
+    }
+}
+
+/// Applies the remote fields with a newer revision, fields are merged one by one so changes
+/// of different devices to different fields are all kept.
+/// Returns whether local fields are newer than the remote ones and need to be uploaded
 pub async fn update_settings_from_sync(
     remote_settings: HashMap<String, serde_json::Value>,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<bool, SyncError> {
     // Return early if no settings to process
     if remote_settings.is_empty() {
-        return Ok(());
+        return Ok(false);
     }
 
     let db: DatabaseConnection = db().await?;
     let current_settings = get_global_settings();
 
-    // Handle encryption state changes
-    let remote_encryption = remote_settings
-        .get("encryption")
-        .and_then(|v| v.as_bool())
-        .unwrap_or(false);
-
     let mut remote_settings = remote_settings;
 
-    // Skip encryption as it is handled separately
-    remote_settings.remove("encryption");
-    // Skip display_scale as it is calculated on first time setup
-    remote_settings.remove("display_scale");
-    // Skip startup as it users choice
-    remote_settings.remove("startup");
+    let remote_revisions: HashMap<String, SyncRevision> = remote_settings
+        .remove("sync_revisions")
+        .and_then(|revisions| serde_json::from_value(revisions).ok())
+        .unwrap_or_default();
+
+    remove_local_settings(&current_settings, &mut remote_settings);
+
+    // Convert current settings to Value to get the schema structure
+    let current_value = serde_json::to_value(&current_settings)?;
+    let serde_json::Value::Object(mut map) = current_value else {
+        return Ok(false);
+    };
+    // compared like a field, but applied to the hotkey table
+    map.insert(
+        SYNC_HOTKEYS_KEY.to_string(),
+        serde_json::to_value(get_sync_hotkeys_db().await?)?,
+    );
+
+    // Last writer wins per field, the device id decides between edits of the same revision
+    let mut local_revisions = get_sync_revisions(&current_settings);
+    let mut local_newer = false;
+    remote_settings.retain(|key, value| {
+        let local = local_revisions.get(key).cloned().unwrap_or_default();
+        let remote = remote_revisions.get(key).cloned().unwrap_or_default();
+        let differs = map.get(key) != Some(value);
+
+        if differs && remote.is_concurrent(&local) {
+            printlog!(
+                "(conflict) setting {} changed on {:?} and {:?} at revision {}, keeping {:?}",
+                key,
+                local.modified_by,
+                remote.modified_by,
+                local.revision,
+                local.clone().max(remote.clone()).modified_by
+            );
+        } else if differs && remote < local {
+            printlog!(
+                "(conflict) setting {} is at revision {} locally but {} remotely, keeping local",
+                key,
+                local.revision,
+                remote.revision
+            );
+        }
+
+        local_newer |= remote < local;
+        remote >= local
+    });
 
+    let local_hotkeys = map.remove(SYNC_HOTKEYS_KEY);
+    let mut hotkeys_changed = false;
+    if let Some(hotkeys) = remote_settings.remove(SYNC_HOTKEYS_KEY) {
+        if Some(&hotkeys) != local_hotkeys.as_ref() {
+            if let Ok(hotkeys) = serde_json::from_value::<Vec<SyncHotkey>>(hotkeys) {
+                hotkeys_changed = update_sync_hotkeys_db(hotkeys).await?;
+            }
+        }
+        if let Some(remote) = remote_revisions.get(SYNC_HOTKEYS_KEY) {
+            local_revisions.insert(SYNC_HOTKEYS_KEY.to_string(), remote.clone());
+        }
+    }
+    if hotkeys_changed {
+        reload_hotkeys().await;
+        printlog!("(remote) applied hotkeys");
+    }
+
+    // Handle encryption state changes
     let local_encryption = current_settings.encryption;
+    let remote_encryption = remote_settings
+        .remove("encryption")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(local_encryption);
 
     match (local_encryption, remote_encryption, is_encryption_key_set()) {
         // Local unencrypted -> Remote encrypted
@@ -175,38 +420,40 @@ pub async fn update_settings_from_sync(
 
             remote_settings.insert("encryption".to_string(), serde_json::Value::Bool(false));
 
-            decrypt_all_clipboards()
-                .await
-                .expect("Failed to decrypt clipboards");
+            // the job stops the sync loop this is called from, so it runs on its own task
+            tauri::async_runtime::spawn(async {
+                if let Err(e) = decrypt_all_clipboards().await {
+                    printlog!("Error decrypting clipboards: {:?}", e);
+                }
+            });
         }
         _ => {}
     }
 
-    // Convert current settings to Value to get the schema structure
-    let current_value = serde_json::to_value(&current_settings)?;
-
-    // Merge the remote settings with current settings
-    // This preserves the structure and types of the current settings
-    // while only updating fields that exist in both
-    let merged_value = match current_value {
-        serde_json::Value::Object(mut map) => {
-            for (key, value) in remote_settings {
-                if map.contains_key(&key) {
-                    // Only update if types match or can be converted
-                    if let Ok(converted) =
-                        serde_json::from_value::<serde_json::Value>(value.clone())
-                    {
-                        map.insert(key, converted);
-                    }
-                }
-            }
-            serde_json::Value::Object(map)
+    // Only fields that are applied take over the remote revision, a deferred encryption change
+    // keeps the local one so it is not uploaded as if it was the remote value
+    let mut changed = false;
+    for (key, value) in remote_settings {
+        if !map.contains_key(&key) {
+            continue;
         }
-        _ => return Ok(()),
-    };
+        if let Some(remote) = remote_revisions.get(&key) {
+            local_revisions.insert(key.clone(), remote.clone());
+        }
+        changed |= map.get(&key) != Some(&value);
+        map.insert(key, value);
+    }
+
+    if !changed && local_revisions == get_sync_revisions(&current_settings) {
+        return Ok(local_newer);
+    }
+
+    map.insert("sync_revisions".to_string(), json!(local_revisions));
 
     // Deserialize back into settings model, ignoring errors for individual fields
-    if let Ok(new_settings) = serde_json::from_value::<settings::Model>(merged_value) {
+    if let Ok(new_settings) =
+        serde_json::from_value::<settings::Model>(serde_json::Value::Object(map))
+    {
         let active_model: settings::ActiveModel = new_settings.into();
 
         let settings = settings::Entity::update(active_model.reset_all())
@@ -222,17 +469,17 @@ pub async fn update_settings_from_sync(
         printlog!("(remote) applied settings");
     }
 
-    Ok(())
+    Ok(local_newer)
 }
 
 pub fn init_settings_window() {
-    get_app()
-        .emit_to(
-            EventTarget::any(),
-            ListenEvent::InitSettings.to_string().as_str(),
-            (),
-        )
-        .expect("Failed to emit download progress event");
+    if let Err(e) = get_app().emit_to(
+        EventTarget::any(),
+        ListenEvent::InitSettings.to_string().as_str(),
+        (),
+    ) {
+        printlog!("failed to emit settings event: {:?}", e);
+    }
 }
 
 pub fn get_global_settings() -> settings::Model {
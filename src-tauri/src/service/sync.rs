@@ -1,15 +1,52 @@
-use super::settings::{
-    get_global_settings, get_settings_db, update_settings_from_sync, update_settings_synchronize_db,
+use super::{
+    cipher::{
+        derive_sync_key, generate_salt, get_sync_key, get_sync_salt, is_sync_key_set,
+        remove_sync_key, save_sync_key, set_sync_key,
+    },
+    settings::{
+        get_global_settings, get_settings_db, get_settings_for_sync, update_settings_db,
+        update_settings_from_sync, update_settings_synchronize_db,
+    },
+    sync_outbox::{count_sync_outbox_db, queue_sync_changes},
 };
 use crate::{
     prelude::*,
-    tao::global::get_app,
-    utils::{providers::google_drive::GoogleDriveProviderImpl, sync_manager::SyncManager},
+    tao::{
+        config::set_sync_folder,
+        global::{get_app, get_sync_pull, get_sync_status_store},
+    },
+    utils::{
+        providers::{
+            google_drive::GoogleDriveProviderImpl,
+            local_folder::LocalFolderProviderImpl,
+            manifest::with_manifest,
+            memory::MemoryProviderImpl,
+            network::{load_ca_bundle, NetworkSettings, ProxyConnector},
+            s3::S3ProviderImpl,
+            server::ServerProviderImpl,
+            token_storage::revoke_tokens,
+            webdav::WebDavProviderImpl,
+        },
+        sync_manager::SyncManager,
+    },
+};
+use chrono::Utc;
+use common::types::{
+    cipher::{EncryptionError, SecretKey},
+    enums::{ListenEvent, SyncOperation, SyncProviderType},
+    sync::{
+        S3Credentials, ServerCredentials, SyncError, SyncProvider, SyncProviderStatus, SyncStatus,
+        WebDavCredentials,
+    },
+    types::CommandError,
 };
-use common::types::{enums::SyncProviderType, sync::SyncProvider, types::CommandError};
 use entity::settings;
-use std::{collections::HashMap, sync::Arc};
-use tauri::{Manager, State};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use tauri::{Emitter, EventTarget, Manager, State};
 use tokio::sync::Mutex;
 
 pub async fn upsert_settings_sync(
@@ -17,43 +54,166 @@ pub async fn upsert_settings_sync(
     block_main_thread: bool,
 ) -> Result<(), CommandError> {
     if settings.sync {
-        let settings_map: HashMap<String, serde_json::Value> =
-            serde_json::from_value(serde_json::to_value(settings.clone())?)?;
         if block_main_thread {
-            get_sync_provider()
-                .await
-                .upsert_settings(&settings_map)
-                .await
-                .expect("Failed to upsert settings");
+            push_settings(get_sync_provider().await?.as_ref()).await?;
         } else {
             tauri::async_runtime::spawn(async move {
-                get_sync_provider()
-                    .await
-                    .upsert_settings(&settings_map)
-                    .await
-                    .expect("Failed to upsert settings");
+                let result = match get_sync_provider().await {
+                    Ok(provider) => push_settings(provider.as_ref())
+                        .await
+                        .map_err(CommandError::from),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    printlog!("failed to upload settings: {:?}", e);
+                }
             });
         }
     }
     Ok(())
 }
 
-pub async fn get_sync_provider() -> State<'static, Arc<dyn SyncProvider>> {
-    if let Some(sync_state) = get_app().try_state() {
-        return sync_state;
+/// Uploads the settings after applying the fields other devices changed in the meantime,
+/// so the upload does not overwrite them
+async fn push_settings(provider: &dyn SyncProvider) -> Result<(), SyncError> {
+    let remote_settings = provider.get_settings().await?;
+    update_settings_from_sync(remote_settings.clone()).await?;
+    upload_settings(provider, remote_settings).await
+}
+
+/// Uploads the settings, `remote_settings` provides the keys this device does not sync
+async fn upload_settings(
+    provider: &dyn SyncProvider,
+    remote_settings: HashMap<String, serde_json::Value>,
+) -> Result<(), SyncError> {
+    provider
+        .upsert_settings(&get_settings_for_sync(remote_settings).await?)
+        .await
+}
+
+/// Removes remote clipboards beyond a lowered sync limit and uploads the new limit
+pub async fn cleanup_sync_limit(settings: &settings::Model) -> Result<(), CommandError> {
+    let provider = get_sync_provider().await?;
+    let remote_clipboards = provider.fetch_all_clipboards().await?;
+    provider.cleanup_old_clipboards(&remote_clipboards).await?;
+    provider.flush_manifest().await?;
+
+    upsert_settings_sync(settings, false).await
+}
+
+pub async fn get_sync_provider() -> Result<Arc<dyn SyncProvider>, CommandError> {
+    if let Some(provider) = get_sync_provider_state()
+        .read()
+        .map_err(|_| CommandError::new("Failed to read sync provider"))?
+        .clone()
+    {
+        return Ok(provider);
     }
 
-    let provider = match get_global_settings().sync_provider.as_str() {
-        s if s == SyncProviderType::GoogleDrive.to_string() => Arc::new(
-            GoogleDriveProviderImpl::new()
-                .await
-                .expect("Failed to create Google Drive provider"),
-        ),
-        _ => panic!("Provider type not implemented"),
+    let provider = create_sync_provider(&get_global_settings().sync_provider).await?;
+
+    set_sync_provider(Some(provider.clone()));
+    Ok(provider)
+}
+
+/// Creates the provider of the `SyncProviderType` named `provider_type`
+async fn create_sync_provider(provider_type: &str) -> Result<Arc<dyn SyncProvider>, CommandError> {
+    let provider: Arc<dyn SyncProvider> = match provider_type {
+        s if s == SyncProviderType::GoogleDrive.to_string() => {
+            with_manifest(GoogleDriveProviderImpl::new().await?)
+        }
+        s if s == SyncProviderType::WebDav.to_string() => {
+            with_manifest(WebDavProviderImpl::new().await?)
+        }
+        s if s == SyncProviderType::LocalFolder.to_string() => {
+            with_manifest(LocalFolderProviderImpl::new().await?)
+        }
+        s if s == SyncProviderType::S3.to_string() => with_manifest(S3ProviderImpl::new().await?),
+        s if s == SyncProviderType::Server.to_string() => {
+            with_manifest(ServerProviderImpl::new().await?)
+        }
+        s if s == SyncProviderType::DryRun.to_string() => {
+            with_manifest(MemoryProviderImpl::default())
+        }
+        _ => return Err(CommandError::new("MAIN.ERROR.SYNC_PROVIDER_UNKNOWN")),
     };
 
-    get_app().manage(provider.clone() as Arc<dyn SyncProvider>);
-    get_app().state()
+    Ok(provider)
+}
+
+/// Replaces the cached provider, `None` creates it again from the settings on next use
+pub fn set_sync_provider(provider: Option<Arc<dyn SyncProvider>>) {
+    if let Ok(mut state) = get_sync_provider_state().write() {
+        *state = provider;
+    }
+}
+
+fn get_sync_provider_state() -> State<'static, RwLock<Option<Arc<dyn SyncProvider>>>> {
+    match get_app().try_state() {
+        Some(provider) => provider,
+        None => {
+            get_app().manage(RwLock::<Option<Arc<dyn SyncProvider>>>::new(None));
+            get_app().state()
+        }
+    }
+}
+
+/// Providers every change is copied to besides the sync provider, in the configured order
+pub fn get_sync_mirror_types(settings: &settings::Model) -> Vec<String> {
+    serde_json::from_value::<Vec<String>>(settings.sync_mirrors.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|provider_type| *provider_type != settings.sync_provider)
+        .collect()
+}
+
+/// The mirrors by type, a mirror that can not be created comes with its error so the others
+/// are still written to
+pub async fn get_sync_mirrors() -> Vec<(String, Result<Arc<dyn SyncProvider>, SyncError>)> {
+    let mut mirrors = Vec::new();
+
+    for provider_type in get_sync_mirror_types(&get_global_settings()) {
+        let cached = get_sync_mirror_state()
+            .read()
+            .ok()
+            .and_then(|state| state.get(&provider_type).cloned());
+
+        let mirror = match cached {
+            Some(mirror) => Ok(mirror),
+            None => match create_sync_provider(&provider_type).await {
+                Ok(mirror) => {
+                    if let Ok(mut state) = get_sync_mirror_state().write() {
+                        state.insert(provider_type.clone(), mirror.clone());
+                    }
+                    Ok(mirror)
+                }
+                Err(e) => Err(SyncError::from(e)),
+            },
+        };
+
+        mirrors.push((provider_type, mirror));
+    }
+
+    mirrors
+}
+
+/// Drops the cached mirrors, they are created again from the settings on next use
+pub fn reset_sync_mirrors() {
+    if let Ok(mut state) = get_sync_mirror_state().write() {
+        state.clear();
+    }
+}
+
+fn get_sync_mirror_state() -> State<'static, RwLock<HashMap<String, Arc<dyn SyncProvider>>>> {
+    match get_app().try_state() {
+        Some(mirrors) => mirrors,
+        None => {
+            get_app().manage(RwLock::<HashMap<String, Arc<dyn SyncProvider>>>::new(
+                HashMap::new(),
+            ));
+            get_app().state()
+        }
+    }
 }
 
 pub fn get_sync_manager() -> State<'static, Mutex<SyncManager>> {
@@ -70,7 +230,13 @@ pub fn get_sync_manager() -> State<'static, Mutex<SyncManager>> {
 pub fn setup_sync_interval() {
     tauri::async_runtime::spawn(async {
         // needs to be real db call because of setup race condition in `fn setup_settings()`
-        let settings = get_settings_db().await.expect("Failed to get settings");
+        let settings = match get_settings_db().await {
+            Ok(settings) => settings,
+            Err(e) => {
+                printlog!("failed to read settings, sync not started: {:?}", e);
+                return;
+            }
+        };
 
         if settings.sync {
             get_sync_manager().lock().await.start().await;
@@ -78,24 +244,109 @@ pub fn setup_sync_interval() {
     });
 }
 
+/// Pulls remote changes right away instead of waiting for the next interval
+pub fn trigger_sync() -> Result<(), CommandError> {
+    if !get_global_settings().sync {
+        return Err(CommandError::new("MAIN.ERROR.SYNC_DISABLED"));
+    }
+
+    get_sync_pull().notify_one();
+    Ok(())
+}
+
+/// Sync status with the outbox size read fresh from the database
+pub async fn load_sync_status() -> Result<SyncStatus, CommandError> {
+    let pending_uploads = count_sync_outbox_db().await?;
+
+    let mut status = get_sync_status_store();
+    status.pending_uploads = pending_uploads;
+    status.sealed = is_sync_key_set();
+    align_provider_statuses(&mut status, &get_global_settings());
+
+    Ok(status.clone())
+}
+
+/// Lists the configured providers in order, keeping what is known about each of them
+fn align_provider_statuses(status: &mut SyncStatus, settings: &settings::Model) {
+    let previous = std::mem::take(&mut status.providers);
+
+    status.providers = std::iter::once(settings.sync_provider.clone())
+        .chain(get_sync_mirror_types(settings))
+        .map(|provider| {
+            let mut provider_status = previous
+                .iter()
+                .find(|previous| previous.provider == provider)
+                .cloned()
+                .unwrap_or_else(|| SyncProviderStatus {
+                    provider: provider.clone(),
+                    ..Default::default()
+                });
+            provider_status.mirror = provider != settings.sync_provider;
+            provider_status
+        })
+        .collect();
+}
+
+/// Records how the last sync with `provider` went, `error` is `None` on success
+pub async fn update_provider_status(provider: &str, error: Option<String>) {
+    let settings = get_global_settings();
+
+    update_sync_status(|status| {
+        align_provider_statuses(status, &settings);
+
+        if let Some(provider_status) = status
+            .providers
+            .iter_mut()
+            .find(|provider_status| provider_status.provider == provider)
+        {
+            match error {
+                Some(error) => provider_status.last_error = Some(error),
+                None => {
+                    provider_status.last_success = Some(Utc::now().naive_utc());
+                    provider_status.last_error = None;
+                }
+            }
+        }
+    })
+    .await;
+}
+
+/// Applies `update` to the sync status and emits it to every window
+pub async fn update_sync_status(update: impl FnOnce(&mut SyncStatus)) {
+    let pending_uploads = count_sync_outbox_db().await;
+
+    let status = {
+        let mut status = get_sync_status_store();
+        update(&mut status);
+        status.sealed = is_sync_key_set();
+        if let Ok(pending_uploads) = pending_uploads {
+            status.pending_uploads = pending_uploads;
+        }
+        status.clone()
+    };
+
+    if let Err(e) = get_app().emit_to(
+        EventTarget::any(),
+        ListenEvent::SyncStatus.to_string().as_str(),
+        status,
+    ) {
+        printlog!("failed to emit sync status: {:?}", e);
+    }
+}
+
 pub async fn sync_toggle() -> Result<bool, CommandError> {
     let new_sync_state = !get_global_settings().sync;
 
     if new_sync_state {
         // Trying to enable sync
-        let provider = get_sync_provider().await;
+        let provider = get_sync_provider().await?;
         if !provider.is_authenticated().await {
             update_settings_synchronize_db(false).await?;
             return Err(CommandError::Error("Authentication failed".to_string()));
         }
 
-        let remote_settings = provider
-            .get_settings()
-            .await
-            .expect("Failed to get settings");
-        update_settings_from_sync(remote_settings)
-            .await
-            .expect("Failed to update settings");
+        let remote_settings = provider.get_settings().await?;
+        update_settings_from_sync(remote_settings).await?;
 
         let settings = update_settings_synchronize_db(true).await?;
 
@@ -112,3 +363,345 @@ pub async fn sync_toggle() -> Result<bool, CommandError> {
 
     Ok(new_sync_state)
 }
+
+/// Makes `provider` the sync provider, a running sync is restarted with it
+async fn switch_sync_provider(
+    provider: Arc<dyn SyncProvider>,
+    provider_type: SyncProviderType,
+) -> Result<(), CommandError> {
+    get_sync_manager().lock().await.stop().await;
+    set_sync_provider(Some(provider));
+
+    let mut settings = get_global_settings();
+    settings.sync_provider = provider_type.to_string();
+    // the sync provider can not mirror itself
+    settings.sync_mirrors = json!(get_sync_mirror_types(&settings));
+    let settings = update_settings_db(settings).await?;
+
+    if settings.sync {
+        get_sync_manager().lock().await.start().await;
+    }
+
+    Ok(())
+}
+
+/// Stops mirroring to `provider_type` and falls back to Google Drive with sync turned off
+/// if it is the sync provider
+async fn reset_sync_provider(provider_type: SyncProviderType) -> Result<(), CommandError> {
+    let provider_type = provider_type.to_string();
+    let mut settings = get_global_settings();
+    let mut mirrors = get_sync_mirror_types(&settings);
+
+    let is_mirror = mirrors.contains(&provider_type);
+    if settings.sync_provider != provider_type && !is_mirror {
+        return Ok(());
+    }
+
+    get_sync_manager().lock().await.stop().await;
+
+    if is_mirror {
+        mirrors.retain(|mirror| *mirror != provider_type);
+        settings.sync_mirrors = json!(mirrors);
+        reset_sync_mirrors();
+    }
+
+    if settings.sync_provider == provider_type {
+        set_sync_provider(None);
+        settings.sync = false;
+        settings.sync_provider = SyncProviderType::GoogleDrive.to_string();
+    }
+
+    let settings = update_settings_db(settings).await?;
+
+    if settings.sync {
+        get_sync_manager().lock().await.start().await;
+    }
+
+    Ok(())
+}
+
+/// Copies every change to the connected provider `provider_type` as well, for example a local
+/// backup folder next to Google Drive. The sync provider stays the only one pulled from
+pub async fn add_sync_mirror(provider_type: String) -> Result<(), CommandError> {
+    if !SyncProviderType::iter().any(|known| known.to_string() == provider_type) {
+        return Err(CommandError::new("MAIN.ERROR.SYNC_PROVIDER_UNKNOWN"));
+    }
+
+    let mut settings = get_global_settings();
+    if settings.sync_provider == provider_type {
+        return Err(CommandError::new("MAIN.ERROR.SYNC_MIRROR_IS_PROVIDER"));
+    }
+
+    let mut mirrors = get_sync_mirror_types(&settings);
+    if mirrors.contains(&provider_type) {
+        return Ok(());
+    }
+
+    let mirror = create_sync_provider(&provider_type).await?;
+    if !mirror.is_authenticated().await {
+        return Err(CommandError::new(
+            "MAIN.ERROR.SYNC_MIRROR_AUTHENTICATION_FAILED",
+        ));
+    }
+
+    get_sync_manager().lock().await.stop().await;
+    if let Ok(mut state) = get_sync_mirror_state().write() {
+        state.insert(provider_type.clone(), mirror);
+    }
+
+    mirrors.push(provider_type);
+    settings.sync_mirrors = json!(mirrors);
+    let settings = update_settings_db(settings).await?;
+
+    if settings.sync {
+        get_sync_manager().lock().await.start().await;
+    }
+
+    Ok(())
+}
+
+/// Stops copying changes to `provider_type`, what it holds already is left in place
+pub async fn remove_sync_mirror(provider_type: String) -> Result<(), CommandError> {
+    let mut settings = get_global_settings();
+    let mut mirrors = get_sync_mirror_types(&settings);
+    if !mirrors.contains(&provider_type) {
+        return Ok(());
+    }
+
+    get_sync_manager().lock().await.stop().await;
+    if let Ok(mut state) = get_sync_mirror_state().write() {
+        state.remove(&provider_type);
+    }
+
+    mirrors.retain(|mirror| *mirror != provider_type);
+    settings.sync_mirrors = json!(mirrors);
+    let settings = update_settings_db(settings).await?;
+
+    if settings.sync {
+        get_sync_manager().lock().await.start().await;
+    }
+
+    Ok(())
+}
+
+/// Revokes the Google login and removes the stored token, Drive asks for a new login next time
+pub async fn disconnect_google_drive() -> Result<(), CommandError> {
+    revoke_tokens().await;
+    reset_sync_provider(SyncProviderType::GoogleDrive).await
+}
+
+/// Saves the proxy and CA bundle of the sync clients, the providers are created again so a
+/// running sync picks them up right away
+pub async fn change_sync_network(network: NetworkSettings) -> Result<(), CommandError> {
+    ProxyConnector::new(&network)
+        .map_err(|_| CommandError::new("MAIN.ERROR.SYNC_PROXY_INVALID"))?;
+    load_ca_bundle(&network).map_err(|_| CommandError::new("MAIN.ERROR.SYNC_CA_BUNDLE_INVALID"))?;
+
+    get_sync_manager().lock().await.stop().await;
+    set_sync_provider(None);
+    reset_sync_mirrors();
+
+    let mut settings = get_global_settings();
+    settings.sync_proxy_url = network.proxy_url;
+    settings.sync_no_proxy = network.no_proxy;
+    settings.sync_ca_bundle = network.ca_bundle;
+    let settings = update_settings_db(settings).await?;
+
+    if settings.sync {
+        get_sync_manager().lock().await.start().await;
+    }
+
+    Ok(())
+}
+
+/// Verifies the WebDAV login and makes WebDAV the sync provider
+pub async fn connect_webdav(credentials: WebDavCredentials) -> Result<(), CommandError> {
+    let provider = WebDavProviderImpl::from_credentials(credentials.clone())?;
+    if !provider.is_authenticated().await {
+        return Err(CommandError::new("MAIN.ERROR.WEBDAV_AUTHENTICATION_FAILED"));
+    }
+    WebDavProviderImpl::save_credentials(&credentials)?;
+
+    switch_sync_provider(with_manifest(provider), SyncProviderType::WebDav).await
+}
+
+/// Removes the stored WebDAV login
+pub async fn disconnect_webdav() -> Result<(), CommandError> {
+    WebDavProviderImpl::remove_credentials();
+    reset_sync_provider(SyncProviderType::WebDav).await
+}
+
+/// Syncs through a folder that is kept in sync by another tool, e.g. Syncthing
+pub async fn connect_local_folder(path: String) -> Result<(), CommandError> {
+    let provider = LocalFolderProviderImpl::from_path(path.clone());
+    if !provider.is_authenticated().await {
+        return Err(CommandError::new("MAIN.ERROR.SYNC_FOLDER_NOT_FOUND"));
+    }
+    set_sync_folder(Some(path));
+
+    switch_sync_provider(with_manifest(provider), SyncProviderType::LocalFolder).await
+}
+
+pub async fn disconnect_local_folder() -> Result<(), CommandError> {
+    set_sync_folder(None);
+    reset_sync_provider(SyncProviderType::LocalFolder).await
+}
+
+/// Verifies the bucket access and makes the S3 bucket the sync provider
+pub async fn connect_s3(credentials: S3Credentials) -> Result<(), CommandError> {
+    let provider = S3ProviderImpl::from_credentials(credentials.clone())?;
+    if !provider.is_authenticated().await {
+        return Err(CommandError::new("MAIN.ERROR.S3_AUTHENTICATION_FAILED"));
+    }
+    S3ProviderImpl::save_credentials(&credentials)?;
+
+    switch_sync_provider(with_manifest(provider), SyncProviderType::S3).await
+}
+
+/// Removes the stored bucket access keys
+pub async fn disconnect_s3() -> Result<(), CommandError> {
+    S3ProviderImpl::remove_credentials();
+    reset_sync_provider(SyncProviderType::S3).await
+}
+
+/// Verifies the token and makes the clippy sync server the sync provider
+pub async fn connect_server(credentials: ServerCredentials) -> Result<(), CommandError> {
+    let provider = ServerProviderImpl::from_credentials(credentials.clone())?;
+    if !provider.is_authenticated().await {
+        return Err(CommandError::new("MAIN.ERROR.SERVER_AUTHENTICATION_FAILED"));
+    }
+    ServerProviderImpl::save_credentials(&credentials)?;
+
+    switch_sync_provider(with_manifest(provider), SyncProviderType::Server).await
+}
+
+/// Removes the stored sync server token
+pub async fn disconnect_server() -> Result<(), CommandError> {
+    ServerProviderImpl::remove_credentials();
+    reset_sync_provider(SyncProviderType::Server).await
+}
+
+/// Syncs against an in-memory remote that only logs what would be uploaded or deleted,
+/// it starts out empty again with every restart
+pub async fn connect_dry_run() -> Result<(), CommandError> {
+    switch_sync_provider(
+        with_manifest(MemoryProviderImpl::default()),
+        SyncProviderType::DryRun,
+    )
+    .await
+}
+
+pub async fn disconnect_dry_run() -> Result<(), CommandError> {
+    reset_sync_provider(SyncProviderType::DryRun).await
+}
+
+/// Seals sync payloads and settings with `passphrase` from now on and uploads the remote again
+/// sealed. If another device set a passphrase already, it has to be the same one
+pub async fn set_sync_passphrase(
+    passphrase: String,
+    confirm_passphrase: String,
+) -> Result<(), CommandError> {
+    if is_sync_key_set() {
+        return Err(CommandError::new("MAIN.ERROR.SYNC_PASSPHRASE_ALREADY_SET"));
+    }
+
+    if passphrase != confirm_passphrase {
+        return Err(CommandError::new("MAIN.ERROR.PASSWORD_NOT_MATCH"));
+    }
+
+    let salt = match pull_sync_salt().await? {
+        Some(salt) => salt,
+        None => generate_salt().map_err(|e| CommandError::new(&e.to_string()))?,
+    };
+    let key_bytes =
+        derive_sync_key(&passphrase, &salt).map_err(|e| CommandError::new(&e.to_string()))?;
+    set_sync_key(Some((SecretKey::new(*key_bytes), salt.clone())))
+        .map_err(|e| CommandError::new(&e.to_string()))?;
+
+    // remote settings sealed with another passphrase can not be opened with this one
+    let remote_settings = match pull_sealed_settings().await {
+        Ok(remote_settings) => remote_settings,
+        Err(e) => {
+            set_sync_key(None).ok();
+            return Err(match e {
+                SyncError::Auth(message) if message == EncryptionError::InvalidKey.to_string() => {
+                    CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD")
+                }
+                e => e.into(),
+            });
+        }
+    };
+
+    save_sync_key(&key_bytes, &salt)?;
+    reseal_remote(remote_settings).await
+}
+
+/// Uploads payloads and settings without a passphrase again, `passphrase` has to match
+/// the current one
+pub async fn remove_sync_passphrase(passphrase: String) -> Result<(), CommandError> {
+    let (Some(current_key), Some(salt)) = (
+        get_sync_key().map_err(|e| CommandError::new(&e.to_string()))?,
+        get_sync_salt().map_err(|e| CommandError::new(&e.to_string()))?,
+    ) else {
+        return Err(CommandError::new("MAIN.ERROR.SYNC_PASSPHRASE_NOT_SET"));
+    };
+
+    let key_bytes =
+        derive_sync_key(&passphrase, &salt).map_err(|e| CommandError::new(&e.to_string()))?;
+    if key_bytes != current_key {
+        return Err(CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD"));
+    }
+
+    // read while the remote settings can still be opened
+    let remote_settings = pull_sealed_settings().await?;
+
+    set_sync_key(None).map_err(|e| CommandError::new(&e.to_string()))?;
+    remove_sync_key();
+    reseal_remote(remote_settings).await
+}
+
+/// Applies and returns the remote settings, which fails if they are sealed with another
+/// passphrase
+async fn pull_sealed_settings() -> Result<HashMap<String, serde_json::Value>, SyncError> {
+    if !get_global_settings().sync {
+        return Ok(HashMap::new());
+    }
+
+    let remote_settings = get_sync_provider().await?.get_settings().await?;
+    update_settings_from_sync(remote_settings.clone()).await?;
+    Ok(remote_settings)
+}
+
+/// Salt of the passphrase another device set already, with it the same passphrase
+/// derives the same key here
+async fn pull_sync_salt() -> Result<Option<String>, SyncError> {
+    if !get_global_settings().sync {
+        return Ok(None);
+    }
+
+    get_sync_provider().await?.get_settings_salt().await
+}
+
+/// Uploads the settings and every remote clipboard again with the current sync key,
+/// the clipboards are queued as their manifest hash changes with the key
+async fn reseal_remote(
+    remote_settings: HashMap<String, serde_json::Value>,
+) -> Result<(), CommandError> {
+    if !get_global_settings().sync {
+        return Ok(());
+    }
+
+    let provider = get_sync_provider().await?;
+    upload_settings(provider.as_ref(), remote_settings).await?;
+
+    let ids = provider
+        .fetch_all_clipboards()
+        .await?
+        .into_iter()
+        .filter(|clippy| clippy.deleted_at.is_none())
+        .map(|clippy| clippy.id)
+        .collect();
+    queue_sync_changes(ids, SyncOperation::Star).await?;
+
+    trigger_sync()
+}
@@ -0,0 +1,143 @@
+use super::{settings::get_global_settings, sync::update_sync_status};
+use crate::tao::{connection::db, global::get_sync_push};
+use chrono::{Duration, NaiveDateTime, Utc};
+use common::{
+    constants::{SYNC_OUTBOX_RETRY_MAX, SYNC_OUTBOX_RETRY_MIN},
+    types::enums::SyncOperation,
+};
+use entity::sync_outbox;
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbErr, EntityTrait, Iden,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+
+/// Queues local changes, the sync loop pushes them after a short debounce and retries
+/// them until they reach the remote, even across restarts
+pub async fn queue_sync_changes(ids: Vec<Uuid>, operation: SyncOperation) -> Result<(), DbErr> {
+    if !get_global_settings().sync || ids.is_empty() {
+        return Ok(());
+    }
+
+    let db = db().await?;
+    let now = Utc::now().naive_utc();
+
+    for id in ids {
+        let queued = sync_outbox::Entity::find_by_id(id).one(&db).await?;
+
+        match queued {
+            Some(queued) => {
+                // a deletion wins, and an upload already carries the star
+                if queued.operation == SyncOperation::Delete.to_string()
+                    || (queued.operation == SyncOperation::Upload.to_string()
+                        && operation == SyncOperation::Star)
+                {
+                    continue;
+                }
+
+                let mut model: sync_outbox::ActiveModel = queued.into();
+                model.operation = Set(operation.to_string());
+                model.attempts = Set(0);
+                model.error = Set(None);
+                model.next_attempt_at = Set(now);
+                model.update(&db).await?;
+            }
+            None => {
+                sync_outbox::ActiveModel {
+                    clipboard_id: Set(id),
+                    operation: Set(operation.to_string()),
+                    next_attempt_at: Set(now),
+                    created_at: Set(now),
+                    ..Default::default()
+                }
+                .insert(&db)
+                .await?;
+            }
+        }
+    }
+
+    get_sync_push().notify_one();
+    update_sync_status(|_| {}).await;
+
+    Ok(())
+}
+
+/// Local changes that did not reach the remote yet
+pub async fn count_sync_outbox_db() -> Result<u64, DbErr> {
+    let db = db().await?;
+
+    sync_outbox::Entity::find().count(&db).await
+}
+
+/// Operations whose next attempt is due, oldest first
+pub async fn get_due_sync_outbox_db() -> Result<Vec<sync_outbox::Model>, DbErr> {
+    let db = db().await?;
+
+    sync_outbox::Entity::find()
+        .filter(sync_outbox::Column::NextAttemptAt.lte(Utc::now().naive_utc()))
+        .order_by_asc(sync_outbox::Column::CreatedAt)
+        .all(&db)
+        .await
+}
+
+/// When the earliest operation waiting for a retry is due
+pub async fn get_next_sync_outbox_attempt_db() -> Result<Option<NaiveDateTime>, DbErr> {
+    let db = db().await?;
+
+    Ok(sync_outbox::Entity::find()
+        .order_by_asc(sync_outbox::Column::NextAttemptAt)
+        .one(&db)
+        .await?
+        .map(|entry| entry.next_attempt_at))
+}
+
+/// Clipboards with a deletion that did not reach the remote yet
+pub async fn get_pending_sync_deletions_db() -> Result<Vec<Uuid>, DbErr> {
+    let db = db().await?;
+
+    sync_outbox::Entity::find()
+        .filter(sync_outbox::Column::Operation.eq(SyncOperation::Delete.to_string()))
+        .select_only()
+        .column(sync_outbox::Column::ClipboardId)
+        .into_tuple()
+        .all(&db)
+        .await
+}
+
+/// Removes a pushed operation, unless it was queued again while being pushed
+pub async fn complete_sync_outbox_db(entry: &sync_outbox::Model) -> Result<(), DbErr> {
+    let db = db().await?;
+
+    sync_outbox::Entity::delete_many()
+        .filter(sync_outbox::Column::ClipboardId.eq(entry.clipboard_id))
+        .filter(sync_outbox::Column::Operation.eq(entry.operation.clone()))
+        .filter(sync_outbox::Column::NextAttemptAt.eq(entry.next_attempt_at))
+        .exec(&db)
+        .await?;
+
+    Ok(())
+}
+
+/// Keeps a failed operation for a later retry with exponential backoff
+pub async fn fail_sync_outbox_db(entry: &sync_outbox::Model, error: String) -> Result<(), DbErr> {
+    let db = db().await?;
+
+    let attempts = entry.attempts + 1;
+    let delay = SYNC_OUTBOX_RETRY_MIN
+        .saturating_mul(1 << (attempts - 1).clamp(0, 16))
+        .min(SYNC_OUTBOX_RETRY_MAX);
+
+    // an operation queued again in the meantime is due right away instead
+    sync_outbox::Entity::update_many()
+        .set(sync_outbox::ActiveModel {
+            attempts: Set(attempts),
+            error: Set(Some(error)),
+            next_attempt_at: Set(Utc::now().naive_utc() + Duration::seconds(delay)),
+            ..Default::default()
+        })
+        .filter(sync_outbox::Column::ClipboardId.eq(entry.clipboard_id))
+        .filter(sync_outbox::Column::NextAttemptAt.eq(entry.next_attempt_at))
+        .exec(&db)
+        .await?;
+
+    Ok(())
+}
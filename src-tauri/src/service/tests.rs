@@ -0,0 +1,273 @@
+use super::cipher::{
+    create_encryption_key, generate_recovery_key, get_clipboard_key, is_same_encryption_key,
+    unwrap_recovery_key, wrap_encryption_key, wrap_recovery_key,
+};
+use super::search::{
+    index_terms, matches_search, query_terms, remove_clipboard_index, search_index_query,
+    write_clipboard_index,
+};
+use super::settings::{remove_local_settings, settings_for_sync};
+use chrono::Utc;
+use common::{
+    constants::SYNC_HOTKEYS_KEY,
+    types::{
+        cipher::EncryptionError,
+        orm_query::FullClipboardDto,
+        sync::{SyncHotkey, SyncRevision},
+    },
+};
+use entity::{clipboard, clipboard_search, clipboard_text, settings};
+use ring::hmac;
+use sea_orm::{
+    prelude::Uuid, ColumnTrait, ConnectionTrait, Database, DatabaseConnection, DbBackend,
+    EntityTrait, IntoActiveModel, QueryFilter, Schema,
+};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+
+fn text_clipboard(data: &str) -> FullClipboardDto {
+    let id = Uuid::now_v7();
+
+    FullClipboardDto {
+        clipboard: clipboard::Model {
+            id,
+            types: json!(["text"]),
+            encrypted: true,
+            created_at: Utc::now().naive_utc(),
+            ..Default::default()
+        },
+        text: Some(clipboard_text::Model {
+            id: Uuid::now_v7(),
+            clipboard_id: id,
+            r#type: "text".to_string(),
+            data: data.to_string(),
+        }),
+        html: None,
+        image: None,
+        rtf: None,
+        files: Vec::new(),
+    }
+}
+
+/// Database with only the tables the search index needs
+async fn search_db() -> DatabaseConnection {
+    let db = Database::connect("sqlite::memory:").await.unwrap();
+    let schema = Schema::new(DbBackend::Sqlite);
+    db.execute(
+        db.get_database_backend()
+            .build(&schema.create_table_from_entity(clipboard::Entity)),
+    )
+    .await
+    .unwrap();
+    db.execute(
+        db.get_database_backend()
+            .build(&schema.create_table_from_entity(clipboard_search::Entity)),
+    )
+    .await
+    .unwrap();
+    db
+}
+
+async fn indexed(db: &DatabaseConnection, key: &hmac::Key, clipboard: &FullClipboardDto) {
+    clipboard::Entity::insert(clipboard.clipboard.clone().into_active_model())
+        .exec_without_returning(db)
+        .await
+        .unwrap();
+    write_clipboard_index(db, clipboard, key).await.unwrap();
+}
+
+/// Ids the index offers as candidates for `search`
+async fn candidates(db: &DatabaseConnection, key: &hmac::Key, search: &str) -> HashSet<Uuid> {
+    clipboard::Entity::find()
+        .filter(
+            clipboard::Column::Id
+                .in_subquery(search_index_query(search, std::slice::from_ref(key))),
+        )
+        .all(db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|clipboard| clipboard.id)
+        .collect()
+}
+
+fn search_key() -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, b"search index test key")
+}
+
+#[test]
+fn text_is_indexed_as_lowercase_ngrams() {
+    let terms: HashSet<&str> = ["a", "b", "c", "ab", "bc", "abc"].into();
+
+    assert_eq!(
+        index_terms("AbC"),
+        terms.into_iter().map(str::to_string).collect()
+    );
+}
+
+#[test]
+fn long_texts_are_indexed_to_the_end() {
+    let text = format!("{}needle", "a".repeat(10_000));
+
+    assert!(query_terms("needle").is_subset(&index_terms(&text)));
+}
+
+#[test]
+fn searches_match_the_whole_string_or_all_its_trigrams() {
+    assert_eq!(query_terms("Ab"), HashSet::from(["ab".to_string()]));
+    assert_eq!(
+        query_terms("abcd"),
+        HashSet::from(["abc".to_string(), "bcd".to_string()])
+    );
+}
+
+#[tokio::test]
+async fn index_offers_every_entry_with_the_trigrams_of_the_search() {
+    let db = search_db().await;
+    let key = search_key();
+    let greeting = text_clipboard("Hello World");
+    let other = text_clipboard("something else");
+    indexed(&db, &key, &greeting).await;
+    indexed(&db, &key, &other).await;
+
+    assert_eq!(
+        candidates(&db, &key, "lo wo").await,
+        HashSet::from([greeting.clipboard.id])
+    );
+    assert_eq!(
+        candidates(&db, &key, "e").await,
+        HashSet::from([greeting.clipboard.id, other.clipboard.id])
+    );
+    // tokens of another key never match
+    let other_key = hmac::Key::new(hmac::HMAC_SHA256, b"another key");
+    assert!(candidates(&db, &other_key, "hello").await.is_empty());
+}
+
+#[tokio::test]
+async fn candidates_are_checked_against_the_decrypted_text() {
+    let db = search_db().await;
+    let key = search_key();
+    // every trigram of the search occurs, but not the search itself
+    let scattered = text_clipboard("xyz abc bcx cxy");
+    indexed(&db, &key, &scattered).await;
+
+    assert_eq!(
+        candidates(&db, &key, "abcxyz").await,
+        HashSet::from([scattered.clipboard.id])
+    );
+    assert!(!matches_search(&scattered, "abcxyz"));
+    assert!(matches_search(&scattered, "ABC BCX"));
+}
+
+#[tokio::test]
+async fn updated_entries_are_indexed_again() {
+    let db = search_db().await;
+    let key = search_key();
+    let mut clipboard = text_clipboard("first draft");
+    indexed(&db, &key, &clipboard).await;
+
+    if let Some(text) = clipboard.text.as_mut() {
+        text.data = "final version".to_string();
+    }
+    write_clipboard_index(&db, &clipboard, &key).await.unwrap();
+
+    assert!(candidates(&db, &key, "draft").await.is_empty());
+    assert_eq!(
+        candidates(&db, &key, "final").await,
+        HashSet::from([clipboard.clipboard.id])
+    );
+
+    remove_clipboard_index(&db, clipboard.clipboard.id)
+        .await
+        .unwrap();
+    assert!(candidates(&db, &key, "final").await.is_empty());
+}
+
+#[test]
+fn recovery_key_unwraps_the_data_key_to_reset_the_password() {
+    let keys = create_encryption_key("password").unwrap();
+    let data_key = get_clipboard_key(false).unwrap();
+
+    let recovery_key = generate_recovery_key().unwrap();
+    let wrapped = wrap_recovery_key(&recovery_key, &data_key).unwrap();
+
+    // typed without dashes and in lowercase
+    let typed = recovery_key.replace('-', "").to_lowercase();
+    let recovered = unwrap_recovery_key(&typed, &wrapped).unwrap();
+    assert_eq!(*recovered, *data_key);
+
+    // the new password wraps the same data key, so synced keys still replace each other
+    let reset = wrap_encryption_key("new password", Some(wrapped)).unwrap();
+    assert!(is_same_encryption_key(&keys, &reset));
+}
+
+#[test]
+fn wrong_recovery_key_is_rejected() {
+    let wrapped = wrap_recovery_key(&generate_recovery_key().unwrap(), &[7u8; 32]).unwrap();
+
+    let other = generate_recovery_key().unwrap();
+    assert!(matches!(
+        unwrap_recovery_key(&other, &wrapped),
+        Err(EncryptionError::InvalidRecoveryKey)
+    ));
+    assert!(matches!(
+        unwrap_recovery_key("not a recovery key", &wrapped),
+        Err(EncryptionError::InvalidRecoveryKey)
+    ));
+}
+
+#[test]
+fn excluded_settings_stay_local_and_hotkeys_round_trip() {
+    let revision = SyncRevision {
+        revision: 1,
+        modified_by: Some("a".to_string()),
+    };
+    let device_a = settings::Model {
+        sync: true,
+        dark_mode: true,
+        language: "en".to_string(),
+        sync_revisions: Some(json!(HashMap::from([
+            ("dark_mode", revision.clone()),
+            (SYNC_HOTKEYS_KEY, revision.clone()),
+        ]))),
+        ..Default::default()
+    };
+    let hotkeys = vec![SyncHotkey {
+        event: "window_display_toggle".to_string(),
+        ctrl: true,
+        alt: false,
+        shift: true,
+        key: "KeyY".to_string(),
+        status: true,
+    }];
+    let remote = settings_for_sync(&device_a, &hotkeys, HashMap::new()).unwrap();
+
+    let device_b = settings::Model {
+        sync: true,
+        dark_mode: false,
+        language: "de".to_string(),
+        sync_excluded_settings: json!(["dark_mode"]),
+        ..Default::default()
+    };
+
+    // downloaded, the excluded field and the list of excluded fields are not taken over
+    let mut downloaded = remote.clone();
+    remove_local_settings(&device_b, &mut downloaded);
+    assert!(!downloaded.contains_key("dark_mode"));
+    assert!(!downloaded.contains_key("sync_excluded_settings"));
+    assert_eq!(downloaded["language"], json!("en"));
+
+    let synced: Vec<SyncHotkey> =
+        serde_json::from_value(downloaded[SYNC_HOTKEYS_KEY].clone()).unwrap();
+    assert_eq!(synced, hotkeys);
+
+    // uploaded, the excluded field keeps the value and revision of the other devices
+    let uploaded = settings_for_sync(&device_b, &synced, remote).unwrap();
+    assert_eq!(uploaded["dark_mode"], json!(true));
+    assert_eq!(uploaded["language"], json!("de"));
+    assert_eq!(uploaded[SYNC_HOTKEYS_KEY], json!(hotkeys));
+
+    let revisions: HashMap<String, SyncRevision> =
+        serde_json::from_value(uploaded["sync_revisions"].clone()).unwrap();
+    assert_eq!(revisions["dark_mode"], revision);
+}
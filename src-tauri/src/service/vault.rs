@@ -0,0 +1,173 @@
+use super::{
+    cipher::{
+        clear_vault_key, generate_salt, get_remote_setting, is_encryption_key_set,
+        is_vault_key_set, set_vault_key, vault_key_check,
+    },
+    clipboard::{
+        get_clipboard_db, init_clipboards, load_clipboards_with_relations, upsert_clipboard_dto,
+    },
+    decrypt::decrypt_clipboard,
+    encrypt::encrypt_clipboard,
+    search::{index_clipboard_db, index_missing_clipboards_db},
+    settings::{get_global_settings, update_settings_db},
+    sync_outbox::queue_sync_changes,
+};
+use crate::tao::connection::db;
+use common::types::{cipher::EncryptionError, enums::SyncOperation, types::CommandError};
+use entity::clipboard;
+use sea_orm::{prelude::Uuid, ColumnTrait, EntityTrait, QueryFilter};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Unlocks the vault, the passphrase is verified against the check value of the vault key.
+/// Creating the vault needs the passphrase entered twice, as nothing could catch a typo later
+pub async fn unlock_vault(
+    password: String,
+    confirm_password: Option<String>,
+) -> Result<(), CommandError> {
+    let (salt, known) = match get_vault_salt().await? {
+        Some((salt, known)) => (salt, known),
+        None => (
+            generate_salt().map_err(|e| CommandError::new(&e.to_string()))?,
+            None,
+        ),
+    };
+    set_vault_key(&password, &salt).map_err(|e| CommandError::new(&e.to_string()))?;
+
+    let verified = verify_vault_key(&password, confirm_password.as_deref(), known).await;
+    if verified.is_err() {
+        clear_vault_key();
+    }
+    let check = verified?;
+
+    let mut settings = get_global_settings();
+    if settings.vault_check.as_ref() != Some(&check) || settings.vault_salt.as_ref() != Some(&salt)
+    {
+        settings.vault_check = Some(check);
+        settings.vault_salt = Some(salt);
+        update_settings_db(settings).await?;
+    }
+
+    // index vault entries that were synced while the vault was locked
+    index_missing_clipboards_db().await?;
+    init_clipboards();
+
+    Ok(())
+}
+
+/// Salt of the vault and the check value of its key, `None` if no vault was created yet
+async fn get_vault_salt() -> Result<Option<(String, Option<String>)>, CommandError> {
+    let settings = get_global_settings();
+    if let Some(salt) = settings.vault_salt {
+        return Ok(Some((salt, settings.vault_check)));
+    }
+
+    // another device may have created the vault before the settings were synced here
+    let remote_string = |value: serde_json::Value| value.as_str().map(str::to_string);
+    let Some(salt) = get_remote_setting("vault_salt")
+        .await?
+        .and_then(remote_string)
+    else {
+        return Ok(None);
+    };
+    let known = get_remote_setting("vault_check")
+        .await?
+        .and_then(remote_string);
+
+    Ok(Some((salt, known)))
+}
+
+/// Checks the vault key that was just set against the `known` check value and returns its own
+async fn verify_vault_key(
+    password: &str,
+    confirm_password: Option<&str>,
+    known: Option<String>,
+) -> Result<String, CommandError> {
+    let check = vault_key_check().map_err(|e| CommandError::new(&e.to_string()))?;
+
+    let verified = match known {
+        Some(known) => known == check,
+        // without a check value one of the vault entries is opened instead
+        None => match open_vault_entry().await? {
+            Some(opened) => opened,
+            None => match confirm_password {
+                Some(confirm_password) if confirm_password == password => true,
+                Some(_) => return Err(CommandError::new("MAIN.ERROR.PASSWORD_NOT_MATCH")),
+                None => return Err(CommandError::new("MAIN.ERROR.VAULT_CONFIRM_PASSWORD")),
+            },
+        },
+    };
+
+    if !verified {
+        return Err(CommandError::new("MAIN.ERROR.INCORRECT_PASSWORD"));
+    }
+
+    Ok(check)
+}
+
+/// Whether a vault entry decrypts with the vault key, `None` if the vault is empty
+async fn open_vault_entry() -> Result<Option<bool>, CommandError> {
+    let db = db().await?;
+    let Some(clipboard) = clipboard::Entity::find()
+        .filter(clipboard::Column::Vault.eq(true))
+        .one(&db)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let mut clipboards = load_clipboards_with_relations(vec![clipboard]).await?;
+    match decrypt_clipboard(clipboards.remove(0)).map(Zeroizing::new) {
+        Ok(_) => Ok(Some(true)),
+        Err(EncryptionError::DecryptionFailed | EncryptionError::InvalidKey) => Ok(Some(false)),
+        Err(e) => Err(CommandError::new(&e.to_string())),
+    }
+}
+
+pub fn lock_vault() {
+    clear_vault_key();
+    init_clipboards();
+}
+
+/// Moves a clipboard into or out of the vault, re-encrypting it with the matching key
+pub async fn vault_clipboard_db(id: Uuid, vault: bool) -> Result<(), CommandError> {
+    if !is_vault_key_set() {
+        return Err(CommandError::new(&EncryptionError::VaultLocked.to_string()));
+    }
+
+    let settings = get_global_settings();
+    let mut clipboard = get_clipboard_db(id).await?;
+
+    if clipboard.clipboard.vault == vault {
+        return Ok(());
+    }
+
+    // entries leaving the vault fall back to the history encryption
+    let encrypt = vault || settings.encryption;
+    if encrypt && !vault && !is_encryption_key_set() {
+        return Err(CommandError::new(&EncryptionError::NoKey.to_string()));
+    }
+
+    if clipboard.clipboard.encrypted {
+        clipboard = decrypt_clipboard(clipboard).map_err(|e| CommandError::new(&e.to_string()))?;
+    }
+    clipboard.clipboard.vault = vault;
+
+    let stored = if encrypt {
+        encrypt_clipboard(clipboard.clone())
+    } else {
+        clipboard.clone()
+    };
+    upsert_clipboard_dto(stored).await?;
+
+    if encrypt {
+        index_clipboard_db(&clipboard).await?;
+        clipboard.zeroize();
+    }
+
+    // the remote copy is replaced the same way a starred clipboard is
+    queue_sync_changes(vec![id], SyncOperation::Star).await?;
+
+    init_clipboards();
+
+    Ok(())
+}
@@ -1,9 +1,10 @@
 use crate::tao::global::get_app;
 use common::{
-    constants::{CONFIG_NAME, DB_NAME},
+    constants::{CONFIG_NAME, DB_NAME, DEVICE_NAME_DEFAULT},
     printlog,
     types::types::{Config, DataPath},
 };
+use sea_orm::prelude::Uuid;
 use std::{
     fs,
     path::{Path, PathBuf},
@@ -19,7 +20,10 @@ pub fn setup_config() {
     }
 
     let config = Config {
-        db: format!("{}", &data_path.db_file_path),
+        db: data_path.db_file_path.clone(),
+        sync_folder: None,
+        device_id: Some(Uuid::now_v7().to_string()),
+        device_name: Some(default_device_name()),
     };
 
     fs::write(
@@ -80,6 +84,58 @@ pub fn get_config() -> (Config, DataPath) {
     (config, data_path)
 }
 
+pub fn set_sync_folder(sync_folder: Option<String>) {
+    let (mut config, data_path) = get_config();
+
+    config.sync_folder = sync_folder;
+
+    fs::write(
+        &data_path.config_file_path,
+        serde_json::to_string(&config).expect("Failed to serialize config"),
+    )
+    .expect("Failed to write config");
+}
+
+/// Id of this installation, configs written before it existed get one on first use
+pub fn load_device_id() -> String {
+    let (mut config, data_path) = get_config();
+
+    if let Some(device_id) = config.device_id {
+        return device_id;
+    }
+
+    let device_id = Uuid::now_v7().to_string();
+    config.device_id = Some(device_id.clone());
+    config.device_name = config.device_name.or_else(|| Some(default_device_name()));
+
+    if let Err(e) = fs::write(
+        &data_path.config_file_path,
+        serde_json::to_string(&config).expect("Failed to serialize config"),
+    ) {
+        printlog!("failed to save device id: {:?}", e);
+    }
+
+    device_id
+}
+
+/// Name of this installation shown to the other devices
+pub fn get_device_name() -> String {
+    get_config()
+        .0
+        .device_name
+        .unwrap_or_else(default_device_name)
+}
+
+fn default_device_name() -> String {
+    ["COMPUTERNAME", "HOSTNAME"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEVICE_NAME_DEFAULT.to_string())
+}
+
 pub fn change_clipboard_db_location_enable() {
     // get local config from app data
     let (mut config, data_path) = get_config();
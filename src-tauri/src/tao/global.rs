@@ -1,12 +1,17 @@
-use super::tao_constants::{
-    APP, CLIPBOARD_CACHE, HOTKEYS, HOTKEY_MANAGER, HOTKEY_RUNNING, HOTKEY_STOP_TX, MAIN_WINDOW,
-    WINDOW_STOP_TX,
+use super::{
+    config::load_device_id,
+    tao_constants::{
+        APP, DEVICE_ID, HOTKEYS, HOTKEY_MANAGER, HOTKEY_RUNNING, HOTKEY_STOP_TX, JOB_CANCEL,
+        JOB_LOCK, MAIN_WINDOW, SYNC_PULL, SYNC_PUSH, SYNC_STATUS, WINDOW_STOP_TX,
+    },
+};
+use common::types::{hotkey::SafeHotKeyManager, sync::SyncStatus, types::Key};
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, MutexGuard},
 };
-use common::types::{hotkey::SafeHotKeyManager, orm_query::FullClipboardDto, types::Key};
-use moka::sync::Cache;
-use std::{collections::HashMap, sync::MutexGuard, time::Duration};
 use tauri::{AppHandle, WebviewWindow};
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, Notify};
 
 pub fn get_main_window() -> MutexGuard<'static, WebviewWindow> {
     MAIN_WINDOW
@@ -56,14 +61,39 @@ pub fn get_hotkey_running() -> MutexGuard<'static, bool> {
         .expect("Failed to lock HOTKEY_RUNNING")
 }
 
+pub fn get_job_lock() -> &'static tokio::sync::Mutex<()> {
+    JOB_LOCK.get().expect("Failed to get JOB_LOCK")
+}
+
+pub fn get_job_cancel() -> &'static AtomicBool {
+    JOB_CANCEL.get().expect("Failed to get JOB_CANCEL")
+}
+
+pub fn get_sync_push() -> &'static Notify {
+    SYNC_PUSH.get().expect("Failed to get SYNC_PUSH")
+}
+
+pub fn get_sync_pull() -> &'static Notify {
+    SYNC_PULL.get().expect("Failed to get SYNC_PULL")
+}
+
+pub fn get_sync_status_store() -> MutexGuard<'static, SyncStatus> {
+    SYNC_STATUS
+        .get()
+        .expect("Failed to get SYNC_STATUS")
+        .lock()
+        .expect("Failed to lock SYNC_STATUS")
+}
+
+pub fn get_device_id() -> &'static str {
+    DEVICE_ID.get_or_init(load_device_id)
+}
+
 pub fn get_app() -> &'static AppHandle {
     APP.get().expect("Failed to get APP")
 }
 
-pub fn get_cache() -> &'static Cache<String, Vec<FullClipboardDto>> {
-    CLIPBOARD_CACHE.get_or_init(|| {
-        Cache::builder()
-            .time_to_live(Duration::from_secs(300)) // 5 minutes TTL
-            .build()
-    })
+/// The app handle, `None` while no app runs, e.g. in tests
+pub fn try_get_app() -> Option<&'static AppHandle> {
+    APP.get()
 }
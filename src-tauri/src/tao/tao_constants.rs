@@ -1,15 +1,12 @@
-use common::types::{
-    enums::WebWindow, hotkey::SafeHotKeyManager, orm_query::FullClipboardDto, types::Key,
-};
+use common::types::{enums::WebWindow, hotkey::SafeHotKeyManager, sync::SyncStatus, types::Key};
 use global_hotkey::GlobalHotKeyManager;
-use moka::sync::Cache;
 use sea_orm::Iden;
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex, OnceLock},
+    sync::{atomic::AtomicBool, Arc, Mutex, OnceLock},
 };
 use tauri::{Manager, WebviewWindow};
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, Notify};
 
 pub static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
 pub static MAIN_WINDOW: OnceLock<Arc<Mutex<WebviewWindow>>> = OnceLock::new();
@@ -19,7 +16,14 @@ pub static HOTKEY_RUNNING: OnceLock<Arc<Mutex<bool>>> = OnceLock::new();
 pub static HOTKEYS: OnceLock<Arc<Mutex<HashMap<u32, Key>>>> = OnceLock::new();
 pub static HOTKEY_STOP_TX: OnceLock<Mutex<Option<oneshot::Sender<()>>>> = OnceLock::new();
 pub static WINDOW_STOP_TX: OnceLock<Mutex<Option<oneshot::Sender<()>>>> = OnceLock::new();
-pub static CLIPBOARD_CACHE: OnceLock<Cache<String, Vec<FullClipboardDto>>> = OnceLock::new();
+
+pub static JOB_LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+pub static JOB_CANCEL: OnceLock<AtomicBool> = OnceLock::new();
+
+pub static SYNC_PUSH: OnceLock<Notify> = OnceLock::new();
+pub static SYNC_PULL: OnceLock<Notify> = OnceLock::new();
+pub static SYNC_STATUS: OnceLock<Mutex<SyncStatus>> = OnceLock::new();
+pub static DEVICE_ID: OnceLock<String> = OnceLock::new();
 
 pub fn setup_globals(app: &mut tauri::App) {
     APP.set(app.handle().clone())
@@ -41,6 +45,21 @@ pub fn setup_globals(app: &mut tauri::App) {
     WINDOW_STOP_TX
         .set(Mutex::new(None))
         .unwrap_or_else(|_| panic!("Failed to initialize WINDOW_STOP_TX"));
+    JOB_LOCK
+        .set(tokio::sync::Mutex::new(()))
+        .unwrap_or_else(|_| panic!("Failed to initialize JOB_LOCK"));
+    JOB_CANCEL
+        .set(AtomicBool::new(false))
+        .unwrap_or_else(|_| panic!("Failed to initialize JOB_CANCEL"));
+    SYNC_PUSH
+        .set(Notify::new())
+        .unwrap_or_else(|_| panic!("Failed to initialize SYNC_PUSH"));
+    SYNC_PULL
+        .set(Notify::new())
+        .unwrap_or_else(|_| panic!("Failed to initialize SYNC_PULL"));
+    SYNC_STATUS
+        .set(Mutex::new(SyncStatus::default()))
+        .unwrap_or_else(|_| panic!("Failed to initialize SYNC_STATUS"));
     MAIN_WINDOW
         .set(Arc::new(Mutex::new(
             app.get_webview_window(WebWindow::Main.to_string().as_str())
@@ -2,15 +2,17 @@ use crate::prelude::*;
 use crate::service::cipher::is_encryption_key_set;
 use crate::service::clipboard::{new_clipboard_event, upsert_clipboard_dto};
 use crate::service::encrypt::encrypt_clipboard;
+use crate::service::search::index_clipboard_db;
 use crate::service::settings::get_global_settings;
 use crate::service::{
     clipboard::{get_last_clipboard_db, insert_clipboard_dbo},
+    sync_outbox::queue_sync_changes,
     window::calculate_thumbnail_dimensions,
 };
-use crate::tao::global::{get_app, get_cache};
+use crate::tao::global::{get_app, get_device_id};
 use base64::{engine::general_purpose::STANDARD, Engine};
 use chrono::DateTime;
-use common::types::enums::{ClipboardTextType, ClipboardType};
+use common::types::enums::{ClipboardTextType, ClipboardType, SyncOperation};
 use common::types::orm_query::FullClipboardDbo;
 use common::types::types::TextMatcher;
 use image::imageops;
@@ -24,6 +26,7 @@ use tauri_plugin_clipboard::Clipboard;
 use urlencoding::decode;
 
 pub trait ClipboardManagerExt {
+    #[allow(clippy::new_ret_no_self)]
     fn new() -> FullClipboardDbo;
     fn upsert_clipboard() -> impl std::future::Future<Output = ()> + Send;
     fn check_if_last_is_same(&mut self) -> impl std::future::Future<Output = bool> + Send;
@@ -107,10 +110,18 @@ impl ClipboardManagerExt for FullClipboardDbo {
                 upsert_clipboard_dto(encrypt_clipboard(clipboard.clone()))
                     .await
                     .expect("Failed to upsert");
+
+                // Index the plaintext so encrypted clipboards stay searchable
+                if let Err(e) = index_clipboard_db(&clipboard).await {
+                    printlog!("Failed to index clipboard: {:?}", e);
+                }
             }
 
-            // Clear cache for encrypted clipboards search
-            get_cache().invalidate_all();
+            if let Err(e) =
+                queue_sync_changes(vec![clipboard.clipboard.id], SyncOperation::Upload).await
+            {
+                printlog!("Failed to queue clipboard for sync: {:?}", e);
+            }
 
             new_clipboard_event(clipboard);
         }
@@ -119,7 +130,7 @@ impl ClipboardManagerExt for FullClipboardDbo {
     async fn check_if_last_is_same(&mut self) -> bool {
         if let Ok(last) = get_last_clipboard_db().await {
             let last_types = ClipboardType::from_json_value(&last.clipboard.types);
-            let curr_types = ClipboardType::from_json_value(&self.clipboard_model.types.as_ref());
+            let curr_types = ClipboardType::from_json_value(self.clipboard_model.types.as_ref());
 
             match (last_types, curr_types) {
                 (Some(lt), Some(ct)) if lt.len() == ct.len() => {
@@ -286,6 +297,7 @@ impl ClipboardManagerExt for FullClipboardDbo {
         self.clipboard_model = entity::clipboard::ActiveModel {
             id: Set(Uuid::now_v7()),
             types: Set(ClipboardType::to_json_value(&types)),
+            device_id: Set(Some(get_device_id().to_string())),
             ..Default::default()
         };
     }
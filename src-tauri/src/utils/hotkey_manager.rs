@@ -164,7 +164,7 @@ pub fn parse_shortcut(ctrl: bool, alt: bool, shift: bool, key: &str) -> String {
 fn format_key_for_parsing(key: &str) -> String {
     if key.len() >= 2 && (key.starts_with('F') || key.starts_with('f')) {
         if let Ok(number) = key[1..].parse::<u32>() {
-            if number >= 1 && number <= 24 {
+            if (1..=24).contains(&number) {
                 // Adjust the range if necessary
                 return key.to_uppercase(); // This is a function key like F1, F2, ..., F24
             }
@@ -1,24 +1,29 @@
-use super::parse_clipboard_info;
-use crate::prelude::*;
-use crate::{
-    service::settings::{get_global_settings, update_settings_synchronize_db},
-    tao::{config::get_data_path, global::get_app},
-    utils::providers::{create_clipboard_filename, uuid_to_datetime},
+use super::{
+    cleanup_old_clipboards, compare_and_fetch_new_clipboards, create_clipboard_filename,
+    dedupe_clipboards, ignore_not_found,
+    network::{drive_connector, network_settings, DriveConnector},
+    parse_clipboard_info,
+    payload::{decode_payload, delete_chunks, encode_payload, remote_chunks, ChunkStore},
+    star_clipboard,
+    token_storage::EncryptedTokenStorage,
+    upload_new_clipboards, uuid_to_datetime,
 };
+use crate::{service::settings::update_settings_synchronize_db, tao::global::get_app};
 use chrono::{NaiveDateTime, TimeZone, Utc};
 use common::{
-    constants::{BACKUP_FILE_PREFIX, BACKUP_SETTINGS_PREFIX, TOKEN_NAME},
+    constants::{
+        BACKUP_FILE_PREFIX, BACKUP_SETTINGS_PREFIX, SYNC_MANIFEST_NAME, SYNC_PAYLOAD_VERSION,
+    },
     printlog,
     types::{
-        enums::ListenEvent,
         orm_query::FullClipboardDto,
-        sync::{Clippy, GoogleDriveProvider, SyncProvider},
-        types::{CommandError, Progress},
+        sync::{Clippy, GoogleDriveProvider, SyncError, SyncManifest, SyncProvider},
+        types::CommandError,
     },
 };
 use google_drive3::{
     api::*,
-    hyper_rustls, hyper_util,
+    hyper_util,
     yup_oauth2::{self, authenticator_delegate::InstalledFlowDelegate},
     DriveHub,
 };
@@ -26,8 +31,8 @@ use http_body_util::BodyExt;
 use migration::async_trait;
 use sea_orm::prelude::Uuid;
 use serde_json::Value;
-use std::{collections::HashMap, future::Future, io::Cursor, pin::Pin};
-use tauri::{Emitter, EventTarget, Manager};
+use std::{collections::HashMap, future::Future, io::Cursor, pin::Pin, sync::Mutex};
+use tauri::Manager;
 use tauri_plugin_clipboard::Clipboard;
 use tauri_plugin_opener::OpenerExt;
 
@@ -44,17 +49,18 @@ impl InstalledFlowDelegate for BrowserUrlOpenFlowDelegate {
             get_app()
                 .state::<Clipboard>()
                 .write_text(url.to_string())
-                .expect("Failed to write URL to clipboard");
+                .map_err(|e| e.to_string())?;
             get_app()
                 .opener()
                 .open_url(url, None::<String>)
-                .expect("Failed to open URL");
+                .map_err(|e| e.to_string())?;
             Ok(String::new())
         })
     }
 }
 
-pub struct GoogleDriveProviderImpl(GoogleDriveProvider);
+/// Keeps the head revision of the manifest as last read, `None` if there was none
+pub struct GoogleDriveProviderImpl(GoogleDriveProvider<DriveConnector>, Mutex<Option<String>>);
 
 impl GoogleDriveProviderImpl {
     pub async fn new() -> Result<Self, CommandError> {
@@ -96,59 +102,58 @@ impl GoogleDriveProviderImpl {
             ..Default::default()
         };
 
-        let token_path = std::path::Path::new(&get_data_path().config_path).join(TOKEN_NAME);
+        // the login and the Drive requests both go through the configured proxy
+        let connector = drive_connector(&network_settings())?;
+        let auth_client =
+            hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+                .build(connector.clone());
 
-        let auth = yup_oauth2::InstalledFlowAuthenticator::builder(
+        let auth = yup_oauth2::InstalledFlowAuthenticator::with_client(
             secret,
             yup_oauth2::InstalledFlowReturnMethod::HTTPRedirect,
+            auth_client,
         )
         .flow_delegate(Box::new(BrowserUrlOpenFlowDelegate))
-        .persist_tokens_to_disk(token_path)
+        .with_storage(Box::new(EncryptedTokenStorage::load()))
         .build()
         .await?;
 
         let client =
             hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
-                .build(
-                    hyper_rustls::HttpsConnectorBuilder::new()
-                        .with_native_roots()?
-                        .https_or_http()
-                        .enable_http1()
-                        .build(),
-                );
+                .build(connector);
 
         let provider = GoogleDriveProvider {
             hub: DriveHub::new(client, auth),
         };
 
-        let impl_provider = Self(provider); // Changed: Use tuple struct initialization
+        let impl_provider = Self(provider, Mutex::new(None)); // Changed: Use tuple struct initialization
 
         if impl_provider.is_authenticated().await {
             printlog!("authenticated with Google Drive");
-            update_settings_synchronize_db(true)
-                .await
-                .expect("Failed to update settings");
+            update_settings_synchronize_db(true).await?;
         }
 
         Ok(impl_provider)
     }
 
-    async fn fetch_all_clipboard_files(&self) -> Result<Vec<File>, Box<dyn std::error::Error>> {
+    async fn fetch_all_clipboard_files(&self) -> Result<Vec<File>, SyncError> {
         let mut all_files = Vec::new();
         let mut page_token = None;
 
-        while let Ok((_, file_list)) = self
-            .0
-            .hub
-            .files()
-            .list()
-            .q(&format!("name contains '{}'", BACKUP_FILE_PREFIX))
-            .spaces("appDataFolder")
-            .add_scope(Scope::Appdata.as_ref())
-            .page_token(page_token.as_deref().unwrap_or_default())
-            .doit()
-            .await
-        {
+        // a failed page fails the listing, a partial one would look like remote deletions
+        loop {
+            let (_, file_list) = self
+                .0
+                .hub
+                .files()
+                .list()
+                .q(&format!("name contains '{}'", BACKUP_FILE_PREFIX))
+                .spaces("appDataFolder")
+                .add_scope(Scope::Appdata.as_ref())
+                .page_token(page_token.as_deref().unwrap_or_default())
+                .doit()
+                .await?;
+
             if let Some(files) = file_list.files {
                 all_files.extend(files);
             }
@@ -162,7 +167,7 @@ impl GoogleDriveProviderImpl {
         Ok(all_files)
     }
 
-    async fn find_settings_file(&self) -> Result<Option<File>, Box<dyn std::error::Error>> {
+    async fn find_settings_file(&self) -> Result<Option<File>, SyncError> {
         let (_, file_list) = self
             .0
             .hub
@@ -176,137 +181,137 @@ impl GoogleDriveProviderImpl {
 
         Ok(file_list.files.and_then(|files| files.into_iter().next()))
     }
+
+    async fn find_manifest_file(&self) -> Result<Option<File>, SyncError> {
+        let (_, file_list) = self
+            .0
+            .hub
+            .files()
+            .list()
+            .q(&format!("name = '{}'", SYNC_MANIFEST_NAME))
+            .spaces("appDataFolder")
+            .param("fields", "files(id,headRevisionId)")
+            .add_scope(Scope::Appdata.as_ref())
+            .doit()
+            .await?;
+
+        Ok(file_list.files.and_then(|files| files.into_iter().next()))
+    }
+
+    async fn download_file(&self, id: &str) -> Result<Vec<u8>, SyncError> {
+        let (mut response, _) = self
+            .0
+            .hub
+            .files()
+            .get(id)
+            .param("alt", "media")
+            .acknowledge_abuse(true)
+            .add_scope(Scope::Appdata.as_ref())
+            .doit()
+            .await?;
+
+        Ok(response
+            .body_mut()
+            .collect()
+            .await
+            .map_err(|e| SyncError::Network(e.to_string()))?
+            .to_bytes()
+            .to_vec())
+    }
+
+    async fn delete_file(&self, id: &str) -> Result<(), SyncError> {
+        self.0
+            .hub
+            .files()
+            .delete(id)
+            .add_scope(Scope::Appdata.as_ref())
+            .doit()
+            .await?;
+
+        Ok(())
+    }
 }
 
+/// Chunks are files in the app data folder, the file id is the chunk id
 #[async_trait::async_trait]
-impl SyncProvider for GoogleDriveProviderImpl {
-    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, Box<dyn std::error::Error>> {
-        let filelist = self.fetch_all_clipboard_files().await?;
-        let mut clipboards = Vec::new();
-        let mut seen_ids = std::collections::HashSet::new();
-
-        for file in filelist {
-            if let Some(remote) =
-                parse_clipboard_info(&file.name.expect("No name"), &file.id.expect("No id"))
-            {
-                // Only add if we haven't seen this ID before
-                if seen_ids.insert(remote.id) {
-                    clipboards.push(remote);
-                } else {
-                    printlog!(
-                        "Found duplicate clipboard: {} from {}, deleting duplicate",
-                        remote.id,
-                        uuid_to_datetime(&remote.id)
-                    );
-                    // Delete the duplicate file
-                    self.delete_clipboard(&remote).await;
-                }
-            }
-        }
+impl ChunkStore for GoogleDriveProviderImpl {
+    async fn put_chunk(&self, name: &str, content: Vec<u8>) -> Result<String, SyncError> {
+        let file = File {
+            name: Some(name.to_string()),
+            mime_type: Some("application/octet-stream".into()),
+            parents: Some(vec!["appDataFolder".into()]),
+            ..Default::default()
+        };
 
-        // Sort by created_at
-        clipboards.sort_by(|a, b| b.id.cmp(&a.id));
-
-        if !clipboards.is_empty() {
-            let newest = &clipboards[0];
-            let oldest = &clipboards[clipboards.len() - 1];
-            printlog!(
-                "(remote) found {} clipboards from {} to {}",
-                clipboards.len(),
-                uuid_to_datetime(&oldest.id),
-                uuid_to_datetime(&newest.id)
-            );
-        } else {
-            printlog!("(remote) found no clipboards");
-        }
+        let (_, file) = self
+            .0
+            .hub
+            .files()
+            .create(file)
+            .add_scope(Scope::Appdata.as_ref())
+            .upload(
+                Cursor::new(content),
+                "application/octet-stream"
+                    .parse()
+                    .map_err(|_| "Invalid mime type")?,
+            )
+            .await?;
 
-        Ok(clipboards)
+        file.id
+            .ok_or_else(|| SyncError::CorruptPayload("uploaded chunk has no id".to_string()))
     }
 
-    async fn compare_and_fetch_new_clipboards(
-        &self,
-        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
-        remote_clipboards: &Vec<Clippy>,
-    ) -> Result<Vec<FullClipboardDto>, Box<dyn std::error::Error>> {
-        let mut new_clipboards = Vec::new();
-
-        let total = remote_clipboards.len();
-        for (index, file) in remote_clipboards.iter().enumerate() {
-            // Skip if the clipboard is marked for deletion
-            if file.deleted_at.is_some() {
-                continue;
-            }
+    async fn get_chunk(&self, id: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        Ok(Some(self.download_file(id).await?))
+    }
 
-            if let Some((local_star, _local_timestamp)) = local_clipboards.get(&file.id) {
-                // star status is different
-                if local_star == &file.star {
-                    continue;
-                }
-            }
+    async fn delete_chunk(&self, id: &str) -> Result<(), SyncError> {
+        self.delete_file(id).await
+    }
+}
 
-            printlog!(
-                "downloading clipboard: {} from {} star: {} encrypted: {}",
-                file.id,
-                uuid_to_datetime(&file.id),
-                file.star,
-                file.encrypted
-            );
-
-            get_app().emit_to(
-                EventTarget::any(),
-                ListenEvent::Progress.to_string().as_str(),
-                Progress {
-                    label: "SETTINGS.ENCRYPT.DOWNLOADING_REMOTE_CLIPBOARDS".to_string(),
-                    total,
-                    current: index + 1,
-                },
-            )?;
-
-            new_clipboards.push(self.download_by_id(&file.provider_id).await?);
-        }
+#[async_trait::async_trait]
+impl SyncProvider for GoogleDriveProviderImpl {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        let clipboards = self
+            .fetch_all_clipboard_files()
+            .await?
+            .into_iter()
+            .filter_map(|file| parse_clipboard_info(file.name.as_deref()?, file.id.as_ref()?))
+            .collect();
+
+        Ok(dedupe_clipboards(self, clipboards).await)
+    }
 
-        Ok(new_clipboards)
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        compare_and_fetch_new_clipboards(self, local_clipboards, remote_clipboards).await
     }
 
     async fn upload_new_clipboards(
         &self,
         new_local_clipboards: &[FullClipboardDto],
-        remote_clipboards: &Vec<Clippy>,
-    ) -> Result<Vec<Clippy>, Box<dyn std::error::Error>> {
-        let mut new_clipboards = Vec::new();
-
-        // Skip clipboards that were recently deleted
-        let recently_deleted: Vec<_> = remote_clipboards
-            .iter()
-            .filter(|c| c.deleted_at.is_some())
-            .map(|c| c.id)
-            .collect();
-
-        for clipboard in new_local_clipboards {
-            if remote_clipboards
-                .iter()
-                .any(|clip| clip.id == clipboard.clipboard.id)
-                || recently_deleted.contains(&clipboard.clipboard.id)
-            {
-                continue;
-            }
-            new_clipboards.push(self.upload_clipboard(clipboard).await?);
-        }
-
-        Ok(new_clipboards)
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
     }
 
-    async fn mark_for_deletion(&self, clippy: &Clippy) {
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
         let new_name = create_clipboard_filename(
             &clippy.id,
             &clippy.star,
             &clippy.encrypted,
             &clippy.created_at,
             Some(Utc::now().naive_utc()),
+            clippy.format,
+            clippy.chunks,
         );
 
         let file = google_drive3::api::File {
-            name: Some(new_name),
+            name: Some(new_name.clone()),
             ..Default::default()
         };
 
@@ -316,119 +321,40 @@ impl SyncProvider for GoogleDriveProviderImpl {
             .update(file, &clippy.provider_id)
             .add_scope(Scope::Appdata.as_ref())
             .doit_without_upload()
-            .await
-            .expect("Failed to rename file");
+            .await?;
 
         printlog!("(remote) marked clipboard for deletion: {}", clippy.id);
-    }
 
-    async fn delete_clipboard(&self, clippy: &Clippy) {
-        self.0
-            .hub
-            .files()
-            .delete(&clippy.provider_id)
-            .add_scope(Scope::Appdata.as_ref())
-            .doit()
-            .await
-            .ok();
+        Ok(parse_clipboard_info(&new_name, &clippy.provider_id)
+            .ok_or("Failed to parse clipboard info")?)
     }
 
-    async fn download_by_id(
-        &self,
-        id: &String,
-    ) -> Result<FullClipboardDto, Box<dyn std::error::Error>> {
-        let (mut response, _) = self
-            .0
-            .hub
-            .files()
-            .get(id)
-            .param("alt", "media")
-            .acknowledge_abuse(true)
-            .add_scope(Scope::Appdata.as_ref())
-            .doit()
-            .await?;
-
-        let content = String::from_utf8(response.body_mut().collect().await?.to_bytes().to_vec())?;
-        Ok(serde_json::from_str(&content)?)
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        let chunks = remote_chunks(self, clippy).await;
+        ignore_not_found(self.delete_file(&clippy.provider_id).await)?;
+        delete_chunks(self, &chunks, &[]).await;
+        Ok(())
     }
 
-    async fn cleanup_old_clipboards(
-        &self,
-        remote_clipboards: &Vec<Clippy>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let sync_limit = get_global_settings().sync_limit as usize;
-
-        // Get all non-starred clipboards
-        let mut all_clipboards: Vec<_> =
-            remote_clipboards.iter().filter(|clip| !clip.star).collect();
-
-        // Sort by creation date
-        all_clipboards.sort_by(|a, b| a.id.cmp(&b.id));
-
-        // Find all marked-for-deletion indices
-        let marked_indices: Vec<usize> = all_clipboards
-            .iter()
-            .enumerate()
-            .filter(|(_, clip)| clip.deleted_at.is_some())
-            .map(|(idx, _)| idx)
-            .collect();
-
-        // Only delete if we have clipboards beyond the sync limit
-        if all_clipboards.len() <= sync_limit {
-            return Ok(());
-        }
-
-        if let Some(&last_marked_idx) = marked_indices.last() {
-            // Calculate how many clipboards we need to delete
-
-            let total_to_delete = all_clipboards.len() - sync_limit + marked_indices.len();
-
-            printlog!(
-                "total clipboards: {}, marked clipboards: {}, total to delete: {} (last marked: {})",
-                all_clipboards.len(),
-                marked_indices.len(),
-                total_to_delete,
-                last_marked_idx
-            );
-            // Only delete if we can remove everything up to and including the last marked clipboard
-            if total_to_delete > last_marked_idx {
-                // Delete oldest clipboards including the marked ones
-                for clippy in all_clipboards.iter().take(total_to_delete) {
-                    printlog!(
-                        "deleting clipboard: {} from {} (marked: {})",
-                        clippy.id,
-                        uuid_to_datetime(&clippy.id),
-                        clippy.deleted_at.is_some()
-                    );
-                    self.delete_clipboard(clippy).await;
-                }
-            }
-        } else {
-            // No marked clipboards - normal cleanup
-            let to_delete = all_clipboards.len() - sync_limit;
-            for clippy in all_clipboards.iter().take(to_delete) {
-                printlog!(
-                    "deleting clipboard: {} from {}",
-                    clippy.id,
-                    uuid_to_datetime(&clippy.id),
-                );
-                self.delete_clipboard(clippy).await;
-            }
-        }
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        let content = self.download_file(id).await?;
+        decode_payload(self, &content).await
+    }
 
-        Ok(())
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
     }
 
-    async fn upload_clipboard(
-        &self,
-        clipboard: &FullClipboardDto,
-    ) -> Result<Clippy, Box<dyn std::error::Error>> {
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        let payload = encode_payload(self, clipboard).await?;
         let file_name = create_clipboard_filename(
             &clipboard.clipboard.id,
             &clipboard.clipboard.star,
             &clipboard.clipboard.encrypted,
             &clipboard.clipboard.created_at,
             None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
         );
 
         printlog!(
@@ -441,7 +367,7 @@ impl SyncProvider for GoogleDriveProviderImpl {
 
         let file = File {
             name: Some(file_name),
-            mime_type: Some("application/json".into()),
+            mime_type: Some("application/octet-stream".into()),
             created_time: Some(Utc.from_utc_datetime(&clipboard.clipboard.created_at)),
             parents: Some(vec!["appDataFolder".into()]),
             ..Default::default()
@@ -454,23 +380,30 @@ impl SyncProvider for GoogleDriveProviderImpl {
             .create(file)
             .add_scope(Scope::Appdata.as_ref())
             .upload(
-                Cursor::new(serde_json::to_string(&clipboard)?),
-                "application/json".parse()?,
+                Cursor::new(payload.content),
+                "application/octet-stream"
+                    .parse()
+                    .map_err(|_| "Invalid mime type")?,
             )
             .await?;
 
-        Ok(parse_clipboard_info(
-            file.name.as_ref().expect("No name"),
-            file.id.as_ref().expect("No id"),
-        )
-        .expect("Failed to parse clipboard info"))
+        let (Some(name), Some(id)) = (file.name, file.id) else {
+            return Err(SyncError::CorruptPayload(
+                "uploaded file has no name or id".to_string(),
+            ));
+        };
+
+        Ok(parse_clipboard_info(&name, &id).ok_or("Failed to parse clipboard info")?)
     }
 
     async fn update_clipboard(
         &self,
         local_clipboard: &FullClipboardDto,
         remote_clipboard: &Clippy,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<Clippy, SyncError> {
+        let stale_chunks = remote_chunks(self, remote_clipboard).await;
+        let payload = encode_payload(self, local_clipboard).await?;
+
         // Create new filename
         let new_name = create_clipboard_filename(
             &remote_clipboard.id,
@@ -478,6 +411,8 @@ impl SyncProvider for GoogleDriveProviderImpl {
             &local_clipboard.clipboard.encrypted,
             &local_clipboard.clipboard.created_at,
             None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
         );
 
         printlog!(
@@ -490,8 +425,8 @@ impl SyncProvider for GoogleDriveProviderImpl {
 
         // Create metadata update
         let file = google_drive3::api::File {
-            name: Some(new_name),
-            mime_type: Some("application/json".into()),
+            name: Some(new_name.clone()),
+            mime_type: Some("application/octet-stream".into()),
             ..Default::default()
         };
 
@@ -502,47 +437,26 @@ impl SyncProvider for GoogleDriveProviderImpl {
             .update(file, &remote_clipboard.provider_id)
             .add_scope(Scope::Appdata.as_ref())
             .upload(
-                Cursor::new(serde_json::to_string(&local_clipboard)?),
-                "application/json".parse()?,
+                Cursor::new(payload.content),
+                "application/octet-stream"
+                    .parse()
+                    .map_err(|_| "Invalid mime type")?,
             )
             .await?;
 
-        Ok(())
+        delete_chunks(self, &stale_chunks, &payload.chunks).await;
+
+        Ok(
+            parse_clipboard_info(&new_name, &remote_clipboard.provider_id)
+                .ok_or("Failed to parse clipboard info")?,
+        )
     }
 
-    async fn star_clipboard(&self, clippy: &FullClipboardDto) {
-        let clipboards = self
-            .fetch_all_clipboards()
-            .await
-            .expect("Failed to star fetch clipboards");
-
-        let remote_clipboards = clipboards
-            .iter()
-            .find(|clip| clip.id == clippy.clipboard.id);
-
-        if let Some(remote_clipboard) = remote_clipboards {
-            printlog!(
-                "starring clipboard: {} from {} star: {} encrypted: {}",
-                remote_clipboard.id,
-                uuid_to_datetime(&remote_clipboard.id),
-                clippy.clipboard.star,
-                clippy.clipboard.encrypted
-            );
-
-            self.update_clipboard(clippy, remote_clipboard)
-                .await
-                .expect("Failed to star update clipboard");
-        } else {
-            self.upload_clipboard(clippy)
-                .await
-                .expect("Failed to star upload clipboard");
-        }
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
     }
 
-    async fn upsert_settings(
-        &self,
-        settings: &HashMap<String, Value>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
         let file = File {
             name: Some(format!("{}.json", BACKUP_SETTINGS_PREFIX)),
             mime_type: Some("application/json".into()),
@@ -559,7 +473,9 @@ impl SyncProvider for GoogleDriveProviderImpl {
             .add_scope(Scope::Appdata.as_ref())
             .upload(
                 Cursor::new(serde_json::to_string(settings)?),
-                "application/json".parse()?,
+                "application/json"
+                    .parse()
+                    .map_err(|_| "Invalid mime type")?,
             )
             .await?;
 
@@ -568,36 +484,127 @@ impl SyncProvider for GoogleDriveProviderImpl {
         Ok(())
     }
 
-    async fn get_settings(
-        &self,
-    ) -> Result<HashMap<String, serde_json::Value>, Box<dyn std::error::Error>> {
-        let file = match self.find_settings_file().await? {
-            Some(f) => f,
-            None => return Ok(HashMap::new()),
+    async fn get_settings(&self) -> Result<HashMap<String, serde_json::Value>, SyncError> {
+        let Some(file_id) = self.find_settings_file().await?.and_then(|file| file.id) else {
+            return Ok(HashMap::new());
         };
 
         let (mut response, _) = self
             .0
             .hub
             .files()
-            .get(&file.id.expect("No id"))
+            .get(&file_id)
             .param("alt", "media")
             .acknowledge_abuse(true)
             .add_scope(Scope::Appdata.as_ref())
             .doit()
             .await?;
 
-        let content = String::from_utf8(response.body_mut().collect().await?.to_bytes().to_vec())?;
+        let content = response
+            .body_mut()
+            .collect()
+            .await
+            .map_err(|e| SyncError::Network(e.to_string()))?
+            .to_bytes();
 
-        let settings: HashMap<String, serde_json::Value> = serde_json::from_str(&content)?;
+        let settings: HashMap<String, serde_json::Value> = serde_json::from_slice(&content)?;
 
         Ok(settings)
     }
 
-    async fn is_authenticated(&self) -> bool {
-        match self.0.hub.auth.get_token(&[Scope::Appdata.as_ref()]).await {
-            Ok(_) => true,
-            Err(_) => false,
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        let file = self.find_manifest_file().await?;
+        *self.1.lock().map_err(|e| e.to_string())? =
+            file.as_ref().and_then(|file| file.head_revision_id.clone());
+        let Some(file_id) = file.and_then(|file| file.id) else {
+            return Ok(None);
+        };
+
+        let (mut response, _) = self
+            .0
+            .hub
+            .files()
+            .get(&file_id)
+            .param("alt", "media")
+            .acknowledge_abuse(true)
+            .add_scope(Scope::Appdata.as_ref())
+            .doit()
+            .await?;
+
+        let content = response
+            .body_mut()
+            .collect()
+            .await
+            .map_err(|e| SyncError::Network(e.to_string()))?
+            .to_bytes();
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    /// Drive has no conditional update, so the head revision is compared right before the
+    /// write. Only the manifest this device read last is replaced
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        let content = Cursor::new(serde_json::to_string(manifest)?);
+
+        let file = self.find_manifest_file().await?;
+        let read_revision = self.1.lock().map_err(|e| e.to_string())?.clone();
+        if file.as_ref().and_then(|file| file.head_revision_id.clone()) != read_revision {
+            return Err(SyncError::Conflict(format!(
+                "{} was changed by another device",
+                SYNC_MANIFEST_NAME
+            )));
         }
+
+        // The manifest is updated in place, a second copy would never be read
+        let (_, written) = match file.and_then(|file| file.id) {
+            Some(file_id) => {
+                self.0
+                    .hub
+                    .files()
+                    .update(File::default(), &file_id)
+                    .param("fields", "id,headRevisionId")
+                    .add_scope(Scope::Appdata.as_ref())
+                    .upload(
+                        content,
+                        "application/json"
+                            .parse()
+                            .map_err(|_| "Invalid mime type")?,
+                    )
+                    .await?
+            }
+            None => {
+                let file = File {
+                    name: Some(SYNC_MANIFEST_NAME.to_string()),
+                    mime_type: Some("application/json".into()),
+                    parents: Some(vec!["appDataFolder".into()]),
+                    ..Default::default()
+                };
+
+                self.0
+                    .hub
+                    .files()
+                    .create(file)
+                    .param("fields", "id,headRevisionId")
+                    .add_scope(Scope::Appdata.as_ref())
+                    .upload(
+                        content,
+                        "application/json"
+                            .parse()
+                            .map_err(|_| "Invalid mime type")?,
+                    )
+                    .await?
+            }
+        };
+        *self.1.lock().map_err(|e| e.to_string())? = written.head_revision_id;
+
+        Ok(())
+    }
+
+    async fn is_authenticated(&self) -> bool {
+        self.0
+            .hub
+            .auth
+            .get_token(&[Scope::Appdata.as_ref()])
+            .await
+            .is_ok()
     }
 }
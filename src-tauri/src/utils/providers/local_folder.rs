@@ -0,0 +1,616 @@
+use super::{
+    cleanup_old_clipboards, create_clipboard_filename, dedupe_clipboards, ignore_not_found,
+    parse_clipboard_info,
+    payload::{
+        decode_payload, delete_chunks, encode_payload, open_settings, remote_chunks, seal_settings,
+        ChunkStore,
+    },
+    star_clipboard, upload_new_clipboards, uuid_to_datetime,
+};
+use crate::{prelude::*, tao::config::get_config};
+use chrono::{NaiveDateTime, Utc};
+use common::{
+    constants::{
+        BACKUP_CHUNK_PREFIX, BACKUP_FILE_PREFIX, BACKUP_SETTINGS_PREFIX,
+        SYNC_MANIFEST_LOCK_STALE_SECS, SYNC_MANIFEST_NAME, SYNC_PAYLOAD_VERSION,
+    },
+    types::{
+        orm_query::FullClipboardDto,
+        sync::{
+            Clippy, ManifestEntry, SyncError, SyncManifest, SyncProvider, SyncRevision, Tombstone,
+        },
+        types::CommandError,
+    },
+};
+use migration::async_trait;
+use regex::Regex;
+use ring::digest;
+use sea_orm::prelude::Uuid;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+/// Conflict copies of Syncthing (`name.sync-conflict-20240101-120000-ABCDEFG.json`)
+/// and Dropbox/Nextcloud (`name (conflicted copy 2024-01-01).json`)
+static CONFLICT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\.sync-conflict-\d{8}-\d{6}-[A-Z0-9]{7}| \([^)]*conflicted copy[^)]*\))")
+        .expect("Invalid conflict regex")
+});
+
+/// Combines two copies of the synced settings, every field keeps the value of the newer
+/// revision as in `update_settings_from_sync`
+pub fn merge_settings(
+    mut settings: HashMap<String, Value>,
+    mut other: HashMap<String, Value>,
+) -> HashMap<String, Value> {
+    let take_revisions = |settings: &mut HashMap<String, Value>| -> HashMap<String, SyncRevision> {
+        settings
+            .remove("sync_revisions")
+            .and_then(|revisions| serde_json::from_value(revisions).ok())
+            .unwrap_or_default()
+    };
+    let mut revisions = take_revisions(&mut settings);
+    let other_revisions = take_revisions(&mut other);
+
+    for (key, value) in other {
+        let revision = other_revisions.get(&key).cloned().unwrap_or_default();
+        let known = revisions.get(&key).cloned().unwrap_or_default();
+
+        if !settings.contains_key(&key) || revision > known {
+            settings.insert(key.clone(), value);
+            if let Some(revision) = other_revisions.get(&key) {
+                revisions.insert(key, revision.clone());
+            }
+        }
+    }
+    settings.insert("sync_revisions".to_string(), json!(revisions));
+
+    settings
+}
+
+/// Combines two copies of the manifest written from the same revision. Entries keep their newer
+/// revision and everything the copies disagree on is moved to a new revision, so devices that
+/// read either copy download it again. Deletions and revocations are never undone
+pub fn merge_manifests(mut manifest: SyncManifest, other: SyncManifest) -> SyncManifest {
+    let revision = manifest.revision.max(other.revision) + 1;
+
+    for (id, entry) in manifest.clipboards.iter_mut() {
+        if !other.clipboards.contains_key(id) {
+            entry.revision = revision;
+        }
+    }
+    for (id, tombstone) in manifest.tombstones.iter_mut() {
+        if !other.tombstones.contains_key(id) {
+            tombstone.revision = revision;
+        }
+    }
+
+    for (id, entry) in other.clipboards {
+        match manifest.clipboards.get_mut(&id) {
+            Some(known) if *known == entry => {}
+            Some(known) => {
+                if entry.revision > known.revision {
+                    *known = entry;
+                }
+                known.revision = revision;
+            }
+            None => {
+                manifest
+                    .clipboards
+                    .insert(id, ManifestEntry { revision, ..entry });
+            }
+        }
+    }
+
+    for (id, device) in other.devices {
+        let merged = match manifest.devices.get(&id) {
+            Some(known) => known.merge(&device),
+            None => device,
+        };
+        manifest.devices.insert(id, merged);
+    }
+
+    for (id, tombstone) in other.tombstones {
+        manifest.tombstones.entry(id).or_insert(Tombstone {
+            revision,
+            ..tombstone
+        });
+    }
+
+    manifest.revision = revision;
+    // both copies are only as complete as the older rebuild
+    manifest.rebuilt_at = manifest.rebuilt_at.min(other.rebuilt_at);
+
+    manifest
+}
+
+/// Stores clipboards as files in a folder that is synced by another tool (Syncthing, Dropbox, ...).
+/// Files use the same names as on Google Drive, so the filename is the provider id
+pub struct LocalFolderProviderImpl {
+    path: PathBuf,
+    /// Hash of the manifest as last read, `None` if there was none. Writes only replace it
+    manifest_hash: Mutex<Option<String>>,
+}
+
+/// Lock file of the manifest, removed again when dropped
+struct ManifestLock(PathBuf);
+
+impl Drop for ManifestLock {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+fn content_hash(content: &[u8]) -> String {
+    digest::digest(&digest::SHA256, content)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl LocalFolderProviderImpl {
+    pub async fn new() -> Result<Self, CommandError> {
+        let (config, _) = get_config();
+        let path = config
+            .sync_folder
+            .ok_or_else(|| CommandError::new("MAIN.ERROR.SYNC_FOLDER_NOT_CONFIGURED"))?;
+
+        Ok(Self::from_path(path))
+    }
+
+    pub fn from_path(path: String) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            manifest_hash: Mutex::new(None),
+        }
+    }
+
+    fn file_path(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Names of all files in the folder
+    async fn list_files(&self) -> Result<Vec<String>, SyncError> {
+        let mut entries = fs::read_dir(&self.path).await?;
+        let mut names = Vec::new();
+
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Merges conflict copies into the file they were copied from. A copy without its original
+    /// takes its place, the settings and manifest are merged, chunks are named by their content
+    /// so their copies are dropped, and of a clipboard the copy written last is kept
+    async fn resolve_conflicts(&self, names: Vec<String>) -> Result<Vec<String>, SyncError> {
+        let mut resolved: HashSet<String> = names
+            .iter()
+            .filter(|name| !CONFLICT_REGEX.is_match(name))
+            .cloned()
+            .collect();
+
+        // the manifest last, its entries are checked against the resolved files
+        let mut conflicts: Vec<&String> = names
+            .iter()
+            .filter(|name| CONFLICT_REGEX.is_match(name))
+            .collect();
+        conflicts.sort_by_key(|name| CONFLICT_REGEX.replace(name, "") == SYNC_MANIFEST_NAME);
+
+        for name in conflicts {
+            let original = CONFLICT_REGEX.replace(name, "").to_string();
+
+            if !resolved.contains(&original) {
+                printlog!("(folder) restoring conflict copy: {}", name);
+                fs::rename(self.file_path(name), self.file_path(&original)).await?;
+                resolved.insert(original);
+            } else if original == format!("{}.json", BACKUP_SETTINGS_PREFIX) {
+                printlog!("(folder) merging settings conflict copy: {}", name);
+                self.merge_settings_copy(name, &original).await?;
+            } else if original == SYNC_MANIFEST_NAME {
+                printlog!("(folder) merging manifest conflict copy: {}", name);
+                self.merge_manifest_copy(name, &original, &resolved).await?;
+            } else if original.starts_with(BACKUP_CHUNK_PREFIX) {
+                printlog!("(folder) removing conflict copy: {}", name);
+                fs::remove_file(self.file_path(name)).await?;
+            } else {
+                printlog!("(folder) keeping newest of conflict copy: {}", name);
+                self.keep_newest(name, &original).await?;
+            }
+        }
+
+        Ok(resolved.into_iter().collect())
+    }
+
+    /// Keeps whichever of a file and its conflict copy was written last
+    async fn keep_newest(&self, copy: &str, original: &str) -> Result<(), SyncError> {
+        let copy_modified = fs::metadata(self.file_path(copy)).await?.modified()?;
+        let original_modified = fs::metadata(self.file_path(original)).await?.modified()?;
+
+        if copy_modified > original_modified {
+            fs::rename(self.file_path(copy), self.file_path(original)).await?;
+        } else {
+            fs::remove_file(self.file_path(copy)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Settings that can't be read or opened with the current passphrase fall back to the newest copy
+    async fn merge_settings_copy(&self, copy: &str, original: &str) -> Result<(), SyncError> {
+        let read = |name: &str| {
+            let path = self.file_path(name);
+            async move {
+                let content = fs::read(path).await.ok()?;
+                let settings = serde_json::from_slice(&content).ok()?;
+                open_settings(settings).ok()
+            }
+        };
+
+        let (Some(settings), Some(other)) = (read(original).await, read(copy).await) else {
+            return self.keep_newest(copy, original).await;
+        };
+
+        let merged = seal_settings(&merge_settings(settings, other))?;
+        self.write_file(original, serde_json::to_vec(&merged)?)
+            .await?;
+        fs::remove_file(self.file_path(copy)).await?;
+
+        Ok(())
+    }
+
+    /// Entries of clipboards whose file is gone are dropped, they were deleted in one of the copies
+    async fn merge_manifest_copy(
+        &self,
+        copy: &str,
+        original: &str,
+        files: &HashSet<String>,
+    ) -> Result<(), SyncError> {
+        let read = |name: &str| {
+            let path = self.file_path(name);
+            async move {
+                let content = fs::read(path).await.ok()?;
+                serde_json::from_slice::<SyncManifest>(&content).ok()
+            }
+        };
+
+        let (Some(manifest), Some(other)) = (read(original).await, read(copy).await) else {
+            return self.keep_newest(copy, original).await;
+        };
+        if manifest.version != other.version {
+            return self.keep_newest(copy, original).await;
+        }
+
+        let mut merged = merge_manifests(manifest, other);
+        merged
+            .clipboards
+            .retain(|_, entry| files.contains(&entry.provider_id));
+
+        self.write_file(original, serde_json::to_vec(&merged)?)
+            .await?;
+        fs::remove_file(self.file_path(copy)).await?;
+
+        Ok(())
+    }
+
+    /// Creates the lock file of the manifest, waiting while another writer on the same folder
+    /// holds it. A lock left behind by a crashed writer is broken once it is stale
+    async fn lock_manifest(&self) -> Result<ManifestLock, SyncError> {
+        let path = self.file_path(&format!(".{}.lock", SYNC_MANIFEST_NAME));
+        let stale = Duration::from_secs(SYNC_MANIFEST_LOCK_STALE_SECS);
+
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .await
+            {
+                Ok(_) => return Ok(ManifestLock(path)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&path)
+                        .await
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok());
+                    if age.is_some_and(|age| age > stale) {
+                        printlog!("(folder) breaking stale manifest lock");
+                        fs::remove_file(&path).await.ok();
+                    } else {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn cache_manifest_hash(&self, hash: Option<String>) -> Result<(), SyncError> {
+        *self.manifest_hash.lock().map_err(|e| e.to_string())? = hash;
+        Ok(())
+    }
+
+    /// Writes into a hidden temp file first, so other devices never see a partial file
+    async fn write_file(&self, name: &str, content: Vec<u8>) -> Result<(), SyncError> {
+        let tmp_path = self.file_path(&format!(".{}.tmp", name));
+
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(&content).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        fs::rename(&tmp_path, self.file_path(name)).await?;
+
+        Ok(())
+    }
+
+    async fn read_file(&self, name: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        match fs::read(self.file_path(name)).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Chunks are files next to the clipboards, the filename is the chunk id
+#[async_trait::async_trait]
+impl ChunkStore for LocalFolderProviderImpl {
+    async fn put_chunk(&self, name: &str, content: Vec<u8>) -> Result<String, SyncError> {
+        self.write_file(name, content).await?;
+        Ok(name.to_string())
+    }
+
+    async fn get_chunk(&self, id: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        self.read_file(id).await
+    }
+
+    async fn delete_chunk(&self, id: &str) -> Result<(), SyncError> {
+        Ok(fs::remove_file(self.file_path(id)).await?)
+    }
+}
+
+#[async_trait::async_trait]
+impl SyncProvider for LocalFolderProviderImpl {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        let names = self.list_files().await?;
+
+        let clipboards = self
+            .resolve_conflicts(names)
+            .await?
+            .into_iter()
+            .filter(|name| name.starts_with(BACKUP_FILE_PREFIX))
+            .filter_map(|name| parse_clipboard_info(&name, &name))
+            .collect();
+
+        Ok(dedupe_clipboards(self, clipboards).await)
+    }
+
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        let mut new_clipboards = Vec::new();
+
+        for file in remote_clipboards {
+            // Skip if the clipboard is marked for deletion
+            if file.deleted_at.is_some() {
+                continue;
+            }
+
+            if let Some((local_star, _local_timestamp)) = local_clipboards.get(&file.id) {
+                // star status is different
+                if local_star == &file.star {
+                    continue;
+                }
+            }
+
+            // the folder tool may still be writing the file, it is picked up on the next sync
+            match self.download_by_id(&file.provider_id).await.ok() {
+                Some(clipboard) => new_clipboards.push(clipboard),
+                None => printlog!(
+                    "(folder) skipping incomplete clipboard: {}",
+                    file.provider_id
+                ),
+            }
+        }
+
+        Ok(new_clipboards)
+    }
+
+    async fn upload_new_clipboards(
+        &self,
+        new_local_clipboards: &[FullClipboardDto],
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
+    }
+
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
+        let new_name = create_clipboard_filename(
+            &clippy.id,
+            &clippy.star,
+            &clippy.encrypted,
+            &clippy.created_at,
+            Some(Utc::now().naive_utc()),
+            clippy.format,
+            clippy.chunks,
+        );
+
+        fs::rename(
+            self.file_path(&clippy.provider_id),
+            self.file_path(&new_name),
+        )
+        .await?;
+
+        printlog!("(remote) marked clipboard for deletion: {}", clippy.id);
+
+        Ok(parse_clipboard_info(&new_name, &new_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        let chunks = remote_chunks(self, clippy).await;
+        ignore_not_found(
+            fs::remove_file(self.file_path(&clippy.provider_id))
+                .await
+                .map_err(SyncError::from),
+        )?;
+        delete_chunks(self, &chunks, &[]).await;
+        Ok(())
+    }
+
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        let content = self
+            .read_file(id)
+            .await?
+            .ok_or_else(|| SyncError::NotFound(format!("clipboard {}", id)))?;
+
+        decode_payload(self, &content).await
+    }
+
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        let payload = encode_payload(self, clipboard).await?;
+        let file_name = create_clipboard_filename(
+            &clipboard.clipboard.id,
+            &clipboard.clipboard.star,
+            &clipboard.clipboard.encrypted,
+            &clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+
+        printlog!(
+            "uploading clipboard: {} from {} star: {} encrypted: {}",
+            clipboard.clipboard.id,
+            uuid_to_datetime(&clipboard.clipboard.id),
+            clipboard.clipboard.star,
+            clipboard.clipboard.encrypted
+        );
+
+        self.write_file(&file_name, payload.content).await?;
+
+        Ok(parse_clipboard_info(&file_name, &file_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn update_clipboard(
+        &self,
+        local_clipboard: &FullClipboardDto,
+        remote_clipboard: &Clippy,
+    ) -> Result<Clippy, SyncError> {
+        let stale_chunks = remote_chunks(self, remote_clipboard).await;
+        let payload = encode_payload(self, local_clipboard).await?;
+        let new_name = create_clipboard_filename(
+            &remote_clipboard.id,
+            &local_clipboard.clipboard.star, // Use local star status
+            &local_clipboard.clipboard.encrypted,
+            &local_clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+
+        printlog!(
+            "updating clipboard: {} from {} star: {} encrypted: {}",
+            remote_clipboard.id,
+            uuid_to_datetime(&remote_clipboard.id),
+            local_clipboard.clipboard.star,
+            local_clipboard.clipboard.encrypted
+        );
+
+        // Write the new file first, so the clipboard is never missing remotely
+        self.write_file(&new_name, payload.content).await?;
+
+        if new_name != remote_clipboard.provider_id {
+            fs::remove_file(self.file_path(&remote_clipboard.provider_id))
+                .await
+                .ok();
+        }
+        delete_chunks(self, &stale_chunks, &payload.chunks).await;
+
+        Ok(parse_clipboard_info(&new_name, &new_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
+    }
+
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
+    }
+
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
+        self.write_file(
+            &format!("{}.json", BACKUP_SETTINGS_PREFIX),
+            serde_json::to_vec(settings)?,
+        )
+        .await?;
+
+        printlog!("(remote) uploaded settings");
+
+        Ok(())
+    }
+
+    /// A partially written settings file fails to parse, the sync is then retried on the next interval
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError> {
+        match self
+            .read_file(&format!("{}.json", BACKUP_SETTINGS_PREFIX))
+            .await?
+        {
+            Some(content) => Ok(serde_json::from_slice(&content)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// The manifest is a file next to the clipboards, `None` until a device writes it
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        let content = self.read_file(SYNC_MANIFEST_NAME).await?;
+        self.cache_manifest_hash(content.as_deref().map(content_hash))?;
+
+        match content {
+            Some(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Only replaces the manifest this device read last, checked and written under the lock
+    /// file. The tool syncing the folder resolves writes of other machines into conflict copies
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        let _lock = self.lock_manifest().await?;
+
+        let current = self.read_file(SYNC_MANIFEST_NAME).await?;
+        let read_hash = self
+            .manifest_hash
+            .lock()
+            .map_err(|e| e.to_string())?
+            .clone();
+        if current.as_deref().map(content_hash) != read_hash {
+            return Err(SyncError::Conflict(format!(
+                "{} was changed by another device",
+                SYNC_MANIFEST_NAME
+            )));
+        }
+
+        let content = serde_json::to_vec(manifest)?;
+        let hash = content_hash(&content);
+        self.write_file(SYNC_MANIFEST_NAME, content).await?;
+        self.cache_manifest_hash(Some(hash))
+    }
+
+    /// The folder may be on a removable or network drive that is not mounted
+    async fn is_authenticated(&self) -> bool {
+        fs::metadata(&self.path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+    }
+}
@@ -0,0 +1,688 @@
+use super::{
+    cleanup_old_clipboards,
+    payload::{open_settings, seal_settings},
+    retry::with_retry,
+    star_clipboard, upload_new_clipboards, uuid_to_datetime,
+};
+use crate::{prelude::*, service::cipher::get_sync_key, tao::global::get_device_id};
+use chrono::{Duration, NaiveDateTime, Utc};
+use common::{
+    constants::{
+        SYNC_MANIFEST_VERSION, SYNC_MANIFEST_WRITE_ATTEMPTS, SYNC_TOMBSTONE_RETENTION_DAYS,
+    },
+    types::{
+        orm_query::FullClipboardDto,
+        sync::{
+            Clippy, DeviceEntry, ManifestEntry, SyncError, SyncManifest, SyncProvider, Tombstone,
+        },
+    },
+};
+use migration::async_trait;
+use ring::{digest, hmac};
+use sea_orm::prelude::Uuid;
+use serde_json::Value;
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+#[derive(Default)]
+struct ManifestState {
+    /// Remote manifest as last read, with the changes of this device applied
+    manifest: Option<SyncManifest>,
+    /// Changes not written to the remote manifest yet, `None` removes the entry
+    pending: HashMap<Uuid, Option<ManifestEntry>>,
+    /// Device registry changes not written to the remote manifest yet
+    pending_devices: HashMap<String, DeviceEntry>,
+    /// Tombstones not written to the remote manifest yet, `None` removes the tombstone
+    pending_tombstones: HashMap<Uuid, Option<Tombstone>>,
+    /// Manifest revision up to which remote changes were downloaded
+    pulled_revision: u64,
+    /// The remote manifest has a newer format, it is read through a full listing and never written
+    read_only: bool,
+}
+
+/// Wraps a provider so listings come from one manifest file instead of a full scan.
+/// Changes made through it are recorded and written back by `flush_manifest`
+pub struct ManifestProvider {
+    inner: Arc<dyn SyncProvider>,
+    state: Mutex<ManifestState>,
+    /// Written into the tombstones of the clipboards deleted through it
+    device_id: String,
+}
+
+/// Wraps `provider` in the retry and manifest layers every provider is used through
+pub fn with_manifest(provider: impl SyncProvider + 'static) -> Arc<dyn SyncProvider> {
+    with_device_manifest(provider, get_device_id())
+}
+
+/// `with_manifest` acting as the given device
+pub fn with_device_manifest(
+    provider: impl SyncProvider + 'static,
+    device_id: &str,
+) -> Arc<dyn SyncProvider> {
+    Arc::new(ManifestProvider {
+        inner: Arc::new(with_retry(provider)),
+        state: Mutex::new(ManifestState::default()),
+        device_id: device_id.to_string(),
+    })
+}
+
+/// Tombstones that can be dropped, once every active device has seen them or at the latest
+/// when the retention runs out. Tombstones not written yet are kept
+pub fn collectable_tombstones(manifest: &SyncManifest, now: NaiveDateTime) -> Vec<Uuid> {
+    let retention = Duration::days(SYNC_TOMBSTONE_RETENTION_DAYS);
+    let registered = every_device_registered(manifest);
+
+    manifest
+        .tombstones
+        .iter()
+        .filter(|(_, tombstone)| {
+            tombstone.revision > 0
+                && (now - tombstone.deleted_at > retention
+                    || registered
+                        && manifest
+                            .devices
+                            .values()
+                            .filter(|device| device.revoked_at.is_none())
+                            .all(|device| device.seen_revision >= tombstone.revision))
+        })
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+/// Whether every device found in the remote clipboards and tombstones is in the registry.
+/// A device that did not register yet can't tell which deletions it has seen
+fn every_device_registered(manifest: &SyncManifest) -> bool {
+    manifest
+        .clipboards
+        .values()
+        .filter_map(|entry| entry.device_id.as_ref())
+        .chain(
+            manifest
+                .tombstones
+                .values()
+                .filter_map(|tombstone| tombstone.deleted_by.as_ref()),
+        )
+        .all(|device_id| manifest.devices.contains_key(device_id))
+}
+
+/// Keyed with the sync passphrase if one is set, so the manifest reveals nothing about
+/// sealed content and a new passphrase uploads every clipboard again
+fn hash_clipboard(clipboard: &FullClipboardDto) -> Option<String> {
+    let json = serde_json::to_vec(clipboard).ok()?;
+    let hash = match get_sync_key().ok()? {
+        Some(key) => hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key.as_ref()), &json)
+            .as_ref()
+            .to_vec(),
+        None => digest::digest(&digest::SHA256, &json).as_ref().to_vec(),
+    };
+
+    Some(hash.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+impl ManifestProvider {
+    fn state(&self) -> Result<MutexGuard<'_, ManifestState>, SyncError> {
+        Ok(self.state.lock().map_err(|e| e.to_string())?)
+    }
+
+    /// Reads the remote manifest, a missing or outdated one is rebuilt from a full listing
+    async fn load_manifest(&self) -> Result<SyncManifest, SyncError> {
+        let remote = self.inner.get_manifest().await?;
+        let now = Utc::now().naive_utc();
+
+        let remote = match remote {
+            Some(manifest) if manifest.version == SYNC_MANIFEST_VERSION => {
+                self.state()?.read_only = false;
+                return Ok(manifest);
+            }
+            remote => remote,
+        };
+
+        let read_only = remote
+            .as_ref()
+            .is_some_and(|manifest| manifest.version > SYNC_MANIFEST_VERSION);
+        // the counter, registry and tombstones carry over from older formats
+        let older = remote
+            .as_ref()
+            .filter(|manifest| manifest.version <= SYNC_MANIFEST_VERSION);
+        let revision = older.map_or(0, |manifest| manifest.revision) + 1;
+        let devices = older
+            .map(|manifest| manifest.devices.clone())
+            .unwrap_or_default();
+        let mut tombstones = older
+            .map(|manifest| manifest.tombstones.clone())
+            .unwrap_or_default();
+        let previous = remote.filter(|manifest| manifest.version == SYNC_MANIFEST_VERSION);
+
+        let listed = self.inner.fetch_all_clipboards().await?;
+
+        // markers of older versions get a tombstone, so they are collected the same way
+        for clippy in &listed {
+            if let Some(deleted_at) = clippy.deleted_at {
+                tombstones.entry(clippy.id).or_insert(Tombstone {
+                    deleted_at,
+                    deleted_by: None,
+                    revision,
+                });
+            }
+        }
+
+        let clipboards = listed
+            .into_iter()
+            .map(|clippy| {
+                let known = previous
+                    .as_ref()
+                    .and_then(|manifest| manifest.clipboards.get(&clippy.id))
+                    .filter(|entry| {
+                        entry.provider_id == clippy.provider_id
+                            && entry.star == clippy.star
+                            && entry.deleted_at == clippy.deleted_at
+                    });
+
+                let entry = ManifestEntry {
+                    hash: known.and_then(|entry| entry.hash.clone()),
+                    revision: known.map_or(revision, |entry| entry.revision),
+                    device_id: known.and_then(|entry| entry.device_id.clone()),
+                    ..ManifestEntry::from(&clippy)
+                };
+                (clippy.id, entry)
+            })
+            .collect();
+
+        let manifest = SyncManifest {
+            version: SYNC_MANIFEST_VERSION,
+            revision,
+            rebuilt_at: now,
+            clipboards,
+            devices,
+            tombstones,
+        };
+
+        self.state()?.read_only = read_only;
+        if !read_only {
+            match self.inner.put_manifest(&manifest).await {
+                Ok(()) => printlog!("(remote) rebuilt manifest at revision {}", revision),
+                // the next read picks up the manifest the other device wrote
+                Err(SyncError::Conflict(e)) => {
+                    printlog!("(remote) manifest was rebuilt by another device: {}", e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    fn record(
+        &self,
+        clippy: &Clippy,
+        hash: Option<String>,
+        device_id: Option<String>,
+    ) -> Result<(), SyncError> {
+        let entry = ManifestEntry {
+            hash,
+            device_id,
+            ..ManifestEntry::from(clippy)
+        };
+
+        let mut state = self.state()?;
+        if let Some(manifest) = state.manifest.as_mut() {
+            manifest.clipboards.insert(clippy.id, entry.clone());
+        }
+        state.pending.insert(clippy.id, Some(entry));
+
+        // callers only write a tombstoned clipboard again where it may come back
+        if clippy.deleted_at.is_none() {
+            let lifted = state
+                .manifest
+                .as_mut()
+                .and_then(|manifest| manifest.tombstones.remove(&clippy.id))
+                .is_some();
+            if lifted || state.pending_tombstones.contains_key(&clippy.id) {
+                state.pending_tombstones.insert(clippy.id, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn bury(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        let tombstone = Tombstone {
+            deleted_at: clippy.deleted_at.unwrap_or_else(|| Utc::now().naive_utc()),
+            deleted_by: Some(self.device_id.clone()),
+            revision: 0,
+        };
+
+        let mut state = self.state()?;
+        if let Some(manifest) = state.manifest.as_mut() {
+            manifest.tombstones.insert(clippy.id, tombstone.clone());
+        }
+        state.pending_tombstones.insert(clippy.id, Some(tombstone));
+
+        Ok(())
+    }
+
+    fn known_hash(&self, id: &Uuid) -> Option<String> {
+        let state = self.state().ok()?;
+        state.manifest.as_ref()?.clipboards.get(id)?.hash.clone()
+    }
+
+    fn known_device(&self, id: &Uuid) -> Option<String> {
+        let state = self.state().ok()?;
+        state
+            .manifest
+            .as_ref()?
+            .clipboards
+            .get(id)?
+            .device_id
+            .clone()
+    }
+}
+
+/// Adds `devices` to a registry, entries of the same device are merged
+fn merge_devices(
+    registry: &mut HashMap<String, DeviceEntry>,
+    devices: &HashMap<String, DeviceEntry>,
+) {
+    for (id, device) in devices {
+        let merged = match registry.get(id) {
+            Some(known) => known.merge(device),
+            None => device.clone(),
+        };
+        registry.insert(id.clone(), merged);
+    }
+}
+
+/// Writes the changes recorded by a device into a manifest under a new revision
+fn apply_changes(
+    manifest: &mut SyncManifest,
+    pending: &HashMap<Uuid, Option<ManifestEntry>>,
+    pending_devices: &HashMap<String, DeviceEntry>,
+    pending_tombstones: &HashMap<Uuid, Option<Tombstone>>,
+) {
+    manifest.revision += 1;
+    for (id, entry) in pending {
+        match entry {
+            Some(entry) => manifest.clipboards.insert(
+                *id,
+                ManifestEntry {
+                    revision: manifest.revision,
+                    ..entry.clone()
+                },
+            ),
+            None => manifest.clipboards.remove(id),
+        };
+    }
+    merge_devices(&mut manifest.devices, pending_devices);
+    for (id, tombstone) in pending_tombstones {
+        match tombstone {
+            // an earlier deletion of the clipboard by another device is kept
+            Some(tombstone) => {
+                manifest.tombstones.entry(*id).or_insert(Tombstone {
+                    revision: manifest.revision,
+                    ..tombstone.clone()
+                });
+            }
+            None => {
+                manifest.tombstones.remove(id);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SyncProvider for ManifestProvider {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        let mut manifest = self.load_manifest().await?;
+
+        let mut state = self.state()?;
+        for (id, entry) in &state.pending {
+            match entry {
+                Some(entry) => manifest.clipboards.insert(*id, entry.clone()),
+                None => manifest.clipboards.remove(id),
+            };
+        }
+        merge_devices(&mut manifest.devices, &state.pending_devices);
+        for (id, tombstone) in &state.pending_tombstones {
+            match tombstone {
+                Some(tombstone) => manifest.tombstones.insert(*id, tombstone.clone()),
+                None => manifest.tombstones.remove(id),
+            };
+        }
+
+        // a clipboard uploaded again by an older version stays deleted
+        let mut clipboards: Vec<Clippy> = manifest
+            .clipboards
+            .iter()
+            .map(|(id, entry)| {
+                let mut clippy = entry.to_clippy(*id);
+                if let Some(tombstone) = manifest.tombstones.get(id) {
+                    clippy.deleted_at.get_or_insert(tombstone.deleted_at);
+                }
+                clippy
+            })
+            .collect();
+        clipboards.sort_by_key(|clippy| Reverse(clippy.id));
+
+        printlog!(
+            "(remote) manifest revision {} lists {} clipboards",
+            manifest.revision,
+            clipboards.len()
+        );
+
+        state.manifest = Some(manifest);
+
+        Ok(clipboards)
+    }
+
+    /// Only entries that changed since the last download or are missing locally are compared
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        let (changed, revision) = {
+            let state = self.state()?;
+            let Some(manifest) = state.manifest.as_ref() else {
+                return Err("remote manifest was not loaded".into());
+            };
+
+            let changed: Vec<Clippy> = remote_clipboards
+                .iter()
+                .filter(|clippy| {
+                    !local_clipboards.contains_key(&clippy.id)
+                        || manifest
+                            .clipboards
+                            .get(&clippy.id)
+                            .is_none_or(|entry| entry.revision > state.pulled_revision)
+                })
+                .cloned()
+                .collect();
+
+            (changed, manifest.revision)
+        };
+
+        let new_clipboards = self
+            .inner
+            .compare_and_fetch_new_clipboards(local_clipboards, &changed)
+            .await?;
+
+        self.state()?.pulled_revision = revision;
+
+        Ok(new_clipboards)
+    }
+
+    async fn upload_new_clipboards(
+        &self,
+        new_local_clipboards: &[FullClipboardDto],
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
+    }
+
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
+        let marked = self.inner.mark_for_deletion(clippy).await?;
+        self.record(
+            &marked,
+            self.known_hash(&clippy.id),
+            self.known_device(&clippy.id),
+        )?;
+        self.bury(&marked)?;
+
+        Ok(marked)
+    }
+
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        self.inner.delete_clipboard(clippy).await?;
+
+        let mut state = self.state()?;
+
+        // a duplicate file of the clipboard does not remove the listed one
+        let listed = state
+            .manifest
+            .as_ref()
+            .and_then(|manifest| manifest.clipboards.get(&clippy.id))
+            .is_some_and(|entry| entry.provider_id == clippy.provider_id);
+
+        if listed {
+            if let Some(manifest) = state.manifest.as_mut() {
+                manifest.clipboards.remove(&clippy.id);
+            }
+            state.pending.insert(clippy.id, None);
+        }
+
+        Ok(())
+    }
+
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        self.inner.download_by_id(id).await
+    }
+
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        let uploaded = self.inner.upload_clipboard(clipboard).await?;
+        self.record(
+            &uploaded,
+            hash_clipboard(clipboard),
+            clipboard.clipboard.device_id.clone(),
+        )?;
+
+        Ok(uploaded)
+    }
+
+    /// Skipped if the remote clipboard already has the same content
+    async fn update_clipboard(
+        &self,
+        local_clipboard: &FullClipboardDto,
+        remote_clipboard: &Clippy,
+    ) -> Result<Clippy, SyncError> {
+        let hash = hash_clipboard(local_clipboard);
+
+        if hash.is_some()
+            && remote_clipboard.deleted_at.is_none()
+            && hash == self.known_hash(&remote_clipboard.id)
+        {
+            printlog!(
+                "clipboard {} from {} is unchanged",
+                remote_clipboard.id,
+                uuid_to_datetime(&remote_clipboard.id)
+            );
+            return Ok(remote_clipboard.clone());
+        }
+
+        let updated = self
+            .inner
+            .update_clipboard(local_clipboard, remote_clipboard)
+            .await?;
+        self.record(&updated, hash, local_clipboard.clipboard.device_id.clone())?;
+
+        Ok(updated)
+    }
+
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
+    }
+
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
+    }
+
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
+        self.inner.upsert_settings(&seal_settings(settings)?).await
+    }
+
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError> {
+        open_settings(self.inner.get_settings().await?)
+    }
+
+    async fn get_settings_salt(&self) -> Result<Option<String>, SyncError> {
+        self.inner.get_settings_salt().await
+    }
+
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        self.inner.get_manifest().await
+    }
+
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        self.inner.put_manifest(manifest).await
+    }
+
+    async fn get_devices(&self) -> Result<HashMap<String, DeviceEntry>, SyncError> {
+        if self.state()?.manifest.is_none() {
+            self.fetch_all_clipboards().await?;
+        }
+
+        Ok(self
+            .state()?
+            .manifest
+            .as_ref()
+            .map(|manifest| manifest.devices.clone())
+            .unwrap_or_default())
+    }
+
+    async fn put_device(&self, id: &str, device: &DeviceEntry) -> Result<(), SyncError> {
+        let mut state = self.state()?;
+
+        let devices = HashMap::from([(id.to_string(), device.clone())]);
+        if let Some(manifest) = state.manifest.as_mut() {
+            merge_devices(&mut manifest.devices, &devices);
+        }
+        merge_devices(&mut state.pending_devices, &devices);
+
+        Ok(())
+    }
+
+    async fn get_tombstones(&self) -> Result<HashMap<Uuid, Tombstone>, SyncError> {
+        if self.state()?.manifest.is_none() {
+            self.fetch_all_clipboards().await?;
+        }
+
+        Ok(self
+            .state()?
+            .manifest
+            .as_ref()
+            .map(|manifest| manifest.tombstones.clone())
+            .unwrap_or_default())
+    }
+
+    /// The marked files go along with their tombstones
+    async fn collect_tombstones(&self) -> Result<(), SyncError> {
+        let collectable: Vec<(Uuid, Option<Clippy>)> = {
+            let state = self.state()?;
+            let Some(manifest) = state.manifest.as_ref().filter(|_| !state.read_only) else {
+                return Ok(());
+            };
+
+            collectable_tombstones(manifest, Utc::now().naive_utc())
+                .into_iter()
+                .map(|id| {
+                    let clippy = manifest
+                        .clipboards
+                        .get(&id)
+                        .map(|entry| entry.to_clippy(id));
+                    (id, clippy)
+                })
+                .collect()
+        };
+
+        for (id, clippy) in collectable {
+            printlog!(
+                "(remote) collecting tombstone of clipboard {} from {}",
+                id,
+                uuid_to_datetime(&id)
+            );
+
+            // the tombstone stays until its clipboard is gone
+            if let Some(clippy) = clippy {
+                self.delete_clipboard(&clippy).await?;
+            }
+
+            let mut state = self.state()?;
+            if let Some(manifest) = state.manifest.as_mut() {
+                manifest.tombstones.remove(&id);
+            }
+            state.pending_tombstones.insert(id, None);
+        }
+
+        Ok(())
+    }
+
+    /// Merges the recorded changes into the latest remote manifest, so entries written by
+    /// other devices in the meantime are kept. Providers only replace the manifest as read
+    async fn flush_manifest(&self) -> Result<(), SyncError> {
+        let (pending, pending_devices, pending_tombstones, cached) = {
+            let state = self.state()?;
+            if (state.pending.is_empty()
+                && state.pending_devices.is_empty()
+                && state.pending_tombstones.is_empty())
+                || state.read_only
+            {
+                return Ok(());
+            }
+            (
+                state.pending.clone(),
+                state.pending_devices.clone(),
+                state.pending_tombstones.clone(),
+                state.manifest.clone(),
+            )
+        };
+
+        // a manifest another device wrote since it was read is read and merged again
+        let mut attempt = 1;
+        let manifest = loop {
+            let mut manifest = match self.inner.get_manifest().await? {
+                Some(manifest) if manifest.version == SYNC_MANIFEST_VERSION => manifest,
+                _ => cached.clone().unwrap_or_else(|| SyncManifest {
+                    version: SYNC_MANIFEST_VERSION,
+                    ..Default::default()
+                }),
+            };
+            apply_changes(
+                &mut manifest,
+                &pending,
+                &pending_devices,
+                &pending_tombstones,
+            );
+
+            match self.inner.put_manifest(&manifest).await {
+                Ok(()) => break manifest,
+                Err(SyncError::Conflict(e)) if attempt < SYNC_MANIFEST_WRITE_ATTEMPTS => {
+                    printlog!(
+                        "(remote) manifest was written meanwhile, merging again ({}/{}): {}",
+                        attempt,
+                        SYNC_MANIFEST_WRITE_ATTEMPTS,
+                        e
+                    );
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        printlog!(
+            "(remote) wrote {} changes to manifest revision {}",
+            pending.len() + pending_devices.len() + pending_tombstones.len(),
+            manifest.revision
+        );
+
+        let mut state = self.state()?;
+        // changes recorded while writing stay pending
+        state
+            .pending
+            .retain(|id, entry| pending.get(id) != Some(&*entry));
+        state
+            .pending_devices
+            .retain(|id, device| pending_devices.get(id) != Some(&*device));
+        state
+            .pending_tombstones
+            .retain(|id, tombstone| pending_tombstones.get(id) != Some(&*tombstone));
+        state.manifest = Some(manifest);
+
+        Ok(())
+    }
+
+    async fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated().await
+    }
+}
@@ -0,0 +1,246 @@
+use super::{
+    cleanup_old_clipboards, compare_and_fetch_new_clipboards, create_clipboard_filename,
+    dedupe_clipboards, ignore_not_found, parse_clipboard_info,
+    payload::{decode_payload, delete_chunks, encode_payload, remote_chunks, ChunkStore},
+    star_clipboard, upload_new_clipboards, uuid_to_datetime,
+};
+use crate::prelude::*;
+use chrono::{NaiveDateTime, Utc};
+use common::{
+    constants::{BACKUP_FILE_PREFIX, SYNC_PAYLOAD_VERSION},
+    types::{
+        orm_query::FullClipboardDto,
+        sync::{Clippy, SyncError, SyncManifest, SyncProvider},
+    },
+};
+use migration::async_trait;
+use sea_orm::prelude::Uuid;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+#[derive(Default)]
+struct MemoryStore {
+    /// Clipboards and chunks by filename, named like the files of the local folder provider
+    files: HashMap<String, Vec<u8>>,
+    settings: HashMap<String, Value>,
+    manifest: Option<SyncManifest>,
+}
+
+/// Keeps the remote in memory and logs every write, used for the dry run mode and in tests.
+/// Clones share the store, so several providers act like devices syncing through one remote
+#[derive(Clone, Default)]
+pub struct MemoryProviderImpl {
+    store: Arc<Mutex<MemoryStore>>,
+}
+
+impl MemoryProviderImpl {
+    fn store(&self) -> Result<MutexGuard<'_, MemoryStore>, SyncError> {
+        Ok(self.store.lock().map_err(|e| e.to_string())?)
+    }
+
+    fn write_file(&self, name: &str, content: Vec<u8>) -> Result<(), SyncError> {
+        printlog!("(dry run) writing {} ({} bytes)", name, content.len());
+        self.store()?.files.insert(name.to_string(), content);
+        Ok(())
+    }
+
+    fn read_file(&self, name: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        Ok(self.store()?.files.get(name).cloned())
+    }
+
+    fn remove_file(&self, name: &str) -> Result<(), SyncError> {
+        printlog!("(dry run) deleting {}", name);
+        self.store()?
+            .files
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| SyncError::NotFound(name.to_string()))
+    }
+
+    /// Names of every stored file, chunks included
+    pub fn file_names(&self) -> Vec<String> {
+        self.store()
+            .map(|store| store.files.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait::async_trait]
+impl ChunkStore for MemoryProviderImpl {
+    async fn put_chunk(&self, name: &str, content: Vec<u8>) -> Result<String, SyncError> {
+        self.write_file(name, content)?;
+        Ok(name.to_string())
+    }
+
+    async fn get_chunk(&self, id: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        self.read_file(id)
+    }
+
+    async fn delete_chunk(&self, id: &str) -> Result<(), SyncError> {
+        self.remove_file(id)
+    }
+}
+
+#[async_trait::async_trait]
+impl SyncProvider for MemoryProviderImpl {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        let clipboards = self
+            .file_names()
+            .into_iter()
+            .filter(|name| name.starts_with(&format!("{}_", BACKUP_FILE_PREFIX)))
+            .filter_map(|name| parse_clipboard_info(&name, &name))
+            .collect();
+
+        Ok(dedupe_clipboards(self, clipboards).await)
+    }
+
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        compare_and_fetch_new_clipboards(self, local_clipboards, remote_clipboards).await
+    }
+
+    async fn upload_new_clipboards(
+        &self,
+        new_local_clipboards: &[FullClipboardDto],
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
+    }
+
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
+        let new_name = create_clipboard_filename(
+            &clippy.id,
+            &clippy.star,
+            &clippy.encrypted,
+            &clippy.created_at,
+            Some(Utc::now().naive_utc()),
+            clippy.format,
+            clippy.chunks,
+        );
+
+        {
+            let mut store = self.store()?;
+            let content = store
+                .files
+                .remove(&clippy.provider_id)
+                .ok_or_else(|| SyncError::NotFound(clippy.provider_id.clone()))?;
+            store.files.insert(new_name.clone(), content);
+        }
+
+        printlog!("(dry run) marked clipboard for deletion: {}", clippy.id);
+
+        Ok(parse_clipboard_info(&new_name, &new_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        let chunks = remote_chunks(self, clippy).await;
+        ignore_not_found(self.remove_file(&clippy.provider_id))?;
+        delete_chunks(self, &chunks, &[]).await;
+        Ok(())
+    }
+
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        let content = self
+            .read_file(id)?
+            .ok_or_else(|| SyncError::NotFound(format!("clipboard {}", id)))?;
+
+        decode_payload(self, &content).await
+    }
+
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        let payload = encode_payload(self, clipboard).await?;
+        let file_name = create_clipboard_filename(
+            &clipboard.clipboard.id,
+            &clipboard.clipboard.star,
+            &clipboard.clipboard.encrypted,
+            &clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+
+        printlog!(
+            "(dry run) uploading clipboard: {} from {} star: {} encrypted: {}",
+            clipboard.clipboard.id,
+            uuid_to_datetime(&clipboard.clipboard.id),
+            clipboard.clipboard.star,
+            clipboard.clipboard.encrypted
+        );
+
+        self.write_file(&file_name, payload.content)?;
+
+        Ok(parse_clipboard_info(&file_name, &file_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn update_clipboard(
+        &self,
+        local_clipboard: &FullClipboardDto,
+        remote_clipboard: &Clippy,
+    ) -> Result<Clippy, SyncError> {
+        let stale_chunks = remote_chunks(self, remote_clipboard).await;
+        let payload = encode_payload(self, local_clipboard).await?;
+        let new_name = create_clipboard_filename(
+            &remote_clipboard.id,
+            &local_clipboard.clipboard.star,
+            &local_clipboard.clipboard.encrypted,
+            &local_clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+
+        printlog!(
+            "(dry run) updating clipboard: {} from {} star: {} encrypted: {}",
+            remote_clipboard.id,
+            uuid_to_datetime(&remote_clipboard.id),
+            local_clipboard.clipboard.star,
+            local_clipboard.clipboard.encrypted
+        );
+
+        self.write_file(&new_name, payload.content)?;
+        if new_name != remote_clipboard.provider_id {
+            self.remove_file(&remote_clipboard.provider_id).ok();
+        }
+        delete_chunks(self, &stale_chunks, &payload.chunks).await;
+
+        Ok(parse_clipboard_info(&new_name, &new_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
+    }
+
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
+    }
+
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
+        printlog!("(dry run) uploading {} settings", settings.len());
+        self.store()?.settings = settings.clone();
+        Ok(())
+    }
+
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError> {
+        Ok(self.store()?.settings.clone())
+    }
+
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        Ok(self.store()?.manifest.clone())
+    }
+
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        printlog!("(dry run) writing manifest revision {}", manifest.revision);
+        self.store()?.manifest = Some(manifest.clone());
+        Ok(())
+    }
+
+    async fn is_authenticated(&self) -> bool {
+        true
+    }
+}
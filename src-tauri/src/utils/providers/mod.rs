@@ -1,26 +1,112 @@
+use crate::{
+    prelude::*,
+    service::settings::get_global_settings,
+    tao::global::{get_device_id, try_get_app},
+};
 use chrono::{DateTime, NaiveDateTime};
 use common::{
     constants::{BACKDUP_DATE_FORMAT, BACKUP_FILE_PREFIX},
-    types::sync::Clippy,
+    types::{
+        enums::{ClipboardType, ListenEvent},
+        orm_query::FullClipboardDto,
+        sync::{Clippy, SyncError, SyncProvider},
+        types::Progress,
+    },
 };
+use entity::settings;
 use sea_orm::prelude::Uuid;
+use std::{cmp::Reverse, collections::HashMap};
+use tauri::{Emitter, EventTarget};
 pub mod google_drive;
+pub mod local_folder;
+pub mod manifest;
+pub mod memory;
+pub mod network;
+pub mod payload;
+pub mod retry;
+pub mod s3;
+pub mod server;
+pub mod token_storage;
+pub mod webdav;
+
+#[cfg(test)]
+mod tests;
 
+/// Creation time of a v7 uuid, remote ids of any other version map to the unix epoch
 pub fn uuid_to_datetime(uuid: &Uuid) -> NaiveDateTime {
-    let ts = uuid.get_timestamp().expect("Not a time-based UUID");
-    let (secs, nanos) = ts.to_unix();
-    DateTime::from_timestamp(secs as i64, nanos as u32)
-        .expect("Invalid timestamp")
-        .naive_utc()
+    uuid.get_timestamp()
+        .and_then(|ts| {
+            let (secs, nanos) = ts.to_unix();
+            DateTime::from_timestamp(secs as i64, nanos)
+        })
+        .map(|datetime| datetime.naive_utc())
+        .unwrap_or_default()
+}
+
+/// Whether the sync filters let a clipboard reach the remote. Every type of the clipboard has
+/// to be allowed, its text type must not be excluded and its content must fit the size limit
+pub fn is_syncable(clipboard: &FullClipboardDto, settings: &settings::Model) -> bool {
+    if clipboard.clipboard.sync_excluded {
+        return false;
+    }
+
+    let allowed_types = ClipboardType::from_json_value(&settings.sync_types).unwrap_or_default();
+    let types = ClipboardType::from_json_value(&clipboard.clipboard.types).unwrap_or_default();
+    if !types.iter().all(|t| allowed_types.contains(t)) {
+        return false;
+    }
+
+    let excluded_text_types: Vec<String> =
+        serde_json::from_value(settings.sync_excluded_text_types.clone()).unwrap_or_default();
+    if clipboard
+        .text
+        .as_ref()
+        .is_some_and(|text| excluded_text_types.contains(&text.r#type))
+    {
+        return false;
+    }
+
+    let size = clipboard.text.as_ref().map_or(0, |text| text.data.len())
+        + clipboard.html.as_ref().map_or(0, |html| html.data.len())
+        + clipboard.rtf.as_ref().map_or(0, |rtf| rtf.data.len())
+        + clipboard.image.as_ref().map_or(0, |image| image.data.len())
+        + clipboard
+            .files
+            .iter()
+            .map(|file| file.data.len())
+            .sum::<usize>();
+
+    size <= settings.sync_max_size.max(0) as usize
+}
+
+/// Payload format and parts from a filename extension, `json` is the uncompressed format 1
+fn parse_payload_extension(extension: &str) -> Option<(u32, u32)> {
+    if extension == "json" {
+        return Some((1, 1));
+    }
+
+    let (format, chunks) = match extension.split_once('.') {
+        Some((format, chunks)) => (format, chunks.strip_prefix('c')?.parse().ok()?),
+        None => (extension, 1),
+    };
+
+    Some((format.strip_prefix('v')?.parse().ok()?, chunks))
+}
+
+fn payload_extension(format: u32, chunks: u32) -> String {
+    match (format, chunks) {
+        (1, _) => "json".to_string(),
+        (format, 1) => format!("v{}", format),
+        (format, chunks) => format!("v{}.c{}", format, chunks),
+    }
 }
 
-pub fn parse_clipboard_info(filename: &str, provider_id: &String) -> Option<Clippy> {
-    let [_, uuid, star, encrypted, created_at, deleted_at]: [&str; 6] = filename
-        .trim_end_matches(".json")
-        .split('_')
-        .collect::<Vec<_>>()
-        .try_into()
-        .ok()?;
+pub fn parse_clipboard_info(filename: &str, provider_id: &str) -> Option<Clippy> {
+    let (name, extension) = filename.split_once('.')?;
+    let (format, chunks) = parse_payload_extension(extension)?;
+
+    let [_, uuid, star, encrypted, created_at, deleted_at]: [&str; 6] =
+        name.split('_').collect::<Vec<_>>().try_into().ok()?;
 
     let id = Uuid::parse_str(uuid).ok()?;
     let starred = star.parse().ok()?;
@@ -38,19 +124,25 @@ pub fn parse_clipboard_info(filename: &str, provider_id: &String) -> Option<Clip
         encrypted,
         created_at,
         deleted_at,
-        provider_id: provider_id.clone(),
+        provider_id: provider_id.to_string(),
+        format,
+        chunks,
     })
 }
 
+/// Older versions skip files with an extension other than `json`, so they never read a payload
+/// format they do not know
 pub fn create_clipboard_filename(
     id: &Uuid,
     starred: &bool,
     encrypted: &bool,
     created_at: &NaiveDateTime,
     deleted_at: Option<NaiveDateTime>,
+    format: u32,
+    chunks: u32,
 ) -> String {
     format!(
-        "{}_{}_{}_{}_{}_{}.json",
+        "{}_{}_{}_{}_{}_{}.{}",
         BACKUP_FILE_PREFIX,
         id,
         starred,
@@ -58,6 +150,237 @@ pub fn create_clipboard_filename(
         created_at.format(BACKDUP_DATE_FORMAT),
         deleted_at
             .map(|date| date.format(BACKDUP_DATE_FORMAT).to_string())
-            .unwrap_or_else(|| "None".to_string())
+            .unwrap_or_else(|| "None".to_string()),
+        payload_extension(format, chunks)
     )
 }
+
+/// Deleting a file that is gone already succeeds, e.g. when a retry follows a lost response
+pub fn ignore_not_found(result: Result<(), SyncError>) -> Result<(), SyncError> {
+    match result {
+        Err(SyncError::NotFound(_)) => Ok(()),
+        result => result,
+    }
+}
+
+/// Removes duplicate remote clipboards and sorts the rest newest first
+pub async fn dedupe_clipboards<P: SyncProvider + ?Sized>(
+    provider: &P,
+    remotes: Vec<Clippy>,
+) -> Vec<Clippy> {
+    let mut clipboards = Vec::new();
+    let mut seen_ids = std::collections::HashSet::new();
+
+    for remote in remotes {
+        // Only add if we haven't seen this ID before
+        if seen_ids.insert(remote.id) {
+            clipboards.push(remote);
+        } else {
+            printlog!(
+                "Found duplicate clipboard: {} from {}, deleting duplicate",
+                remote.id,
+                uuid_to_datetime(&remote.id)
+            );
+            // Delete the duplicate file, a failed attempt is repeated by the next listing
+            if let Err(e) = provider.delete_clipboard(&remote).await {
+                printlog!("failed to delete duplicate clipboard {}: {}", remote.id, e);
+            }
+        }
+    }
+
+    // Sort by created_at
+    clipboards.sort_by_key(|clippy| Reverse(clippy.id));
+
+    if !clipboards.is_empty() {
+        let newest = &clipboards[0];
+        let oldest = &clipboards[clipboards.len() - 1];
+        printlog!(
+            "(remote) found {} clipboards from {} to {}",
+            clipboards.len(),
+            uuid_to_datetime(&oldest.id),
+            uuid_to_datetime(&newest.id)
+        );
+    } else {
+        printlog!("(remote) found no clipboards");
+    }
+
+    clipboards
+}
+
+pub async fn compare_and_fetch_new_clipboards<P: SyncProvider + ?Sized>(
+    provider: &P,
+    local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+    remote_clipboards: &[Clippy],
+) -> Result<Vec<FullClipboardDto>, SyncError> {
+    let mut new_clipboards = Vec::new();
+
+    let total = remote_clipboards.len();
+    for (index, file) in remote_clipboards.iter().enumerate() {
+        // Skip if the clipboard is marked for deletion
+        if file.deleted_at.is_some() {
+            continue;
+        }
+
+        if let Some((local_star, _local_timestamp)) = local_clipboards.get(&file.id) {
+            // star status is different
+            if local_star == &file.star {
+                continue;
+            }
+        }
+
+        printlog!(
+            "downloading clipboard: {} from {} star: {} encrypted: {}",
+            file.id,
+            uuid_to_datetime(&file.id),
+            file.star,
+            file.encrypted
+        );
+
+        if let Some(app) = try_get_app() {
+            app.emit_to(
+                EventTarget::any(),
+                ListenEvent::Progress.to_string().as_str(),
+                Progress {
+                    label: "SETTINGS.ENCRYPT.DOWNLOADING_REMOTE_CLIPBOARDS".to_string(),
+                    total,
+                    current: index + 1,
+                },
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        new_clipboards.push(provider.download_by_id(&file.provider_id).await?);
+    }
+
+    Ok(new_clipboards)
+}
+
+pub async fn upload_new_clipboards<P: SyncProvider + ?Sized>(
+    provider: &P,
+    new_local_clipboards: &[FullClipboardDto],
+    remote_clipboards: &[Clippy],
+) -> Result<Vec<Clippy>, SyncError> {
+    let mut new_clipboards = Vec::new();
+    let settings = get_global_settings();
+
+    // Skip clipboards that were recently deleted
+    let tombstones = provider.get_tombstones().await?;
+    let recently_deleted: Vec<_> = remote_clipboards
+        .iter()
+        .filter(|c| c.deleted_at.is_some())
+        .map(|c| c.id)
+        .chain(tombstones.into_keys())
+        .collect();
+
+    for clipboard in new_local_clipboards {
+        if remote_clipboards
+            .iter()
+            .any(|clip| clip.id == clipboard.clipboard.id)
+            || recently_deleted.contains(&clipboard.clipboard.id)
+        {
+            continue;
+        }
+
+        if !is_syncable(clipboard, &settings) {
+            printlog!(
+                "clipboard {} from {} is excluded by the sync filters",
+                clipboard.clipboard.id,
+                uuid_to_datetime(&clipboard.clipboard.id)
+            );
+            continue;
+        }
+
+        new_clipboards.push(provider.upload_clipboard(clipboard).await?);
+    }
+
+    Ok(new_clipboards)
+}
+
+/// Remote clipboards beyond the sync limit, oldest first. Starred clipboards are never expired
+/// and marked ones are removed with their tombstones
+pub fn expired_clipboards(remote_clipboards: &[Clippy], sync_limit: usize) -> Vec<&Clippy> {
+    let mut clipboards: Vec<_> = remote_clipboards
+        .iter()
+        .filter(|clip| !clip.star && clip.deleted_at.is_none())
+        .collect();
+
+    // Sort by creation date
+    clipboards.sort_by_key(|clippy| clippy.id);
+
+    let expired = clipboards.len().saturating_sub(sync_limit);
+    clipboards.truncate(expired);
+    clipboards
+}
+
+pub async fn cleanup_old_clipboards<P: SyncProvider + ?Sized>(
+    provider: &P,
+    remote_clipboards: &[Clippy],
+) -> Result<(), SyncError> {
+    let sync_limit = get_global_settings().sync_limit as usize;
+
+    for clippy in expired_clipboards(remote_clipboards, sync_limit) {
+        printlog!(
+            "deleting clipboard: {} from {}",
+            clippy.id,
+            uuid_to_datetime(&clippy.id)
+        );
+        provider.delete_clipboard(clippy).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn star_clipboard<P: SyncProvider + ?Sized>(
+    provider: &P,
+    clippy: &FullClipboardDto,
+) -> Result<(), SyncError> {
+    let clipboards = provider.fetch_all_clipboards().await?;
+
+    let remote_clipboards = clipboards
+        .iter()
+        .find(|clip| clip.id == clippy.clipboard.id);
+
+    // a clipboard excluded after it was uploaded is removed from the remote
+    if !is_syncable(clippy, &get_global_settings()) {
+        printlog!(
+            "clipboard {} from {} is excluded by the sync filters",
+            clippy.clipboard.id,
+            uuid_to_datetime(&clippy.clipboard.id)
+        );
+
+        if let Some(remote_clipboard) = remote_clipboards.filter(|clip| clip.deleted_at.is_none()) {
+            provider.mark_for_deletion(remote_clipboard).await?;
+        }
+        return Ok(());
+    }
+
+    // only the device that removed it, by excluding it from sync, brings a clipboard back
+    let tombstone = provider
+        .get_tombstones()
+        .await?
+        .remove(&clippy.clipboard.id);
+    if tombstone.is_some_and(|tombstone| tombstone.deleted_by.as_deref() != Some(get_device_id())) {
+        printlog!(
+            "clipboard {} from {} was deleted on another device",
+            clippy.clipboard.id,
+            uuid_to_datetime(&clippy.clipboard.id)
+        );
+        return Ok(());
+    }
+
+    if let Some(remote_clipboard) = remote_clipboards {
+        printlog!(
+            "starring clipboard: {} from {} star: {} encrypted: {}",
+            remote_clipboard.id,
+            uuid_to_datetime(&remote_clipboard.id),
+            clippy.clipboard.star,
+            clippy.clipboard.encrypted
+        );
+
+        provider.update_clipboard(clippy, remote_clipboard).await?;
+    } else {
+        provider.upload_clipboard(clippy).await?;
+    }
+
+    Ok(())
+}
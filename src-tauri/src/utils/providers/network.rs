@@ -0,0 +1,302 @@
+use crate::service::settings::get_global_settings;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::types::sync::SyncError;
+use entity::settings;
+use google_drive3::{
+    hyper::Uri,
+    hyper_rustls::{HttpsConnector, HttpsConnectorBuilder},
+    hyper_util::{client::legacy::connect::HttpConnector, rt::TokioIo},
+};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer},
+    RootCertStore,
+};
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tower_service::Service;
+
+/// Connector of the Google Drive client, TLS over a connection that may be tunneled
+pub type DriveConnector = HttpsConnector<ProxyConnector>;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Proxy and certificates every HTTP based sync provider connects with
+#[derive(Debug, Clone, Default)]
+pub struct NetworkSettings {
+    /// `http://[user:password@]host:port` of the proxy
+    pub proxy_url: Option<String>,
+    /// Comma separated hosts reached without the proxy, subdomains included
+    pub no_proxy: Option<String>,
+    /// PEM file with certificates trusted on top of the system ones
+    pub ca_bundle: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+impl NetworkSettings {
+    pub fn new(
+        proxy_url: Option<String>,
+        no_proxy: Option<String>,
+        ca_bundle: Option<String>,
+    ) -> Self {
+        Self {
+            proxy_url: non_empty(proxy_url),
+            no_proxy: non_empty(no_proxy),
+            ca_bundle: non_empty(ca_bundle),
+        }
+    }
+}
+
+impl From<&settings::Model> for NetworkSettings {
+    fn from(settings: &settings::Model) -> Self {
+        Self::new(
+            settings.sync_proxy_url.clone(),
+            settings.sync_no_proxy.clone(),
+            settings.sync_ca_bundle.clone(),
+        )
+    }
+}
+
+pub fn network_settings() -> NetworkSettings {
+    NetworkSettings::from(&get_global_settings())
+}
+
+/// Certificates of the configured CA bundle, a bundle without any is rejected
+pub fn load_ca_bundle(
+    network: &NetworkSettings,
+) -> Result<Vec<CertificateDer<'static>>, SyncError> {
+    let Some(path) = network.ca_bundle.as_deref() else {
+        return Ok(Vec::new());
+    };
+
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| SyncError::Other(format!("invalid CA bundle {}: {}", path, e)))?;
+    if certs.is_empty() {
+        return Err(SyncError::Other(format!("no certificates in {}", path)));
+    }
+
+    Ok(certs)
+}
+
+/// Client of the WebDAV, S3 and server providers
+pub fn build_http_client(network: &NetworkSettings) -> Result<reqwest::Client, SyncError> {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(proxy_url) = &network.proxy_url {
+        // reqwest reads the credentials of the url itself
+        parse_proxy(proxy_url)?;
+        let proxy = reqwest::Proxy::all(proxy_url.as_str())?.no_proxy(
+            network
+                .no_proxy
+                .as_deref()
+                .and_then(reqwest::NoProxy::from_string),
+        );
+        builder = builder.proxy(proxy);
+    }
+
+    for cert in load_ca_bundle(network)? {
+        builder = builder.add_root_certificate(reqwest::Certificate::from_der(&cert)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Client of the current settings
+pub fn http_client() -> Result<reqwest::Client, SyncError> {
+    build_http_client(&network_settings())
+}
+
+/// Connector of the Google Drive client, trusting the system roots and the CA bundle
+pub fn drive_connector(network: &NetworkSettings) -> Result<DriveConnector, SyncError> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    for cert in load_ca_bundle(network)? {
+        roots
+            .add(cert)
+            .map_err(|e| SyncError::Other(format!("invalid CA certificate: {}", e)))?;
+    }
+
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    Ok(HttpsConnectorBuilder::new()
+        .with_tls_config(config)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(ProxyConnector::new(network)?))
+}
+
+/// `Basic` authorization of the credentials in a proxy url
+fn proxy_authorization(proxy_url: &str) -> Option<String> {
+    let authority = proxy_url.split_once("://")?.1.split('/').next()?;
+    let (userinfo, _) = authority.rsplit_once('@')?;
+    let userinfo = urlencoding::decode(userinfo).ok()?;
+
+    Some(format!("Basic {}", STANDARD.encode(userinfo.as_bytes())))
+}
+
+#[derive(Clone)]
+struct Proxy {
+    uri: Uri,
+    authorization: Option<String>,
+}
+
+fn parse_proxy(proxy_url: &str) -> Result<Proxy, SyncError> {
+    let invalid = || SyncError::Other(format!("invalid proxy url {}", proxy_url));
+
+    let uri: Uri = proxy_url.parse().map_err(|_| invalid())?;
+    if uri.scheme_str() != Some("http") {
+        return Err(invalid());
+    }
+    let authority = uri.authority().ok_or_else(invalid)?;
+    let host = authority.host();
+    if host.is_empty() {
+        return Err(invalid());
+    }
+
+    let uri = Uri::builder()
+        .scheme("http")
+        .authority(format!("{}:{}", host, authority.port_u16().unwrap_or(80)))
+        .path_and_query("/")
+        .build()
+        .map_err(|_| invalid())?;
+
+    Ok(Proxy {
+        uri,
+        authorization: proxy_authorization(proxy_url),
+    })
+}
+
+/// Hosts of the no-proxy list, lowercased and without leading dots or wildcards
+fn parse_no_proxy(no_proxy: Option<&str>) -> Vec<String> {
+    no_proxy
+        .unwrap_or_default()
+        .split([',', ' ', '\n'])
+        .map(|host| {
+            host.trim()
+                .trim_start_matches("*.")
+                .trim_start_matches('.')
+                .to_lowercase()
+        })
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+/// Whether `host` is reached directly, an entry matches the host and its subdomains
+pub fn bypasses_proxy(no_proxy: &[String], host: &str) -> bool {
+    let host = host.to_lowercase();
+
+    no_proxy
+        .iter()
+        .any(|entry| entry == "*" || host == *entry || host.ends_with(&format!(".{}", entry)))
+}
+
+/// Opens plain connections, through an HTTP CONNECT tunnel of the proxy when one is set.
+/// hyper has no proxy support of its own, so the Drive client goes through this one
+#[derive(Clone)]
+pub struct ProxyConnector {
+    http: HttpConnector,
+    proxy: Option<Proxy>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxyConnector {
+    pub fn new(network: &NetworkSettings) -> Result<Self, SyncError> {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+
+        Ok(Self {
+            http,
+            proxy: network.proxy_url.as_deref().map(parse_proxy).transpose()?,
+            no_proxy: parse_no_proxy(network.no_proxy.as_deref()),
+        })
+    }
+}
+
+/// Asks the proxy for a tunnel to the host of `uri`, the stream is left right after the
+/// response head so the TLS handshake starts on a clean connection
+async fn tunnel(stream: &mut TcpStream, uri: &Uri, authorization: Option<&str>) -> io::Result<()> {
+    let host = uri
+        .host()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?;
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("http") {
+            80
+        } else {
+            443
+        });
+
+    let mut request = format!(
+        "CONNECT {}:{} HTTP/1.1\r\nHost: {}:{}\r\n",
+        host, port, host, port
+    );
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // read byte by byte, anything after the head already belongs to the tunnel
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 8192 {
+            return Err(io::Error::other("proxy response head too large"));
+        }
+        head.push(stream.read_u8().await?);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let status_line = head.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(io::Error::other(format!(
+            "proxy refused the tunnel: {}",
+            status_line
+        )));
+    }
+
+    Ok(())
+}
+
+impl Service<Uri> for ProxyConnector {
+    type Response = TokioIo<TcpStream>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let mut http = self.http.clone();
+        let proxy = self.proxy.clone().filter(|_| {
+            uri.host()
+                .is_some_and(|host| !bypasses_proxy(&self.no_proxy, host))
+        });
+
+        Box::pin(async move {
+            let Some(proxy) = proxy else {
+                return Ok(http.call(uri).await?);
+            };
+
+            let mut stream = http.call(proxy.uri).await?;
+            tunnel(stream.inner_mut(), &uri, proxy.authorization.as_deref()).await?;
+
+            Ok(stream)
+        })
+    }
+}
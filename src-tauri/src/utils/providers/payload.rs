@@ -0,0 +1,220 @@
+use crate::service::{
+    cipher::{get_sync_key, get_sync_salt, open_sync_data, seal_sync_data},
+    decrypt::decrypt_data_with_key,
+    encrypt::encrypt_data_with_key,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    constants::{
+        BACKUP_CHUNK_PREFIX, SYNC_CHUNK_SIZE, SYNC_COMPRESSION_LEVEL, SYNC_SEALED_SETTINGS_KEY,
+        SYNC_SETTINGS_SALT_KEY,
+    },
+    types::{
+        cipher::EncryptionError,
+        orm_query::FullClipboardDto,
+        sync::{Clippy, SyncError},
+    },
+};
+use migration::async_trait;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use zeroize::Zeroizing;
+
+/// Every zstd frame starts with these bytes, a format 1 payload is plain JSON
+static ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Starts a payload that was split into chunks, followed by its `ChunkIndex` as JSON.
+/// Must not start with `ENCRYPTION_MAGIC_STRING`, which marks a sealed payload
+static CHUNK_INDEX_MAGIC: &[u8] = b"chunk-index\n";
+
+/// Where a provider keeps the chunks of a split payload
+#[async_trait::async_trait]
+pub trait ChunkStore: Send + Sync {
+    /// Writes a chunk and returns the id it is read back with
+    async fn put_chunk(&self, name: &str, content: Vec<u8>) -> Result<String, SyncError>;
+
+    async fn get_chunk(&self, id: &str) -> Result<Option<Vec<u8>>, SyncError>;
+
+    async fn delete_chunk(&self, id: &str) -> Result<(), SyncError>;
+}
+
+/// Stored in place of a compressed clipboard larger than `SYNC_CHUNK_SIZE`
+#[derive(Serialize, Deserialize)]
+struct ChunkIndex {
+    /// Chunk ids in order, joined they are the compressed clipboard
+    chunks: Vec<String>,
+}
+
+/// A clipboard encoded for upload
+pub struct Payload {
+    /// Stored under the clipboard filename, the compressed clipboard or its chunk index
+    pub content: Vec<u8>,
+    /// Chunks that were written already, empty if the clipboard fits into one object
+    pub chunks: Vec<String>,
+}
+
+impl Payload {
+    /// Parts the clipboard is stored in, as noted in its filename
+    pub fn parts(&self) -> u32 {
+        self.chunks.len().max(1) as u32
+    }
+}
+
+/// Compresses a clipboard and seals it with the sync passphrase if one is set,
+/// a payload above `SYNC_CHUNK_SIZE` is written to `store` in chunks
+pub async fn encode_payload<S: ChunkStore + ?Sized>(
+    store: &S,
+    clipboard: &FullClipboardDto,
+) -> Result<Payload, SyncError> {
+    let json = serde_json::to_vec(clipboard)?;
+    let compressed = seal_sync_data(zstd::encode_all(json.as_slice(), SYNC_COMPRESSION_LEVEL)?)?;
+
+    if compressed.len() <= SYNC_CHUNK_SIZE {
+        return Ok(Payload {
+            content: compressed,
+            chunks: Vec::new(),
+        });
+    }
+
+    // named by content, so a retried upload writes the same chunks again
+    let hash: String = digest::digest(&digest::SHA256, &compressed).as_ref()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let mut chunks = Vec::new();
+    for (index, chunk) in compressed.chunks(SYNC_CHUNK_SIZE).enumerate() {
+        let name = format!(
+            "{}_{}_{}_{}",
+            BACKUP_CHUNK_PREFIX, clipboard.clipboard.id, hash, index
+        );
+        chunks.push(store.put_chunk(&name, chunk.to_vec()).await?);
+    }
+
+    let mut content = CHUNK_INDEX_MAGIC.to_vec();
+    content.extend(serde_json::to_vec(&ChunkIndex {
+        chunks: chunks.clone(),
+    })?);
+
+    Ok(Payload { content, chunks })
+}
+
+/// Reads a payload of any format, chunks are fetched from `store`
+pub async fn decode_payload<S: ChunkStore + ?Sized>(
+    store: &S,
+    content: &[u8],
+) -> Result<FullClipboardDto, SyncError> {
+    let compressed = match content.strip_prefix(CHUNK_INDEX_MAGIC) {
+        Some(index) => {
+            let index: ChunkIndex = serde_json::from_slice(index)?;
+            let mut compressed = Vec::new();
+            for id in &index.chunks {
+                let chunk = store
+                    .get_chunk(id)
+                    .await?
+                    .ok_or_else(|| SyncError::NotFound(format!("chunk {}", id)))?;
+                compressed.extend(chunk);
+            }
+            compressed
+        }
+        None => content.to_vec(),
+    };
+
+    let compressed = open_sync_data(compressed)?;
+    if !compressed.starts_with(&ZSTD_MAGIC) {
+        return Ok(serde_json::from_slice(&compressed)?);
+    }
+
+    let json = zstd::decode_all(compressed.as_slice())
+        .map_err(|e| SyncError::CorruptPayload(e.to_string()))?;
+
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Chunks a stored payload refers to, empty unless it was split
+pub fn chunk_ids(content: &[u8]) -> Vec<String> {
+    content
+        .strip_prefix(CHUNK_INDEX_MAGIC)
+        .and_then(|index| serde_json::from_slice::<ChunkIndex>(index).ok())
+        .map(|index| index.chunks)
+        .unwrap_or_default()
+}
+
+/// Chunks of a remote clipboard, only a split payload is read to find them
+pub async fn remote_chunks<S: ChunkStore + ?Sized>(store: &S, clippy: &Clippy) -> Vec<String> {
+    if clippy.chunks <= 1 {
+        return Vec::new();
+    }
+
+    match store.get_chunk(&clippy.provider_id).await {
+        Ok(Some(content)) => chunk_ids(&content),
+        _ => Vec::new(),
+    }
+}
+
+/// Removes chunks no payload refers to anymore, an identical upload may still use some of them
+pub async fn delete_chunks<S: ChunkStore + ?Sized>(store: &S, chunks: &[String], keep: &[String]) {
+    for id in chunks.iter().filter(|id| !keep.contains(id)) {
+        store.delete_chunk(id).await.ok();
+    }
+}
+
+/// Seals the settings with the sync passphrase, they are stored under a single key
+/// so the file stays a JSON object. Without a passphrase they are returned unchanged
+pub fn seal_settings(
+    settings: &HashMap<String, Value>,
+) -> Result<HashMap<String, Value>, SyncError> {
+    match (get_sync_key()?, get_sync_salt()?) {
+        (Some(key), Some(salt)) => seal_settings_with_key(settings, &key, &salt),
+        _ => Ok(settings.clone()),
+    }
+}
+
+/// Seals the settings with `key`, its `salt` is stored unsealed next to them so other
+/// devices can derive the key from the passphrase
+pub fn seal_settings_with_key(
+    settings: &HashMap<String, Value>,
+    key: &[u8; 32],
+    salt: &str,
+) -> Result<HashMap<String, Value>, SyncError> {
+    let sealed = encrypt_data_with_key(&serde_json::to_vec(settings)?, key)?;
+
+    Ok(HashMap::from([
+        (
+            SYNC_SEALED_SETTINGS_KEY.to_string(),
+            Value::String(STANDARD.encode(sealed)),
+        ),
+        (
+            SYNC_SETTINGS_SALT_KEY.to_string(),
+            Value::String(salt.to_string()),
+        ),
+    ]))
+}
+
+/// Opens settings sealed by `seal_settings`, unsealed settings are returned unchanged
+pub fn open_settings(
+    settings: HashMap<String, Value>,
+) -> Result<HashMap<String, Value>, SyncError> {
+    open_settings_with_key(settings, get_sync_key()?.as_deref())
+}
+
+/// Opens settings sealed by `seal_settings_with_key`, sealed settings need the `key`
+pub fn open_settings_with_key(
+    mut settings: HashMap<String, Value>,
+    key: Option<&[u8; 32]>,
+) -> Result<HashMap<String, Value>, SyncError> {
+    let Some(Value::String(sealed)) = settings.remove(SYNC_SEALED_SETTINGS_KEY) else {
+        return Ok(settings);
+    };
+
+    let sealed = STANDARD
+        .decode(sealed)
+        .map_err(|e| SyncError::CorruptPayload(e.to_string()))?;
+    let key = key.ok_or(EncryptionError::SyncPassphraseRequired)?;
+
+    let opened = Zeroizing::new(decrypt_data_with_key(&sealed, key)?);
+
+    Ok(serde_json::from_slice(&opened)?)
+}
@@ -0,0 +1,161 @@
+use super::{cleanup_old_clipboards, star_clipboard, upload_new_clipboards};
+use crate::prelude::*;
+use chrono::NaiveDateTime;
+use common::{
+    constants::{SYNC_RETRY_ATTEMPTS, SYNC_RETRY_DELAY_MS},
+    types::{
+        orm_query::FullClipboardDto,
+        sync::{Clippy, DeviceEntry, SyncError, SyncManifest, SyncProvider, Tombstone},
+    },
+};
+use migration::async_trait;
+use sea_orm::prelude::Uuid;
+use serde_json::Value;
+use std::{collections::HashMap, future::Future, time::Duration};
+
+/// Retries transient provider errors (network, rate limits) with exponential backoff.
+/// Composite operations run through the shared helpers, so every single request is retried
+/// on its own instead of repeating uploads that went through. Conflicts need a fresh read
+/// and are returned right away
+pub struct RetryProvider<P> {
+    inner: P,
+}
+
+pub fn with_retry<P: SyncProvider>(provider: P) -> RetryProvider<P> {
+    RetryProvider { inner: provider }
+}
+
+async fn retry<T, F, Fut>(name: &str, operation: F) -> Result<T, SyncError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, SyncError>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match operation().await {
+            Err(e) if e.is_retryable() && attempt < SYNC_RETRY_ATTEMPTS => {
+                let delay = Duration::from_millis(SYNC_RETRY_DELAY_MS << (attempt - 1));
+                printlog!(
+                    "(remote) {} failed, retrying in {:?} ({}/{}): {}",
+                    name,
+                    delay,
+                    attempt,
+                    SYNC_RETRY_ATTEMPTS,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: SyncProvider> SyncProvider for RetryProvider<P> {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        retry("listing", || self.inner.fetch_all_clipboards()).await
+    }
+
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        // only reads, retrying all of it is safe and keeps provider specific downloads
+        retry("download", || {
+            self.inner
+                .compare_and_fetch_new_clipboards(local_clipboards, remote_clipboards)
+        })
+        .await
+    }
+
+    async fn upload_new_clipboards(
+        &self,
+        new_local_clipboards: &[FullClipboardDto],
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
+    }
+
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
+        retry("deletion", || self.inner.mark_for_deletion(clippy)).await
+    }
+
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        retry("deletion", || self.inner.delete_clipboard(clippy)).await
+    }
+
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        retry("download", || self.inner.download_by_id(id)).await
+    }
+
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        retry("upload", || self.inner.upload_clipboard(clipboard)).await
+    }
+
+    async fn update_clipboard(
+        &self,
+        local_clipboard: &FullClipboardDto,
+        remote_clipboard: &Clippy,
+    ) -> Result<Clippy, SyncError> {
+        retry("update", || {
+            self.inner
+                .update_clipboard(local_clipboard, remote_clipboard)
+        })
+        .await
+    }
+
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
+    }
+
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
+    }
+
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
+        retry("settings upload", || self.inner.upsert_settings(settings)).await
+    }
+
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError> {
+        retry("settings download", || self.inner.get_settings()).await
+    }
+
+    async fn get_settings_salt(&self) -> Result<Option<String>, SyncError> {
+        retry("settings salt download", || self.inner.get_settings_salt()).await
+    }
+
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        retry("manifest download", || self.inner.get_manifest()).await
+    }
+
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        retry("manifest upload", || self.inner.put_manifest(manifest)).await
+    }
+
+    async fn get_devices(&self) -> Result<HashMap<String, DeviceEntry>, SyncError> {
+        retry("devices download", || self.inner.get_devices()).await
+    }
+
+    async fn put_device(&self, id: &str, device: &DeviceEntry) -> Result<(), SyncError> {
+        retry("device upload", || self.inner.put_device(id, device)).await
+    }
+
+    async fn get_tombstones(&self) -> Result<HashMap<Uuid, Tombstone>, SyncError> {
+        retry("tombstones download", || self.inner.get_tombstones()).await
+    }
+
+    async fn collect_tombstones(&self) -> Result<(), SyncError> {
+        retry("tombstones cleanup", || self.inner.collect_tombstones()).await
+    }
+
+    async fn flush_manifest(&self) -> Result<(), SyncError> {
+        retry("manifest flush", || self.inner.flush_manifest()).await
+    }
+
+    async fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated().await
+    }
+}
@@ -0,0 +1,695 @@
+use super::{
+    cleanup_old_clipboards, compare_and_fetch_new_clipboards, create_clipboard_filename,
+    dedupe_clipboards, ignore_not_found,
+    network::http_client,
+    parse_clipboard_info,
+    payload::{decode_payload, delete_chunks, encode_payload, remote_chunks, ChunkStore},
+    star_clipboard,
+    token_storage::write_private,
+    upload_new_clipboards, uuid_to_datetime,
+};
+use crate::{prelude::*, tao::config::get_data_path};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use common::{
+    constants::{
+        BACKUP_FILE_PREFIX, BACKUP_SETTINGS_PREFIX, S3_CREDENTIALS_NAME, SYNC_MANIFEST_NAME,
+        SYNC_PAYLOAD_VERSION,
+    },
+    types::{
+        orm_query::FullClipboardDto,
+        sync::{Clippy, S3Credentials, SyncError, SyncManifest, SyncProvider},
+        types::CommandError,
+    },
+};
+use migration::async_trait;
+use regex::Regex;
+use reqwest::{Method, Response, StatusCode, Url};
+use ring::{digest, hmac};
+use sea_orm::prelude::Uuid;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
+
+static CONTENTS_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<Contents>.*?<Key>([^<]*)</Key>.*?<ETag>([^<]*)</ETag>.*?</Contents>")
+        .expect("Invalid contents regex")
+});
+
+static CONTINUATION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<NextContinuationToken>([^<]*)</NextContinuationToken>")
+        .expect("Invalid continuation regex")
+});
+
+/// Stores clipboards as objects in an S3 compatible bucket (AWS, MinIO, Garage, ...).
+/// Object keys use the same names as on Google Drive, the key is the provider id
+pub struct S3ProviderImpl {
+    client: reqwest::Client,
+    credentials: S3Credentials,
+    /// ETags of the objects as last seen, updates only apply to the version that was synced
+    etags: Mutex<HashMap<String, String>>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Signs a request with AWS Signature Version 4, returns the canonical request and the
+/// `Authorization` header. `headers` are lowercase and sorted, `host` and `x-amz-date` included
+pub fn sign_request(
+    credentials: &S3Credentials,
+    method: &str,
+    path: &str,
+    canonical_query: &str,
+    headers: &[(String, String)],
+    payload_hash: &str,
+    now: DateTime<Utc>,
+) -> (String, String) {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v))
+        .collect();
+    let signed_header_names = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method, path, canonical_query, canonical_headers, signed_header_names, payload_hash
+    );
+
+    let scope = format!("{}/{}/s3/aws4_request", date, credentials.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+    );
+
+    let signing_key = [
+        date.as_str(),
+        credentials.region.as_str(),
+        "s3",
+        "aws4_request",
+    ]
+    .iter()
+    .fold(
+        format!("AWS4{}", credentials.secret_key).into_bytes(),
+        |key, part| {
+            hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), part.as_bytes())
+                .as_ref()
+                .to_vec()
+        },
+    );
+    let signature = hex(hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, &signing_key),
+        string_to_sign.as_bytes(),
+    )
+    .as_ref());
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key, scope, signed_header_names, signature
+    );
+
+    (canonical_request, authorization)
+}
+
+impl S3ProviderImpl {
+    pub async fn new() -> Result<Self, CommandError> {
+        let credentials = Self::load_credentials()
+            .ok_or_else(|| CommandError::new("MAIN.ERROR.S3_NOT_CONFIGURED"))?;
+
+        Self::from_credentials(credentials)
+    }
+
+    pub fn from_credentials(credentials: S3Credentials) -> Result<Self, CommandError> {
+        Ok(Self {
+            client: http_client()?,
+            credentials,
+            etags: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn credentials_path() -> PathBuf {
+        std::path::Path::new(&get_data_path().config_path).join(S3_CREDENTIALS_NAME)
+    }
+
+    pub fn load_credentials() -> Option<S3Credentials> {
+        let json = std::fs::read_to_string(Self::credentials_path()).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Holds the secret key, so only the current user may read it
+    pub fn save_credentials(credentials: &S3Credentials) -> Result<(), CommandError> {
+        write_private(
+            &Self::credentials_path(),
+            serde_json::to_string(credentials)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_credentials() {
+        std::fs::remove_file(Self::credentials_path()).ok();
+    }
+
+    /// Key prefix with a trailing slash, or empty for the bucket root
+    fn prefix(&self) -> String {
+        match self.credentials.prefix.trim_matches('/') {
+            "" => String::new(),
+            prefix => format!("{}/", prefix),
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{}", self.prefix(), name)
+    }
+
+    fn cached_etag(&self, key: &str) -> Option<String> {
+        self.etags.lock().ok()?.get(key).cloned()
+    }
+
+    fn cache_etag(&self, key: &str, response: &Response) {
+        let etag = response.headers().get("ETag").and_then(|v| v.to_str().ok());
+
+        if let (Ok(mut etags), Some(etag)) = (self.etags.lock(), etag) {
+            etags.insert(key.to_string(), etag.to_string());
+        }
+    }
+
+    /// Sends a request signed with AWS Signature Version 4, objects are addressed path style
+    /// (`endpoint/bucket/key`) which every S3 compatible server supports
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, String)],
+        body: Vec<u8>,
+    ) -> Result<Response, SyncError> {
+        let path = std::iter::once(self.credentials.bucket.as_str())
+            .chain(key.split('/'))
+            .map(|segment| urlencoding::encode(segment).into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(k, v)| {
+                (
+                    urlencoding::encode(k).into_owned(),
+                    urlencoding::encode(v).into_owned(),
+                )
+            })
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut url = Url::parse(&format!(
+            "{}/{}",
+            self.credentials.endpoint.trim_end_matches('/'),
+            path
+        ))
+        .map_err(|e| SyncError::Other(format!("Invalid S3 endpoint: {}", e)))?;
+        if !canonical_query.is_empty() {
+            url.set_query(Some(&canonical_query));
+        }
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            _ => return Err("Invalid S3 endpoint".into()),
+        };
+
+        let now = Utc::now();
+        let payload_hash = hex(digest::digest(&digest::SHA256, &body).as_ref());
+
+        let mut signed_headers: Vec<(String, String)> = vec![
+            ("host".to_string(), host),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            (
+                "x-amz-date".to_string(),
+                now.format("%Y%m%dT%H%M%SZ").to_string(),
+            ),
+        ];
+        signed_headers.extend(
+            headers
+                .iter()
+                .map(|(k, v)| (k.to_lowercase(), v.trim().to_string())),
+        );
+        signed_headers.sort();
+
+        let (_, authorization) = sign_request(
+            &self.credentials,
+            method.as_str(),
+            url.path(),
+            &canonical_query,
+            &signed_headers,
+            &payload_hash,
+            now,
+        );
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("Authorization", authorization);
+        for (name, value) in signed_headers.iter().filter(|(k, _)| k != "host") {
+            request = request.header(name, value);
+        }
+
+        Ok(request.body(body).send().await?)
+    }
+
+    /// Keys and ETags of all objects starting with `prefix`
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<(String, String)>, SyncError> {
+        let mut objects = Vec::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = continuation_token.as_deref() {
+                query.push(("continuation-token", token));
+            }
+
+            let body = self
+                .send(Method::GET, "", &query, &[], Vec::new())
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+
+            objects.extend(
+                CONTENTS_REGEX
+                    .captures_iter(&body)
+                    .map(|c| (xml_unescape(&c[1]), xml_unescape(&c[2]))),
+            );
+
+            match CONTINUATION_REGEX.captures(&body) {
+                Some(c) => continuation_token = Some(xml_unescape(&c[1])),
+                None => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+        headers: &[(&str, String)],
+    ) -> Result<(), SyncError> {
+        let mut headers = headers.to_vec();
+        if !headers.iter().any(|(name, _)| *name == "Content-Type") {
+            headers.push(("Content-Type", "application/json".to_string()));
+        }
+
+        let response = self.send(Method::PUT, key, &[], &headers, body).await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(SyncError::Conflict(format!(
+                "{} was changed by another device",
+                key
+            )));
+        }
+
+        let response = response.error_for_status()?;
+        self.cache_etag(key, &response);
+
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        let response = self.send(Method::GET, key, &[], &[], Vec::new()).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+        self.cache_etag(key, &response);
+
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), SyncError> {
+        self.send(Method::DELETE, key, &[], &[], Vec::new())
+            .await?
+            .error_for_status()?;
+
+        if let Ok(mut etags) = self.etags.lock() {
+            etags.remove(key);
+        }
+
+        Ok(())
+    }
+
+    /// ETag of the object as last seen, asks the server if it was not listed yet
+    async fn etag(&self, key: &str) -> Result<String, SyncError> {
+        if let Some(etag) = self.cached_etag(key) {
+            return Ok(etag);
+        }
+
+        let response = self
+            .send(Method::HEAD, key, &[], &[], Vec::new())
+            .await?
+            .error_for_status()?;
+        self.cache_etag(key, &response);
+
+        self.cached_etag(key)
+            .ok_or_else(|| SyncError::CorruptPayload(format!("{} has no ETag", key)))
+    }
+
+    /// Writes `body` to `key` in place of `remote`, only if `remote` is still the version that
+    /// was synced
+    async fn replace_object(
+        &self,
+        remote: &Clippy,
+        key: &str,
+        body: Vec<u8>,
+        etag: String,
+    ) -> Result<(), SyncError> {
+        let content_type = ("Content-Type", "application/octet-stream".to_string());
+
+        if key == remote.provider_id {
+            return self
+                .put_object(key, body, &[("If-Match", etag), content_type])
+                .await;
+        }
+
+        let unchanged = self
+            .send(
+                Method::HEAD,
+                &remote.provider_id,
+                &[],
+                &[("If-Match", etag)],
+                Vec::new(),
+            )
+            .await?;
+        if unchanged.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(SyncError::Conflict(format!(
+                "{} was changed by another device",
+                remote.provider_id
+            )));
+        }
+        unchanged.error_for_status()?;
+
+        // Write the new object first, so the clipboard is never missing remotely
+        self.put_object(key, body, &[content_type]).await?;
+        self.delete_object(&remote.provider_id).await
+    }
+}
+
+/// Chunks are objects next to the clipboards, the key is the chunk id
+#[async_trait::async_trait]
+impl ChunkStore for S3ProviderImpl {
+    async fn put_chunk(&self, name: &str, content: Vec<u8>) -> Result<String, SyncError> {
+        let key = self.key(name);
+        self.put_object(
+            &key,
+            content,
+            &[("Content-Type", "application/octet-stream".to_string())],
+        )
+        .await?;
+        Ok(key)
+    }
+
+    /// Leaves the cached ETags alone, they have to stay at the version that was synced
+    async fn get_chunk(&self, id: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        let response = self.send(Method::GET, id, &[], &[], Vec::new()).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.bytes().await?.to_vec()))
+    }
+
+    async fn delete_chunk(&self, id: &str) -> Result<(), SyncError> {
+        self.delete_object(id).await
+    }
+}
+
+#[async_trait::async_trait]
+impl SyncProvider for S3ProviderImpl {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        let prefix = self.prefix();
+        let objects = self
+            .list_objects(&format!("{}{}", prefix, BACKUP_FILE_PREFIX))
+            .await?;
+
+        if let Ok(mut etags) = self.etags.lock() {
+            etags.extend(objects.iter().cloned());
+        }
+
+        let clipboards = objects
+            .into_iter()
+            .filter_map(|(key, _)| parse_clipboard_info(key.strip_prefix(prefix.as_str())?, &key))
+            .collect();
+
+        Ok(dedupe_clipboards(self, clipboards).await)
+    }
+
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        compare_and_fetch_new_clipboards(self, local_clipboards, remote_clipboards).await
+    }
+
+    async fn upload_new_clipboards(
+        &self,
+        new_local_clipboards: &[FullClipboardDto],
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
+    }
+
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
+        let new_name = create_clipboard_filename(
+            &clippy.id,
+            &clippy.star,
+            &clippy.encrypted,
+            &clippy.created_at,
+            Some(Utc::now().naive_utc()),
+            clippy.format,
+            clippy.chunks,
+        );
+        let new_key = self.key(&new_name);
+
+        // S3 has no rename, the object is copied to the new key and the old one removed
+        let copy_source = format!(
+            "/{}/{}",
+            self.credentials.bucket,
+            urlencoding::encode(&clippy.provider_id)
+        );
+        self.send(
+            Method::PUT,
+            &new_key,
+            &[],
+            &[("x-amz-copy-source", copy_source)],
+            Vec::new(),
+        )
+        .await?
+        .error_for_status()?;
+        self.delete_object(&clippy.provider_id).await?;
+
+        printlog!("(remote) marked clipboard for deletion: {}", clippy.id);
+
+        Ok(parse_clipboard_info(&new_name, &new_key).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        let chunks = remote_chunks(self, clippy).await;
+        ignore_not_found(self.delete_object(&clippy.provider_id).await)?;
+        delete_chunks(self, &chunks, &[]).await;
+        Ok(())
+    }
+
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        let content = self
+            .get_object(id)
+            .await?
+            .ok_or_else(|| SyncError::NotFound(format!("clipboard {}", id)))?;
+
+        decode_payload(self, &content).await
+    }
+
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        let payload = encode_payload(self, clipboard).await?;
+        let file_name = create_clipboard_filename(
+            &clipboard.clipboard.id,
+            &clipboard.clipboard.star,
+            &clipboard.clipboard.encrypted,
+            &clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+        let key = self.key(&file_name);
+
+        printlog!(
+            "uploading clipboard: {} from {} star: {} encrypted: {}",
+            clipboard.clipboard.id,
+            uuid_to_datetime(&clipboard.clipboard.id),
+            clipboard.clipboard.star,
+            clipboard.clipboard.encrypted
+        );
+
+        self.put_object(
+            &key,
+            payload.content,
+            &[("Content-Type", "application/octet-stream".to_string())],
+        )
+        .await?;
+
+        Ok(parse_clipboard_info(&file_name, &key).ok_or("Failed to parse clipboard info")?)
+    }
+
+    /// Only applies if the remote object is still the version that was synced, a clipboard
+    /// changed by another device in the meantime is picked up by the next sync instead
+    async fn update_clipboard(
+        &self,
+        local_clipboard: &FullClipboardDto,
+        remote_clipboard: &Clippy,
+    ) -> Result<Clippy, SyncError> {
+        let etag = self.etag(&remote_clipboard.provider_id).await?;
+        let stale_chunks = remote_chunks(self, remote_clipboard).await;
+        let payload = encode_payload(self, local_clipboard).await?;
+
+        let new_name = create_clipboard_filename(
+            &remote_clipboard.id,
+            &local_clipboard.clipboard.star, // Use local star status
+            &local_clipboard.clipboard.encrypted,
+            &local_clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+        let new_key = self.key(&new_name);
+        let updated =
+            parse_clipboard_info(&new_name, &new_key).ok_or("Failed to parse clipboard info")?;
+
+        printlog!(
+            "updating clipboard: {} from {} star: {} encrypted: {}",
+            remote_clipboard.id,
+            uuid_to_datetime(&remote_clipboard.id),
+            local_clipboard.clipboard.star,
+            local_clipboard.clipboard.encrypted
+        );
+
+        if let Err(e) = self
+            .replace_object(remote_clipboard, &new_key, payload.content, etag)
+            .await
+        {
+            // the chunks of the version that won stay
+            delete_chunks(self, &payload.chunks, &stale_chunks).await;
+            return Err(e);
+        }
+        delete_chunks(self, &stale_chunks, &payload.chunks).await;
+
+        Ok(updated)
+    }
+
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
+    }
+
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
+    }
+
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
+        self.put_object(
+            &self.key(&format!("{}.json", BACKUP_SETTINGS_PREFIX)),
+            serde_json::to_vec(settings)?,
+            &[],
+        )
+        .await?;
+
+        printlog!("(remote) uploaded settings");
+
+        Ok(())
+    }
+
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError> {
+        match self
+            .get_object(&self.key(&format!("{}.json", BACKUP_SETTINGS_PREFIX)))
+            .await?
+        {
+            Some(content) => Ok(serde_json::from_slice(&content)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// `None` if no manifest object exists under the prefix yet
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        match self.get_object(&self.key(SYNC_MANIFEST_NAME)).await? {
+            Some(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Only replaces the manifest this device read last, a concurrent write fails with 412
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        let key = self.key(SYNC_MANIFEST_NAME);
+        let condition = match self.cached_etag(&key) {
+            Some(etag) => ("If-Match", etag),
+            None => ("If-None-Match", "*".to_string()),
+        };
+
+        self.put_object(&key, serde_json::to_vec(manifest)?, &[condition])
+            .await
+    }
+
+    /// Lists a single object, which also works with keys that are limited to the prefix
+    async fn is_authenticated(&self) -> bool {
+        let prefix = self.prefix();
+        let response = self
+            .send(
+                Method::GET,
+                "",
+                &[
+                    ("list-type", "2"),
+                    ("max-keys", "1"),
+                    ("prefix", prefix.as_str()),
+                ],
+                &[],
+                Vec::new(),
+            )
+            .await
+            .map_err(|e| e.to_string());
+
+        match response {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                printlog!("(s3) authentication failed: {}", response.status());
+                false
+            }
+            Err(e) => {
+                printlog!("(s3) authentication failed: {}", e);
+                false
+            }
+        }
+    }
+}
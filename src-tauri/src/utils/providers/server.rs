@@ -0,0 +1,434 @@
+use super::{
+    cleanup_old_clipboards, compare_and_fetch_new_clipboards, dedupe_clipboards, ignore_not_found,
+    network::http_client,
+    payload::{decode_payload, delete_chunks, encode_payload, ChunkStore},
+    star_clipboard,
+    token_storage::write_private,
+    upload_new_clipboards, uuid_to_datetime,
+};
+use crate::{prelude::*, tao::config::get_data_path};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{NaiveDateTime, Utc};
+use common::{
+    constants::{SERVER_CREDENTIALS_NAME, SYNC_MANIFEST_VERSION, SYNC_PAYLOAD_VERSION},
+    types::{
+        orm_query::FullClipboardDto,
+        sync::{Clippy, ManifestEntry, ServerCredentials, SyncError, SyncManifest, SyncProvider},
+        types::CommandError,
+    },
+};
+use migration::async_trait;
+use reqwest::{Method, RequestBuilder, StatusCode};
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+/// Clipboard metadata as listed by the clippy sync server
+#[derive(Deserialize)]
+struct ServerClipboard {
+    id: Uuid,
+    star: bool,
+    encrypted: bool,
+    created_at: NaiveDateTime,
+    deleted_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    purged: bool,
+    #[serde(default = "default_payload_format")]
+    format: u32,
+    #[serde(default)]
+    chunks: Vec<String>,
+}
+
+fn default_payload_format() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+struct ServerChanges {
+    revision: u64,
+    full: bool,
+    clipboards: Vec<ServerClipboard>,
+}
+
+/// The compressed payload is sent as base64, the server stores it as is
+#[derive(Serialize)]
+struct ServerUpload {
+    star: bool,
+    encrypted: bool,
+    created_at: NaiveDateTime,
+    payload: String,
+    format: u32,
+    chunks: Vec<String>,
+}
+
+impl From<ServerClipboard> for Clippy {
+    fn from(clipboard: ServerClipboard) -> Self {
+        Clippy {
+            id: clipboard.id,
+            provider_id: clipboard.id.to_string(),
+            star: clipboard.star,
+            encrypted: clipboard.encrypted,
+            created_at: clipboard.created_at,
+            deleted_at: clipboard.deleted_at,
+            format: clipboard.format,
+            chunks: clipboard.chunks.len().max(1) as u32,
+        }
+    }
+}
+
+/// Remote listing as of `revision`, kept up to date through delta queries
+#[derive(Default)]
+struct ServerListing {
+    revision: u64,
+    clipboards: HashMap<Uuid, Clippy>,
+}
+
+/// Syncs with a self-hosted `clippy-server`, the clipboard id is the provider id
+pub struct ServerProviderImpl {
+    client: reqwest::Client,
+    credentials: ServerCredentials,
+    listing: Mutex<ServerListing>,
+}
+
+impl ServerProviderImpl {
+    pub async fn new() -> Result<Self, CommandError> {
+        let credentials = Self::load_credentials()
+            .ok_or_else(|| CommandError::new("MAIN.ERROR.SERVER_NOT_CONFIGURED"))?;
+
+        Self::from_credentials(credentials)
+    }
+
+    pub fn from_credentials(credentials: ServerCredentials) -> Result<Self, CommandError> {
+        Ok(Self {
+            client: http_client()?,
+            credentials,
+            listing: Mutex::new(ServerListing::default()),
+        })
+    }
+
+    fn credentials_path() -> PathBuf {
+        std::path::Path::new(&get_data_path().config_path).join(SERVER_CREDENTIALS_NAME)
+    }
+
+    pub fn load_credentials() -> Option<ServerCredentials> {
+        let json = std::fs::read_to_string(Self::credentials_path()).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// The token grants access to the space, keep it unreadable for other users
+    pub fn save_credentials(credentials: &ServerCredentials) -> Result<(), CommandError> {
+        write_private(
+            &Self::credentials_path(),
+            serde_json::to_string(credentials)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_credentials() {
+        std::fs::remove_file(Self::credentials_path()).ok();
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(
+                method,
+                format!(
+                    "{}/api/{}",
+                    self.credentials.url.trim_end_matches('/'),
+                    path
+                ),
+            )
+            .bearer_auth(&self.credentials.token)
+    }
+}
+
+/// Chunks are uploaded through their own route, the server removes them with their clipboard
+#[async_trait::async_trait]
+impl ChunkStore for ServerProviderImpl {
+    async fn put_chunk(&self, name: &str, content: Vec<u8>) -> Result<String, SyncError> {
+        self.request(Method::PUT, &format!("chunks/{}", name))
+            .body(content)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(name.to_string())
+    }
+
+    async fn get_chunk(&self, id: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        let response = self
+            .request(Method::GET, &format!("chunks/{}", id))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.bytes().await?.to_vec()))
+    }
+
+    async fn delete_chunk(&self, id: &str) -> Result<(), SyncError> {
+        self.request(Method::DELETE, &format!("chunks/{}", id))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl SyncProvider for ServerProviderImpl {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        let since = self.listing.lock().map_err(|e| e.to_string())?.revision;
+
+        let changes: ServerChanges = self
+            .request(Method::GET, "clipboards")
+            .query(&[("since", since)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let clipboards: Vec<Clippy> = {
+            let mut listing = self.listing.lock().map_err(|e| e.to_string())?;
+
+            if changes.full {
+                listing.clipboards.clear();
+            }
+            for clipboard in changes.clipboards {
+                if clipboard.purged {
+                    listing.clipboards.remove(&clipboard.id);
+                } else {
+                    listing.clipboards.insert(clipboard.id, clipboard.into());
+                }
+            }
+            listing.revision = changes.revision;
+
+            listing.clipboards.values().cloned().collect()
+        };
+
+        Ok(dedupe_clipboards(self, clipboards).await)
+    }
+
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        compare_and_fetch_new_clipboards(self, local_clipboards, remote_clipboards).await
+    }
+
+    async fn upload_new_clipboards(
+        &self,
+        new_local_clipboards: &[FullClipboardDto],
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
+    }
+
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
+        let deleted: ServerClipboard = self
+            .request(
+                Method::DELETE,
+                &format!("clipboards/{}", clippy.provider_id),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        printlog!("(remote) marked clipboard for deletion: {}", clippy.id);
+
+        Ok(deleted.into())
+    }
+
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        let response = self
+            .request(
+                Method::DELETE,
+                &format!("clipboards/{}", clippy.provider_id),
+            )
+            .query(&[("purge", true)])
+            .send()
+            .await?;
+
+        ignore_not_found(
+            response
+                .error_for_status()
+                .map(|_| ())
+                .map_err(SyncError::from),
+        )
+    }
+
+    /// Clipboards uploaded before payloads were compressed are stored as plain JSON
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        let payload: Value = self
+            .request(Method::GET, &format!("clipboards/{}", id))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match payload {
+            Value::String(content) => {
+                let content = STANDARD
+                    .decode(content)
+                    .map_err(|e| SyncError::CorruptPayload(e.to_string()))?;
+                decode_payload(self, &content).await
+            }
+            payload => Ok(serde_json::from_value(payload)?),
+        }
+    }
+
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        printlog!(
+            "uploading clipboard: {} from {} star: {} encrypted: {}",
+            clipboard.clipboard.id,
+            uuid_to_datetime(&clipboard.clipboard.id),
+            clipboard.clipboard.star,
+            clipboard.clipboard.encrypted
+        );
+
+        let payload = encode_payload(self, clipboard).await?;
+        let response = self
+            .request(
+                Method::PUT,
+                &format!("clipboards/{}", clipboard.clipboard.id),
+            )
+            .json(&ServerUpload {
+                star: clipboard.clipboard.star,
+                encrypted: clipboard.clipboard.encrypted,
+                created_at: clipboard.clipboard.created_at,
+                payload: STANDARD.encode(&payload.content),
+                format: SYNC_PAYLOAD_VERSION,
+                chunks: payload.chunks.clone(),
+            })
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::CONFLICT {
+            delete_chunks(self, &payload.chunks, &[]).await;
+            return Err(SyncError::Conflict(format!(
+                "clipboard {} was deleted remotely",
+                clipboard.clipboard.id
+            )));
+        }
+
+        let uploaded: ServerClipboard = response.error_for_status()?.json().await?;
+
+        Ok(uploaded.into())
+    }
+
+    async fn update_clipboard(
+        &self,
+        local_clipboard: &FullClipboardDto,
+        remote_clipboard: &Clippy,
+    ) -> Result<Clippy, SyncError> {
+        printlog!(
+            "updating clipboard: {} from {} star: {} encrypted: {}",
+            remote_clipboard.id,
+            uuid_to_datetime(&remote_clipboard.id),
+            local_clipboard.clipboard.star,
+            local_clipboard.clipboard.encrypted
+        );
+
+        // the server replaces the clipboard in place
+        self.upload_clipboard(local_clipboard).await
+    }
+
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
+    }
+
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
+    }
+
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
+        self.request(Method::PUT, "settings")
+            .json(settings)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        printlog!("(remote) uploaded settings");
+
+        Ok(())
+    }
+
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError> {
+        Ok(self
+            .request(Method::GET, "settings")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// The server keeps its own change counter, the manifest is built from its delta listing
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        let clipboards = self.fetch_all_clipboards().await?;
+        let revision = self.listing.lock().map_err(|e| e.to_string())?.revision;
+        let devices = self
+            .request(Method::GET, "devices")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Some(SyncManifest {
+            version: SYNC_MANIFEST_VERSION,
+            revision,
+            rebuilt_at: Utc::now().naive_utc(),
+            clipboards: clipboards
+                .into_iter()
+                .map(|clippy| {
+                    (
+                        clippy.id,
+                        ManifestEntry {
+                            revision,
+                            ..ManifestEntry::from(&clippy)
+                        },
+                    )
+                })
+                .collect(),
+            devices,
+            // the server keeps its own tombstones and expires them itself
+            tombstones: HashMap::new(),
+        }))
+    }
+
+    /// Only the device registry is written, every upload and deletion already updated the
+    /// server index
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        self.request(Method::PUT, "devices")
+            .json(&manifest.devices)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn is_authenticated(&self) -> bool {
+        match self.request(Method::GET, "auth").send().await {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                printlog!("(server) authentication failed: {}", response.status());
+                false
+            }
+            Err(e) => {
+                printlog!("(server) authentication failed: {}", e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use super::network::http_client;
+use crate::{
+    prelude::*,
+    service::{
+        decrypt::decrypt_data_with_key,
+        encrypt::{encrypt_data_with_key, looks_like_encrypted_data},
+    },
+    tao::config::get_data_path,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    constants::{GOOGLE_REVOKE_URL, TOKEN_KEY_NAME, TOKEN_NAME},
+    types::cipher::EncryptionError,
+};
+use google_drive3::yup_oauth2::storage::{TokenInfo, TokenStorage};
+use migration::async_trait;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use zeroize::Zeroizing;
+
+/// A token and the scopes it was granted for, laid out like the plain `token.json` of
+/// yup-oauth2 so the login of an older version is taken over
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredToken {
+    scopes: Vec<String>,
+    token: TokenInfo,
+}
+
+/// Keeps the Google tokens encrypted with a key of this install, in files only the user can read
+pub struct EncryptedTokenStorage {
+    tokens: Mutex<Vec<StoredToken>>,
+}
+
+fn token_path() -> PathBuf {
+    Path::new(&get_data_path().config_path).join(TOKEN_NAME)
+}
+
+fn token_key_path() -> PathBuf {
+    Path::new(&get_data_path().config_path).join(TOKEN_KEY_NAME)
+}
+
+/// Writes a file with permissions for the current user only
+pub fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    // the mode only applies to new files, an existing one may still be readable by others
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(contents)
+}
+
+/// Key of this install the tokens are encrypted with, created on first use
+fn token_key() -> Result<Zeroizing<[u8; 32]>, EncryptionError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+
+    if let Ok(encoded) = fs::read_to_string(token_key_path()) {
+        let key_bytes = STANDARD
+            .decode(encoded.trim())
+            .map(Zeroizing::new)
+            .map_err(|_| EncryptionError::InvalidKey)?;
+        if key_bytes.len() != 32 {
+            return Err(EncryptionError::InvalidKey);
+        }
+
+        key.copy_from_slice(&key_bytes);
+        return Ok(key);
+    }
+
+    SystemRandom::new()
+        .fill(key.as_mut())
+        .map_err(|_| EncryptionError::EncryptionFailed)?;
+    write_private(&token_key_path(), STANDARD.encode(key.as_ref()).as_bytes())
+        .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+    Ok(key)
+}
+
+/// The stored tokens and whether they were stored in plain text, an unreadable file is
+/// treated like a missing login
+fn load_tokens() -> (Vec<StoredToken>, bool) {
+    let Ok(data) = fs::read(token_path()) else {
+        return (Vec::new(), false);
+    };
+
+    let plain = !looks_like_encrypted_data(&data);
+    let json = if plain {
+        data
+    } else {
+        match token_key().and_then(|key| decrypt_data_with_key(&data, &key)) {
+            Ok(json) => json,
+            Err(e) => {
+                printlog!("failed to decrypt the Google token: {}", e);
+                return (Vec::new(), false);
+            }
+        }
+    };
+
+    match serde_json::from_slice(&json) {
+        Ok(tokens) => (tokens, plain),
+        Err(e) => {
+            printlog!("failed to read the Google token: {}", e);
+            (Vec::new(), false)
+        }
+    }
+}
+
+fn save_tokens(tokens: &[StoredToken]) -> anyhow::Result<()> {
+    let json = serde_json::to_vec(tokens)?;
+    let data = encrypt_data_with_key(&json, &*token_key()?)?;
+    write_private(&token_path(), &data)?;
+
+    Ok(())
+}
+
+impl EncryptedTokenStorage {
+    /// Reads the stored tokens, a plain text token of an older version is encrypted right away
+    pub fn load() -> Self {
+        let (tokens, plain) = load_tokens();
+
+        if plain && !tokens.is_empty() {
+            match save_tokens(&tokens) {
+                Ok(()) => printlog!("encrypted the stored Google token"),
+                Err(e) => printlog!("failed to encrypt the Google token: {}", e),
+            }
+        }
+
+        Self {
+            tokens: Mutex::new(tokens),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenStorage for EncryptedTokenStorage {
+    async fn set(&self, scopes: &[&str], token: TokenInfo) -> anyhow::Result<()> {
+        let mut scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+        scopes.sort();
+
+        let mut tokens = self
+            .tokens
+            .lock()
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        tokens.retain(|stored| stored.scopes != scopes);
+        tokens.push(StoredToken { scopes, token });
+
+        save_tokens(&tokens)
+    }
+
+    /// A token granted for more scopes than requested is used as well
+    async fn get(&self, scopes: &[&str]) -> Option<TokenInfo> {
+        self.tokens
+            .lock()
+            .ok()?
+            .iter()
+            .find(|stored| {
+                scopes
+                    .iter()
+                    .all(|scope| stored.scopes.iter().any(|granted| granted == scope))
+            })
+            .map(|stored| stored.token.clone())
+    }
+}
+
+/// Revokes the stored Google login and removes it along with its key. A failed revocation
+/// is only logged, the login is gone from this device either way
+pub async fn revoke_tokens() {
+    let client = match http_client() {
+        Ok(client) => client,
+        Err(e) => {
+            printlog!(
+                "failed to build the client to revoke the Google token: {}",
+                e
+            );
+            reqwest::Client::new()
+        }
+    };
+
+    for stored in load_tokens().0 {
+        let Some(token) = stored.token.refresh_token.or(stored.token.access_token) else {
+            continue;
+        };
+
+        match client
+            .post(GOOGLE_REVOKE_URL)
+            .form(&[("token", token)])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            Ok(_) => printlog!("revoked the Google token"),
+            Err(e) => printlog!("failed to revoke the Google token: {}", e),
+        }
+    }
+
+    fs::remove_file(token_path()).ok();
+    fs::remove_file(token_key_path()).ok();
+}
@@ -0,0 +1,450 @@
+use super::{
+    cleanup_old_clipboards, compare_and_fetch_new_clipboards, create_clipboard_filename,
+    dedupe_clipboards, ignore_not_found,
+    network::http_client,
+    parse_clipboard_info,
+    payload::{decode_payload, delete_chunks, encode_payload, remote_chunks, ChunkStore},
+    star_clipboard,
+    token_storage::write_private,
+    upload_new_clipboards, uuid_to_datetime,
+};
+use crate::{prelude::*, tao::config::get_data_path};
+use chrono::{NaiveDateTime, Utc};
+use common::{
+    constants::{
+        BACKUP_FILE_PREFIX, BACKUP_SETTINGS_PREFIX, SYNC_MANIFEST_NAME, SYNC_PAYLOAD_VERSION,
+        WEBDAV_CREDENTIALS_NAME,
+    },
+    types::{
+        orm_query::FullClipboardDto,
+        sync::{Clippy, SyncError, SyncManifest, SyncProvider, WebDavCredentials},
+        types::CommandError,
+    },
+};
+use migration::async_trait;
+use regex::Regex;
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode};
+use sea_orm::prelude::Uuid;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
+
+static HREF_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(?:[A-Za-z0-9]+:)?href>([^<]*)</(?:[A-Za-z0-9]+:)?href>")
+        .expect("Invalid href regex")
+});
+
+static PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+
+/// Names of the files in a `PROPFIND` response, servers differ in the namespace prefix
+pub fn file_names(multistatus: &str) -> Vec<String> {
+    HREF_REGEX
+        .captures_iter(multistatus)
+        .filter_map(|capture| {
+            let href = urlencoding::decode(&capture[1]).ok()?.into_owned();
+            // the collection itself ends with a slash
+            let name = href.rsplit('/').next()?.to_string();
+            (!name.is_empty()).then_some(name)
+        })
+        .collect()
+}
+
+fn etag(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Stores clipboards as files in a WebDAV collection (Nextcloud, ownCloud, Apache, rclone serve webdav, ...).
+/// Files use the same names as on Google Drive, so the filename is the provider id
+pub struct WebDavProviderImpl {
+    client: reqwest::Client,
+    credentials: WebDavCredentials,
+    /// ETag of the manifest as last read, `None` if there was none. Writes only replace it
+    manifest_etag: Mutex<Option<String>>,
+}
+
+impl WebDavProviderImpl {
+    pub async fn new() -> Result<Self, CommandError> {
+        let credentials = Self::load_credentials()
+            .ok_or_else(|| CommandError::new("MAIN.ERROR.WEBDAV_NOT_CONFIGURED"))?;
+
+        Self::from_credentials(credentials)
+    }
+
+    pub fn from_credentials(credentials: WebDavCredentials) -> Result<Self, CommandError> {
+        Ok(Self {
+            client: http_client()?,
+            credentials,
+            manifest_etag: Mutex::new(None),
+        })
+    }
+
+    fn credentials_path() -> PathBuf {
+        std::path::Path::new(&get_data_path().config_path).join(WEBDAV_CREDENTIALS_NAME)
+    }
+
+    pub fn load_credentials() -> Option<WebDavCredentials> {
+        let json = std::fs::read_to_string(Self::credentials_path()).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Holds the password, so only the current user may read it
+    pub fn save_credentials(credentials: &WebDavCredentials) -> Result<(), CommandError> {
+        write_private(
+            &Self::credentials_path(),
+            serde_json::to_string(credentials)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_credentials() {
+        std::fs::remove_file(Self::credentials_path()).ok();
+    }
+
+    fn collection_url(&self) -> String {
+        format!("{}/", self.credentials.url.trim_end_matches('/'))
+    }
+
+    fn file_url(&self, name: &str) -> String {
+        format!("{}{}", self.collection_url(), urlencoding::encode(name))
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .basic_auth(&self.credentials.username, Some(&self.credentials.password))
+    }
+
+    fn method(name: &'static [u8]) -> Method {
+        Method::from_bytes(name).expect("Invalid WebDAV method")
+    }
+
+    /// Names of all files in the collection
+    async fn list_files(&self) -> Result<Vec<String>, SyncError> {
+        let body = self
+            .request(Self::method(b"PROPFIND"), &self.collection_url())
+            .header("Depth", "1")
+            .header(header::CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        Ok(file_names(&body))
+    }
+
+    async fn put_file(
+        &self,
+        name: &str,
+        body: Vec<u8>,
+        content_type: &'static str,
+    ) -> Result<(), SyncError> {
+        self.request(Method::PUT, &self.file_url(name))
+            .header(header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn get_file(&self, name: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        let response = self
+            .request(Method::GET, &self.file_url(name))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.bytes().await?.to_vec()))
+    }
+
+    fn cache_manifest_etag(&self, etag: Option<String>) -> Result<(), SyncError> {
+        *self.manifest_etag.lock().map_err(|e| e.to_string())? = etag;
+        Ok(())
+    }
+
+    async fn move_file(&self, from: &str, to: &str) -> Result<(), SyncError> {
+        self.request(Self::method(b"MOVE"), &self.file_url(from))
+            .header("Destination", self.file_url(to))
+            .header("Overwrite", "T")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn delete_file(&self, name: &str) -> Result<(), SyncError> {
+        self.request(Method::DELETE, &self.file_url(name))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Chunks are files next to the clipboards, the filename is the chunk id
+#[async_trait::async_trait]
+impl ChunkStore for WebDavProviderImpl {
+    async fn put_chunk(&self, name: &str, content: Vec<u8>) -> Result<String, SyncError> {
+        self.put_file(name, content, "application/octet-stream")
+            .await?;
+        Ok(name.to_string())
+    }
+
+    async fn get_chunk(&self, id: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        self.get_file(id).await
+    }
+
+    async fn delete_chunk(&self, id: &str) -> Result<(), SyncError> {
+        self.delete_file(id).await
+    }
+}
+
+#[async_trait::async_trait]
+impl SyncProvider for WebDavProviderImpl {
+    async fn fetch_all_clipboards(&self) -> Result<Vec<Clippy>, SyncError> {
+        let clipboards = self
+            .list_files()
+            .await?
+            .into_iter()
+            .filter(|name| name.starts_with(BACKUP_FILE_PREFIX))
+            .filter_map(|name| parse_clipboard_info(&name, &name))
+            .collect();
+
+        Ok(dedupe_clipboards(self, clipboards).await)
+    }
+
+    async fn compare_and_fetch_new_clipboards(
+        &self,
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<FullClipboardDto>, SyncError> {
+        compare_and_fetch_new_clipboards(self, local_clipboards, remote_clipboards).await
+    }
+
+    async fn upload_new_clipboards(
+        &self,
+        new_local_clipboards: &[FullClipboardDto],
+        remote_clipboards: &[Clippy],
+    ) -> Result<Vec<Clippy>, SyncError> {
+        upload_new_clipboards(self, new_local_clipboards, remote_clipboards).await
+    }
+
+    async fn mark_for_deletion(&self, clippy: &Clippy) -> Result<Clippy, SyncError> {
+        let new_name = create_clipboard_filename(
+            &clippy.id,
+            &clippy.star,
+            &clippy.encrypted,
+            &clippy.created_at,
+            Some(Utc::now().naive_utc()),
+            clippy.format,
+            clippy.chunks,
+        );
+
+        self.move_file(&clippy.provider_id, &new_name).await?;
+
+        printlog!("(remote) marked clipboard for deletion: {}", clippy.id);
+
+        Ok(parse_clipboard_info(&new_name, &new_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn delete_clipboard(&self, clippy: &Clippy) -> Result<(), SyncError> {
+        let chunks = remote_chunks(self, clippy).await;
+        ignore_not_found(self.delete_file(&clippy.provider_id).await)?;
+        delete_chunks(self, &chunks, &[]).await;
+        Ok(())
+    }
+
+    async fn download_by_id(&self, id: &str) -> Result<FullClipboardDto, SyncError> {
+        let content = self
+            .get_file(id)
+            .await?
+            .ok_or_else(|| SyncError::NotFound(format!("clipboard {}", id)))?;
+
+        decode_payload(self, &content).await
+    }
+
+    async fn upload_clipboard(&self, clipboard: &FullClipboardDto) -> Result<Clippy, SyncError> {
+        let payload = encode_payload(self, clipboard).await?;
+        let file_name = create_clipboard_filename(
+            &clipboard.clipboard.id,
+            &clipboard.clipboard.star,
+            &clipboard.clipboard.encrypted,
+            &clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+
+        printlog!(
+            "uploading clipboard: {} from {} star: {} encrypted: {}",
+            clipboard.clipboard.id,
+            uuid_to_datetime(&clipboard.clipboard.id),
+            clipboard.clipboard.star,
+            clipboard.clipboard.encrypted
+        );
+
+        self.put_file(&file_name, payload.content, "application/octet-stream")
+            .await?;
+
+        Ok(parse_clipboard_info(&file_name, &file_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn update_clipboard(
+        &self,
+        local_clipboard: &FullClipboardDto,
+        remote_clipboard: &Clippy,
+    ) -> Result<Clippy, SyncError> {
+        let stale_chunks = remote_chunks(self, remote_clipboard).await;
+        let payload = encode_payload(self, local_clipboard).await?;
+        let new_name = create_clipboard_filename(
+            &remote_clipboard.id,
+            &local_clipboard.clipboard.star, // Use local star status
+            &local_clipboard.clipboard.encrypted,
+            &local_clipboard.clipboard.created_at,
+            None,
+            SYNC_PAYLOAD_VERSION,
+            payload.parts(),
+        );
+
+        printlog!(
+            "updating clipboard: {} from {} star: {} encrypted: {}",
+            remote_clipboard.id,
+            uuid_to_datetime(&remote_clipboard.id),
+            local_clipboard.clipboard.star,
+            local_clipboard.clipboard.encrypted
+        );
+
+        // Write the new file first, so the clipboard is never missing remotely
+        self.put_file(&new_name, payload.content, "application/octet-stream")
+            .await?;
+
+        if new_name != remote_clipboard.provider_id {
+            self.delete_file(&remote_clipboard.provider_id).await.ok();
+        }
+        delete_chunks(self, &stale_chunks, &payload.chunks).await;
+
+        Ok(parse_clipboard_info(&new_name, &new_name).ok_or("Failed to parse clipboard info")?)
+    }
+
+    async fn star_clipboard(&self, clippy: &FullClipboardDto) -> Result<(), SyncError> {
+        star_clipboard(self, clippy).await
+    }
+
+    async fn cleanup_old_clipboards(&self, remote_clipboards: &[Clippy]) -> Result<(), SyncError> {
+        cleanup_old_clipboards(self, remote_clipboards).await
+    }
+
+    async fn upsert_settings(&self, settings: &HashMap<String, Value>) -> Result<(), SyncError> {
+        self.put_file(
+            &format!("{}.json", BACKUP_SETTINGS_PREFIX),
+            serde_json::to_vec(settings)?,
+            "application/json",
+        )
+        .await?;
+
+        printlog!("(remote) uploaded settings");
+
+        Ok(())
+    }
+
+    async fn get_settings(&self) -> Result<HashMap<String, Value>, SyncError> {
+        match self
+            .get_file(&format!("{}.json", BACKUP_SETTINGS_PREFIX))
+            .await?
+        {
+            Some(content) => Ok(serde_json::from_slice(&content)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Read from the root of the collection, `None` before the first sync
+    async fn get_manifest(&self) -> Result<Option<SyncManifest>, SyncError> {
+        let response = self
+            .request(Method::GET, &self.file_url(SYNC_MANIFEST_NAME))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            self.cache_manifest_etag(None)?;
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+        self.cache_manifest_etag(etag(&response))?;
+
+        Ok(Some(serde_json::from_slice(&response.bytes().await?)?))
+    }
+
+    /// Only replaces the manifest this device read last, a concurrent write fails with 412
+    async fn put_manifest(&self, manifest: &SyncManifest) -> Result<(), SyncError> {
+        let condition = match self
+            .manifest_etag
+            .lock()
+            .map_err(|e| e.to_string())?
+            .clone()
+        {
+            Some(etag) => (header::IF_MATCH, etag),
+            None => (header::IF_NONE_MATCH, "*".to_string()),
+        };
+
+        let response = self
+            .request(Method::PUT, &self.file_url(SYNC_MANIFEST_NAME))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(condition.0, condition.1)
+            .body(serde_json::to_vec(manifest)?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(SyncError::Conflict(format!(
+                "{} was changed by another device",
+                SYNC_MANIFEST_NAME
+            )));
+        }
+
+        // without an ETag in the response the next write reads the manifest first anyway
+        let response = response.error_for_status()?;
+        self.cache_manifest_etag(etag(&response))
+    }
+
+    /// Checks the login and creates the collection on first use
+    async fn is_authenticated(&self) -> bool {
+        let response = self
+            .request(Self::method(b"PROPFIND"), &self.collection_url())
+            .header("Depth", "0")
+            .send()
+            .await;
+
+        match response.map(|r| r.status()) {
+            Ok(status) if status.is_success() => true,
+            Ok(status) if status == StatusCode::NOT_FOUND => self
+                .request(Self::method(b"MKCOL"), &self.collection_url())
+                .send()
+                .await
+                .is_ok_and(|r| r.status().is_success()),
+            Ok(status) => {
+                printlog!("(webdav) authentication failed: {}", status);
+                false
+            }
+            Err(e) => {
+                printlog!("(webdav) authentication failed: {}", e);
+                false
+            }
+        }
+    }
+}
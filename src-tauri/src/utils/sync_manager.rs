@@ -1,13 +1,51 @@
 use crate::prelude::*;
-use crate::service::clipboard::{delete_clipboards_db, new_clipboard_event};
-use crate::service::settings::update_settings_from_sync;
+use crate::service::clipboard::{
+    delete_clipboards_db, delete_device_clipboards_db, get_clipboard_db, get_clipboard_revision_db,
+    get_sync_excluded_ids_db, new_clipboard_event,
+};
+use crate::service::device::upsert_devices_db;
+use crate::service::search::index_encrypted_clipboard_db;
+use crate::service::settings::{
+    get_global_settings, get_settings_for_sync, update_settings_from_sync,
+};
+use crate::service::sync_outbox::{
+    complete_sync_outbox_db, fail_sync_outbox_db, get_due_sync_outbox_db,
+    get_next_sync_outbox_attempt_db, get_pending_sync_deletions_db, queue_sync_changes,
+};
 use crate::service::{
     clipboard::{get_clipboard_uuids_db, get_latest_syncable_cliboards_db, upsert_clipboard_dto},
-    sync::get_sync_provider,
+    sync::{
+        get_sync_mirrors, get_sync_provider, update_provider_status, update_sync_status,
+        upsert_settings_sync,
+    },
+};
+use crate::tao::config::get_device_name;
+use crate::tao::global::{get_device_id, get_sync_pull, get_sync_push};
+use chrono::{NaiveDateTime, Utc};
+use common::constants::{
+    SYNC_DEVICE_SEEN_HOURS, SYNC_PULL_INTERVAL_DEV, SYNC_PULL_INTERVAL_MAX,
+    SYNC_PULL_INTERVAL_PROD, SYNC_PUSH_DEBOUNCE_MS,
+};
+use common::types::enums::SyncOperation;
+use common::types::orm_query::FullClipboardDto;
+use common::types::sync::{Clippy, DeviceEntry, SyncError, SyncProvider, SyncRevision};
+use entity::sync_outbox;
+use sea_orm::{prelude::Uuid, DbErr, Iterable};
+use std::{collections::HashMap, time::Duration};
+use tokio::{
+    task::JoinHandle,
+    time::{self, Instant},
 };
-use sea_orm::prelude::Uuid;
-use std::time::Duration;
-use tokio::{task::JoinHandle, time};
+
+/// What a pull found on the remote
+pub struct RemoteChanges {
+    /// Clipboards deleted remotely, by marker or tombstone
+    pub deleted_clipboards: Vec<Uuid>,
+    /// Clipboards that are new or changed remotely
+    pub new_clipboards: Vec<FullClipboardDto>,
+    /// Newest tombstone revision of the remote
+    pub seen_revision: u64,
+}
 
 pub struct SyncManager {
     job_handle: Option<JoinHandle<()>>,
@@ -22,69 +60,573 @@ impl SyncManager {
         }
     }
 
-    async fn sync_job() -> Result<(), Box<dyn std::error::Error>> {
-        let provider = get_sync_provider().await;
-        if provider.is_authenticated().await {
-            let settings = provider.get_settings().await?;
-            update_settings_from_sync(settings).await?;
+    /// Pulls remote changes and uploads what is missing, returns whether anything changed
+    async fn sync_job() -> Result<bool, SyncError> {
+        let mut changed = false;
 
-            let local_clipboards = get_clipboard_uuids_db().await?;
-            let mut remote_clipboards = provider.fetch_all_clipboards().await?;
+        let provider = get_sync_provider().await?;
+        if !provider.is_authenticated().await {
+            return Err(SyncError::Auth(
+                "not signed in to the sync provider".to_string(),
+            ));
+        }
 
-            let deleted_clipboards: Vec<Uuid> = remote_clipboards
-                .iter()
-                .filter(|clipboard| clipboard.deleted_at.is_some())
-                .map(|clipboard| clipboard.id)
-                .collect();
+        let settings = provider.get_settings().await?;
+        if update_settings_from_sync(settings).await? {
+            upsert_settings_sync(&get_global_settings(), true).await?;
+        }
+
+        let mut remote_clipboards = provider.fetch_all_clipboards().await?;
+        // removes the clipboards of revoked devices, so it goes before the local listing
+        let revoked_devices = Self::sync_devices(provider.as_ref()).await?;
+        let local_clipboards = get_clipboard_uuids_db().await?;
+
+        // deleted here but not pushed yet, must not be downloaded again
+        let pending_deletions = get_pending_sync_deletions_db().await?;
+        remote_clipboards.retain(|clipboard| !pending_deletions.contains(&clipboard.id));
+
+        let remote_count = remote_clipboards
+            .iter()
+            .filter(|clipboard| clipboard.deleted_at.is_none())
+            .count() as u64;
+        let pending_downloads = remote_clipboards
+            .iter()
+            .filter(|clipboard| {
+                clipboard.deleted_at.is_none() && !local_clipboards.contains_key(&clipboard.id)
+            })
+            .count() as u64;
+        update_sync_status(|status| {
+            status.remote_count = remote_count;
+            status.pending_downloads = pending_downloads;
+        })
+        .await;
+
+        // clipboards excluded from sync were removed from the remote by this device
+        let sync_excluded = get_sync_excluded_ids_db().await?;
+        let RemoteChanges {
+            deleted_clipboards,
+            new_clipboards,
+            seen_revision,
+        } = Self::pull_changes(
+            provider.as_ref(),
+            &remote_clipboards,
+            &local_clipboards,
+            &sync_excluded,
+        )
+        .await?;
+
+        changed |= deleted_clipboards
+            .iter()
+            .any(|id| local_clipboards.contains_key(id));
+
+        delete_clipboards_db(deleted_clipboards.clone(), None).await?;
+        Self::see_tombstones(provider.as_ref(), seen_revision).await?;
+
+        changed |= !new_clipboards.is_empty();
+
+        let downloaded = new_clipboards
+            .iter()
+            .filter(|clipboard| !local_clipboards.contains_key(&clipboard.clipboard.id))
+            .count() as u64;
+        update_sync_status(|status| {
+            status.pending_downloads = pending_downloads.saturating_sub(downloaded)
+        })
+        .await;
+
+        for clipboard in new_clipboards {
+            let revoked = clipboard
+                .clipboard
+                .device_id
+                .as_ref()
+                .is_some_and(|device_id| revoked_devices.contains(device_id));
+            if revoked || !Self::remote_wins(&clipboard).await? {
+                continue;
+            }
+
+            upsert_clipboard_dto(clipboard.clone()).await?;
+            if let Err(e) = index_encrypted_clipboard_db(&clipboard).await {
+                printlog!("Failed to index clipboard: {:?}", e);
+            }
+            new_clipboard_event(clipboard);
+        }
+
+        let new_local_clipboards = get_latest_syncable_cliboards_db().await?;
+
+        let new_remote_clipboards = provider
+            .upload_new_clipboards(&new_local_clipboards, &remote_clipboards)
+            .await?;
+
+        changed |= !new_remote_clipboards.is_empty();
+
+        let uploaded = new_remote_clipboards.len() as u64;
+        update_sync_status(|status| status.remote_count = remote_count + uploaded).await;
+
+        remote_clipboards.extend(new_remote_clipboards);
+
+        provider.collect_tombstones().await?;
+        provider.cleanup_old_clipboards(&remote_clipboards).await?;
+        provider.flush_manifest().await?;
+
+        Self::sync_mirrors(&new_local_clipboards, &deleted_clipboards).await;
+
+        Ok(changed)
+    }
+
+    /// Reads what changed on the remote since the last pull, without applying anything locally
+    pub async fn pull_changes(
+        provider: &dyn SyncProvider,
+        remote_clipboards: &[Clippy],
+        local_clipboards: &HashMap<Uuid, (bool, NaiveDateTime)>,
+        sync_excluded: &[Uuid],
+    ) -> Result<RemoteChanges, SyncError> {
+        let tombstones = provider.get_tombstones().await?;
+        let seen_revision = tombstones
+            .values()
+            .map(|tombstone| tombstone.revision)
+            .max()
+            .unwrap_or_default();
+        let deleted_clipboards: Vec<Uuid> = remote_clipboards
+            .iter()
+            .filter(|clipboard| clipboard.deleted_at.is_some())
+            .map(|clipboard| clipboard.id)
+            .chain(tombstones.into_keys())
+            .filter(|id| !sync_excluded.contains(id))
+            .collect();
 
-            delete_clipboards_db(deleted_clipboards, None)
-                .await
-                .expect("Error deleting clipboards");
+        let new_clipboards = provider
+            .compare_and_fetch_new_clipboards(local_clipboards, remote_clipboards)
+            .await?;
 
-            let new_clipboards = provider
-                .compare_and_fetch_new_clipboards(&local_clipboards, &remote_clipboards)
-                .await?;
+        Ok(RemoteChanges {
+            deleted_clipboards,
+            new_clipboards,
+            seen_revision,
+        })
+    }
+
+    /// Brings every mirror up to date with this device, a failing mirror only shows up in its
+    /// own status and does not fail the sync
+    async fn sync_mirrors(new_local_clipboards: &[FullClipboardDto], deleted_clipboards: &[Uuid]) {
+        for (provider_type, mirror) in get_sync_mirrors().await {
+            let result = match mirror {
+                Ok(mirror) => {
+                    Self::sync_mirror(mirror.as_ref(), new_local_clipboards, deleted_clipboards)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
 
-            for clipboard in new_clipboards {
-                upsert_clipboard_dto(clipboard.clone()).await?;
-                new_clipboard_event(clipboard);
+            if let Err(e) = &result {
+                printlog!("(mirror) sync with {} failed: {}", provider_type, e);
             }
+            update_provider_status(&provider_type, result.err().map(|e| e.to_string())).await;
+        }
+    }
+
+    /// Marks the clipboards deleted on the sync provider and uploads the missing ones.
+    /// Mirrors are never pulled from, the sync provider decides what exists
+    async fn sync_mirror(
+        mirror: &dyn SyncProvider,
+        new_local_clipboards: &[FullClipboardDto],
+        deleted_clipboards: &[Uuid],
+    ) -> Result<(), SyncError> {
+        if !mirror.is_authenticated().await {
+            return Err(SyncError::Auth("not signed in to the mirror".to_string()));
+        }
+
+        let mut remote_clipboards = mirror.fetch_all_clipboards().await?;
+
+        for clippy in remote_clipboards.iter_mut() {
+            if clippy.deleted_at.is_none() && deleted_clipboards.contains(&clippy.id) {
+                *clippy = mirror.mark_for_deletion(clippy).await?;
+            }
+        }
+
+        let uploaded = mirror
+            .upload_new_clipboards(new_local_clipboards, &remote_clipboards)
+            .await?;
+        remote_clipboards.extend(uploaded);
+
+        let remote_settings = mirror.get_settings().await?;
+        mirror
+            .upsert_settings(&get_settings_for_sync(remote_settings).await?)
+            .await?;
+
+        mirror.collect_tombstones().await?;
+        mirror.cleanup_old_clipboards(&remote_clipboards).await?;
+        mirror.flush_manifest().await
+    }
+
+    /// Registers this device and applies revocations, returns the revoked device ids.
+    /// Fails once this device was revoked, so a lost device stops syncing
+    async fn sync_devices(provider: &dyn SyncProvider) -> Result<Vec<String>, SyncError> {
+        let device_id = get_device_id();
+        let mut devices = provider.get_devices().await?;
+
+        if devices
+            .get(device_id)
+            .is_some_and(|device| device.revoked_at.is_some())
+        {
+            return Err(SyncError::Auth("this device was revoked".to_string()));
+        }
+
+        let now = Utc::now().naive_utc();
+        let name = get_device_name();
+        let outdated = devices.get(device_id).is_none_or(|device| {
+            device.name != name
+                || now - device.last_seen_at > chrono::Duration::hours(SYNC_DEVICE_SEEN_HOURS)
+        });
+
+        if outdated {
+            let device = DeviceEntry {
+                name,
+                last_seen_at: now,
+                revoked_at: None,
+                seen_revision: 0,
+            };
+            provider.put_device(device_id, &device).await?;
+            devices.insert(device_id.to_string(), device);
+        }
+
+        let local_devices = upsert_devices_db(&devices).await?;
+
+        // revocations made here that did not reach the remote yet
+        let mut revoked_devices = Vec::new();
+        for (id, device) in local_devices {
+            if device.revoked_at.is_none() {
+                continue;
+            }
+
+            if devices
+                .get(&id)
+                .is_none_or(|remote| remote.revoked_at.is_none())
+            {
+                provider.put_device(&id, &device).await?;
+            }
+            revoked_devices.push(id);
+        }
+
+        delete_device_clipboards_db(revoked_devices.clone(), None).await?;
+
+        Ok(revoked_devices)
+    }
+
+    /// Notes in the registry that this device applied the deletions up to `seen_revision`,
+    /// tombstones are collected once every device got that far
+    async fn see_tombstones(
+        provider: &dyn SyncProvider,
+        seen_revision: u64,
+    ) -> Result<(), SyncError> {
+        let device_id = get_device_id();
+        let Some(device) = provider.get_devices().await?.remove(device_id) else {
+            return Ok(());
+        };
 
-            let new_local_clipboards = get_latest_syncable_cliboards_db().await?;
+        if device.seen_revision >= seen_revision {
+            return Ok(());
+        }
 
-            let new_remote_clipboards = provider
-                .upload_new_clipboards(&new_local_clipboards, &remote_clipboards)
-                .await?;
+        provider
+            .put_device(
+                device_id,
+                &DeviceEntry {
+                    seen_revision,
+                    ..device
+                },
+            )
+            .await
+    }
 
-            remote_clipboards.extend(new_remote_clipboards.into_iter());
+    /// Whether a downloaded clipboard replaces the local one, the higher revision wins and the
+    /// device id decides between edits of the same revision. A newer local clipboard is pushed
+    async fn remote_wins(clipboard: &FullClipboardDto) -> Result<bool, SyncError> {
+        let id = clipboard.clipboard.id;
+        let Some(local) = get_clipboard_revision_db(id).await? else {
+            return Ok(true);
+        };
+
+        let remote = SyncRevision {
+            revision: clipboard.clipboard.revision,
+            modified_by: clipboard.clipboard.modified_by.clone(),
+        };
+
+        if remote.is_concurrent(&local) {
+            printlog!(
+                "(conflict) clipboard {} changed on {:?} and {:?} at revision {}, keeping {:?}",
+                id,
+                local.modified_by,
+                remote.modified_by,
+                local.revision,
+                local.clone().max(remote.clone()).modified_by
+            );
+        } else if remote < local {
+            printlog!(
+                "(conflict) clipboard {} is at revision {} locally but {} remotely, keeping local",
+                id,
+                local.revision,
+                remote.revision
+            );
+        }
+
+        if remote < local {
+            queue_sync_changes(vec![id], SyncOperation::Star).await?;
+            return Ok(false);
+        }
 
-            provider.cleanup_old_clipboards(&remote_clipboards).await?;
+        Ok(true)
+    }
+
+    /// Pushes the due operations of the outbox, failed ones stay queued for a retry
+    async fn push_job() -> Result<(), SyncError> {
+        let entries = get_due_sync_outbox_db().await?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        update_sync_status(|status| status.running = true).await;
+
+        let provider = get_sync_provider().await?;
+        let mut remote_clipboards = None;
+        let mut last_error = None;
+
+        for entry in &entries {
+            match Self::push_operation(provider.as_ref(), entry, &mut remote_clipboards).await {
+                Ok(()) => complete_sync_outbox_db(entry).await?,
+                Err(e) => {
+                    let error = format!(
+                        "failed to push {} {}: {}",
+                        entry.operation, entry.clipboard_id, e
+                    );
+                    printlog!("(remote) {}", error);
+                    last_error = Some(error);
+                    fail_sync_outbox_db(entry, e.to_string()).await?
+                }
+            }
         }
 
+        // failed writes stay recorded and go out with the next push or pull
+        if let Err(e) = provider.flush_manifest().await {
+            printlog!("(remote) failed to write sync manifest: {}", e);
+        }
+
+        update_provider_status(&get_global_settings().sync_provider, last_error.clone()).await;
+        Self::push_mirrors(&entries).await;
+
+        update_sync_status(|status| {
+            status.running = false;
+            match last_error {
+                Some(error) => status.last_error = Some(error),
+                None => {
+                    status.last_success = Some(Utc::now().naive_utc());
+                    status.last_error = None;
+                }
+            }
+        })
+        .await;
+
         Ok(())
     }
 
+    /// Pushes the same operations to every mirror. The outbox only waits for the sync
+    /// provider, what a mirror missed is uploaded by the next pull
+    async fn push_mirrors(entries: &[sync_outbox::Model]) {
+        for (provider_type, mirror) in get_sync_mirrors().await {
+            let result = match mirror {
+                Ok(mirror) => Self::push_mirror(mirror.as_ref(), entries).await,
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = &result {
+                printlog!("(mirror) push to {} failed: {}", provider_type, e);
+            }
+            update_provider_status(&provider_type, result.err().map(|e| e.to_string())).await;
+        }
+    }
+
+    async fn push_mirror(
+        mirror: &dyn SyncProvider,
+        entries: &[sync_outbox::Model],
+    ) -> Result<(), SyncError> {
+        let mut remote_clipboards = None;
+        let mut last_error = None;
+
+        for entry in entries {
+            if let Err(e) = Self::push_operation(mirror, entry, &mut remote_clipboards).await {
+                last_error = Some(e);
+            }
+        }
+
+        mirror.flush_manifest().await?;
+
+        last_error.map_or(Ok(()), Err)
+    }
+
+    async fn push_operation(
+        provider: &dyn SyncProvider,
+        entry: &sync_outbox::Model,
+        remote_clipboards: &mut Option<Vec<Clippy>>,
+    ) -> Result<(), SyncError> {
+        let operation = SyncOperation::iter()
+            .find(|operation| operation.to_string() == entry.operation)
+            .ok_or_else(|| format!("unknown sync operation {}", entry.operation))?;
+
+        let clipboard = match operation {
+            SyncOperation::Delete => None,
+            _ => match get_clipboard_db(entry.clipboard_id).await {
+                Ok(clipboard) => Some(clipboard),
+                // deleted locally in the meantime, a queued deletion replaced this entry
+                Err(DbErr::RecordNotFound(_)) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        Self::push_change(
+            provider,
+            operation,
+            entry.clipboard_id,
+            clipboard.as_ref(),
+            remote_clipboards,
+        )
+        .await
+    }
+
+    /// Applies one local change to the remote, `clipboard` is the local clipboard for
+    /// anything but a deletion
+    pub async fn push_change(
+        provider: &dyn SyncProvider,
+        operation: SyncOperation,
+        id: Uuid,
+        clipboard: Option<&FullClipboardDto>,
+        remote_clipboards: &mut Option<Vec<Clippy>>,
+    ) -> Result<(), SyncError> {
+        if operation == SyncOperation::Delete {
+            // deletions need the provider ids, the listing is fetched once per push
+            if remote_clipboards.is_none() {
+                *remote_clipboards = Some(provider.fetch_all_clipboards().await?);
+            }
+
+            let remote = remote_clipboards
+                .iter()
+                .flatten()
+                .find(|clippy| clippy.id == id && clippy.deleted_at.is_none());
+
+            // never reached the remote or deleted there already
+            let Some(clippy) = remote else {
+                return Ok(());
+            };
+
+            printlog!("deleting remote clipboard: {:?}", clippy.id);
+            return provider.mark_for_deletion(clippy).await.map(|_| ());
+        }
+
+        let Some(clipboard) = clipboard else {
+            return Ok(());
+        };
+
+        match operation {
+            SyncOperation::Upload => provider.upload_clipboard(clipboard).await.map(|_| ()),
+            _ => provider.star_clipboard(clipboard).await,
+        }
+    }
+
+    /// Pushes the outbox, returns when the next failed operation should be retried
+    async fn push() -> Option<Instant> {
+        if let Err(e) = Self::push_job().await {
+            printlog!("sync push failed: {:?}", e);
+            let error = e.to_string();
+            update_provider_status(&get_global_settings().sync_provider, Some(error.clone())).await;
+            update_sync_status(|status| {
+                status.running = false;
+                status.last_error = Some(error);
+            })
+            .await;
+        }
+
+        match get_next_sync_outbox_attempt_db().await {
+            Ok(Some(next_attempt_at)) => Some(
+                Instant::now()
+                    + (next_attempt_at - Utc::now().naive_utc())
+                        .to_std()
+                        .unwrap_or_default(),
+            ),
+            Ok(None) => None,
+            Err(e) => {
+                printlog!("failed to read sync outbox: {:?}", e);
+                None
+            }
+        }
+    }
+
     pub async fn start(&mut self) {
         if self.is_running {
             printlog!("sync already running");
             return;
         }
 
-        let interval = if cfg!(debug_assertions) {
-            Duration::from_secs(10)
+        let min_interval = Duration::from_secs(if cfg!(debug_assertions) {
+            SYNC_PULL_INTERVAL_DEV
         } else {
-            Duration::from_secs(30)
-        };
+            SYNC_PULL_INTERVAL_PROD
+        });
+        let max_interval = Duration::from_secs(SYNC_PULL_INTERVAL_MAX);
 
         // Create a new sync task
         let handle = tokio::spawn(async move {
+            let mut interval = min_interval;
+            let mut next_pull = Instant::now();
+            // retry time of failed pushes, operations left from the last run go out first
+            let mut next_push = Some(Instant::now());
+
             loop {
-                // Run the sync job and wait for it to complete
-                if let Err(e) = Self::sync_job().await {
-                    printlog!("sync job failed: {:?}", e);
+                let push_at = next_push.unwrap_or(next_pull);
+
+                tokio::select! {
+                    // Local changes are pushed right away, debounced so bursts go out together
+                    _ = get_sync_push().notified() => {
+                        time::sleep(Duration::from_millis(SYNC_PUSH_DEBOUNCE_MS)).await;
+                        next_push = Self::push().await;
+                        continue;
+                    }
+                    _ = time::sleep_until(push_at), if next_push.is_some() => {
+                        next_push = Self::push().await;
+                        continue;
+                    }
+                    // "Sync now" pulls immediately and resets the backoff
+                    _ = get_sync_pull().notified() => interval = min_interval,
+                    _ = time::sleep_until(next_pull) => {}
                 }
 
-                time::sleep(interval).await;
+                next_push = Self::push().await;
+
+                update_sync_status(|status| status.running = true).await;
+                let result = Self::sync_job().await;
+                if let Err(e) = &result {
+                    printlog!("sync job failed: {}", e);
+                }
+                update_provider_status(
+                    &get_global_settings().sync_provider,
+                    result.as_ref().err().map(|e| e.to_string()),
+                )
+                .await;
+
+                // Idle pulls back off exponentially until something changes
+                interval = match result {
+                    Ok(true) => min_interval,
+                    _ => (interval * 2).min(max_interval),
+                };
+
+                update_sync_status(|status| {
+                    status.running = false;
+                    match result {
+                        Ok(_) => {
+                            status.last_success = Some(Utc::now().naive_utc());
+                            status.last_error = None;
+                        }
+                        Err(e) => status.last_error = Some(e.to_string()),
+                    }
+                })
+                .await;
+                next_pull = Instant::now() + interval;
             }
         });
 
@@ -97,6 +639,8 @@ impl SyncManager {
         if let Some(handle) = self.job_handle.take() {
             handle.abort();
             self.is_running = false;
+            // an aborted job does not get to reset it
+            update_sync_status(|status| status.running = false).await;
             printlog!("sync loop stopped");
         } else {
             printlog!("sync loop not running");
@@ -4,6 +4,15 @@ export enum WebWindow {
   Settings = "settings",
 }
 
+export enum SyncProviderType {
+  GoogleDrive = "google_drive",
+  WebDav = "webdav",
+  LocalFolder = "local_folder",
+  S3 = "s3",
+  Server = "server",
+  DryRun = "dry_run",
+}
+
 export enum FolderLocation {
   Database = "database",
   Config = "config",
@@ -60,4 +69,5 @@ export enum ClipboardType {
 export enum PasswordAction {
   Encrypt = "encrypt",
   Decrypt = "decrypt",
+  Recover = "recover",
 }
\ No newline at end of file
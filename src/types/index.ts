@@ -1,7 +1,7 @@
 import { IconTypes } from "solid-icons";
 import { DictionaryKey } from "../lib/i18n";
 import { ClippyPosition, Language, SettingsTabName, Tab, TabName } from "../utils/constants";
-import { ClipboardTextType, ClipboardType, HotkeyEvent } from "./enums";
+import { ClipboardTextType, ClipboardType, HotkeyEvent, SyncProviderType } from "./enums";
 
 export type DatabaseInfo = {
   records: number;
@@ -30,6 +30,7 @@ export type ClipboardWhere = {
   search?: string;
   star?: boolean;
   img?: boolean;
+  deviceId?: string;
 };
 
 export type SettingsTab = {
@@ -54,7 +55,12 @@ export interface ClipboardModel {
   id: number;
   types: ClipboardType[];
   star: boolean;
+  vault: boolean;
   created_at: string;
+  revision: number;
+  modified_by: string | null;
+  device_id: string | null;
+  sync_excluded: boolean;
 }
 
 export interface ClipboardTextModel {
@@ -123,18 +129,76 @@ export type Settings = {
   startup: boolean;
   sync: boolean;
   sync_limit: number;
-  sync_provider: "google_drive";
+  sync_provider: SyncProviderType;
   dark_mode: boolean;
   tooltip: boolean;
   display_scale: number;
   position: ClippyPosition;
   encryption: boolean;
   enryption_save_before_unlock: boolean;
+  encryption_keys: EncryptionKeys | null;
   max_file_size: number;
   max_image_size: number;
   max_text_size: number;
   max_rtf_size: number;
   max_html_size: number;
+  sync_revisions: Record<string, SyncRevision> | null;
+  sync_types: ClipboardType[];
+  sync_max_size: number;
+  sync_excluded_text_types: ClipboardTextType[];
+  sync_excluded_settings: string[];
+  sync_proxy_url: string | null;
+  sync_no_proxy: string | null;
+  sync_ca_bundle: string | null;
+  sync_mirrors: string[];
+  vault_check: string | null;
+  vault_salt: string | null;
+};
+
+export type SyncRevision = {
+  revision: number;
+  modified_by: string | null;
+};
+
+export type S3Credentials = {
+  endpoint: string;
+  bucket: string;
+  prefix: string;
+  region: string;
+  access_key: string;
+  secret_key: string;
+};
+
+export type SyncStatus = {
+  last_success: string | null;
+  last_error: string | null;
+  pending_uploads: number;
+  pending_downloads: number;
+  remote_count: number;
+  running: boolean;
+  sealed: boolean;
+  providers: SyncProviderStatus[];
+};
+
+export type SyncProviderStatus = {
+  provider: string;
+  mirror: boolean;
+  last_success: string | null;
+  last_error: string | null;
+};
+
+export type Device = {
+  id: string;
+  name: string;
+  last_seen_at: string;
+  revoked_at: string | null;
+  current: boolean;
+};
+
+export type EncryptionKeys = {
+  salt: string;
+  password: string;
+  recovery: string | null;
 };
 
 export type TextMatcher = {
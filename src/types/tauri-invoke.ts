@@ -1,4 +1,4 @@
-import { ClipboardResponse, ClipboardWhere, DatabaseInfo, Hotkey, Settings, TextMatcher } from ".";
+import { ClipboardResponse, ClipboardWhere, DatabaseInfo, Device, Hotkey, S3Credentials, Settings, SyncStatus, TextMatcher } from ".";
 import { ClipboardType, FolderLocation, PasswordAction, WebWindow } from "./enums";
 
 export enum InvokeCommand {
@@ -6,6 +6,7 @@ export enum InvokeCommand {
   GetClipboards = "get_clipboards",
   DeleteClipboard = "delete_clipboard",
   StarClipboard = "star_clipboard",
+  SyncExcludeClipboard = "sync_exclude_clipboard",
   CopyClipboard = "copy_clipboard",
   ClearClipboards = "clear_clipboards",
   SaveClipboardImage = "save_clipboard_image",
@@ -32,11 +33,43 @@ export enum InvokeCommand {
   // Sync commands
   SyncAuthenticateToggle = "sync_authenticate_toggle",
   SyncLimitChange = "sync_limit_change",
+  SyncNow = "sync_now",
+  GetSyncStatus = "get_sync_status",
+  SyncGoogleDriveDisconnect = "sync_google_drive_disconnect",
+  SyncNetworkChange = "sync_network_change",
+  SyncMirrorAdd = "sync_mirror_add",
+  SyncMirrorRemove = "sync_mirror_remove",
+  SyncWebdavConnect = "sync_webdav_connect",
+  SyncWebdavDisconnect = "sync_webdav_disconnect",
+  SyncLocalFolderConnect = "sync_local_folder_connect",
+  SyncLocalFolderDisconnect = "sync_local_folder_disconnect",
+  SyncS3Connect = "sync_s3_connect",
+  SyncS3Disconnect = "sync_s3_disconnect",
+  SyncServerConnect = "sync_server_connect",
+  SyncServerDisconnect = "sync_server_disconnect",
+  SyncDryRunConnect = "sync_dry_run_connect",
+  SyncDryRunDisconnect = "sync_dry_run_disconnect",
+  SyncPassphraseSet = "sync_passphrase_set",
+  SyncPassphraseRemove = "sync_passphrase_remove",
+
+  // Device commands
+  GetDevices = "get_devices",
+  RevokeDevice = "revoke_device",
 
   // Cipher commands
   EnableEncryption = "enable_encryption",
   DisableEncryption = "disable_encryption",
   PasswordUnlock = "password_unlock",
+  GenerateRecoveryKey = "generate_recovery_key",
+
+  // Vault commands
+  VaultUnlock = "vault_unlock",
+  VaultLock = "vault_lock",
+  VaultClipboard = "vault_clipboard",
+
+  // Job commands
+  CancelJob = "cancel_job",
+  ResumeJob = "resume_job",
 
   // App info commands
   GetAppVersion = "get_app_version",
@@ -59,6 +92,10 @@ export interface TauriInvokeCommands {
     args: { id: number; star: boolean };
     return: boolean;
   };
+  [InvokeCommand.SyncExcludeClipboard]: {
+    args: { id: number; excluded: boolean };
+    return: boolean;
+  };
   [InvokeCommand.CopyClipboard]: {
     args: { id: number; type?: ClipboardType | null };
     return: boolean;
@@ -139,6 +176,86 @@ export interface TauriInvokeCommands {
     args: { syncLimit: number };
     return: Settings;
   };
+  [InvokeCommand.SyncNow]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.GetSyncStatus]: {
+    args: undefined;
+    return: SyncStatus;
+  };
+  [InvokeCommand.SyncGoogleDriveDisconnect]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.SyncNetworkChange]: {
+    args: { proxyUrl: string | null; noProxy: string | null; caBundle: string | null };
+    return: void;
+  };
+  [InvokeCommand.SyncMirrorAdd]: {
+    args: { provider: string };
+    return: void;
+  };
+  [InvokeCommand.SyncMirrorRemove]: {
+    args: { provider: string };
+    return: void;
+  };
+  [InvokeCommand.SyncWebdavConnect]: {
+    args: { url: string; username: string; password: string };
+    return: void;
+  };
+  [InvokeCommand.SyncWebdavDisconnect]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.SyncLocalFolderConnect]: {
+    args: { path: string };
+    return: void;
+  };
+  [InvokeCommand.SyncLocalFolderDisconnect]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.SyncS3Connect]: {
+    args: { credentials: S3Credentials };
+    return: void;
+  };
+  [InvokeCommand.SyncS3Disconnect]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.SyncServerConnect]: {
+    args: { url: string; token: string };
+    return: void;
+  };
+  [InvokeCommand.SyncServerDisconnect]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.SyncDryRunConnect]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.SyncDryRunDisconnect]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.SyncPassphraseSet]: {
+    args: { passphrase: string; confirmPassphrase: string };
+    return: void;
+  };
+  [InvokeCommand.SyncPassphraseRemove]: {
+    args: { passphrase: string };
+    return: void;
+  };
+  [InvokeCommand.GetDevices]: {
+    args: undefined;
+    return: Device[];
+  };
+  [InvokeCommand.RevokeDevice]: {
+    args: { id: string };
+    return: void;
+  };
 
   // Cipher commands
   [InvokeCommand.EnableEncryption]: {
@@ -150,7 +267,35 @@ export interface TauriInvokeCommands {
     return: void;
   };
   [InvokeCommand.PasswordUnlock]: {
-    args: { password: string; action: PasswordAction };
+    args: { password: string; action: PasswordAction; newPassword?: string };
+    return: void;
+  };
+  [InvokeCommand.GenerateRecoveryKey]: {
+    args: { password: string };
+    return: string;
+  };
+
+  // Vault commands
+  [InvokeCommand.VaultUnlock]: {
+    args: { password: string; confirmPassword?: string };
+    return: void;
+  };
+  [InvokeCommand.VaultLock]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.VaultClipboard]: {
+    args: { id: number; vault: boolean };
+    return: void;
+  };
+
+  // Job commands
+  [InvokeCommand.CancelJob]: {
+    args: undefined;
+    return: void;
+  };
+  [InvokeCommand.ResumeJob]: {
+    args: undefined;
     return: void;
   };
 